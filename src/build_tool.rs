@@ -0,0 +1,95 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use crate::errors::ProjectError;
+
+pub struct BuildTool {
+    pub name: &'static str,
+    pub wrapper: &'static str,
+    pub executable: &'static str,
+}
+
+pub const MAVEN: BuildTool = BuildTool {
+    name: "maven",
+    wrapper: "mvnw",
+    executable: "mvn",
+};
+
+pub const GRADLE: BuildTool = BuildTool {
+    name: "gradle",
+    wrapper: "gradlew",
+    executable: "gradle",
+};
+
+impl BuildTool {
+    /// Find the executable of the build tool for the project at `root`.
+    ///
+    /// The executable from the configuration takes precedence, followed by a wrapper script
+    /// in the project root or one of its parents and lastly the executable from `PATH`.
+    /// Wrappers that can not be executed are skipped.
+    pub fn find(&self, root: &Path, configured: Option<&Path>) -> Result<PathBuf, ProjectError> {
+        if let Some(executable) = configured {
+            return if is_executable(executable) {
+                Ok(executable.to_path_buf())
+            } else {
+                search_path(executable).ok_or(ProjectError::ConfiguredBuildToolNotFound(
+                    self.name,
+                    executable.display().to_string(),
+                ))
+            };
+        }
+
+        root.ancestors()
+            .map(|directory| directory.join(self.wrapper))
+            .find(|wrapper| is_executable(wrapper))
+            .or_else(|| search_path(Path::new(self.executable)))
+            .ok_or(ProjectError::BuildToolNotFound(self.name))
+    }
+}
+
+/// Find an executable in the directories of `PATH`
+pub fn search_path(executable: &Path) -> Option<PathBuf> {
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|directory| directory.join(executable))
+        .find(|path| is_executable(path))
+}
+
+/// Whether `path` is a file that may be executed, e.g. a wrapper that was checked out
+/// without its execute permission can not
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{env, fs, os::unix::fs::PermissionsExt, process};
+
+    use super::MAVEN;
+
+    #[test]
+    fn wrappers_without_execute_permission_are_skipped() {
+        let root = env::temp_dir().join(format!("java-lsp-{}-wrapper", process::id()));
+        let project = root.join("project");
+        fs::create_dir_all(&project).unwrap();
+        for (directory, mode) in [(&root, 0o755), (&project, 0o644)] {
+            let wrapper = directory.join("mvnw");
+            fs::write(&wrapper, "#!/bin/sh\n").unwrap();
+            fs::set_permissions(&wrapper, fs::Permissions::from_mode(mode)).unwrap();
+        }
+
+        assert_eq!(MAVEN.find(&project, None).unwrap(), root.join("mvnw"));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,215 @@
+use serde::{Deserialize, Serialize};
+use tree_sitter::Node;
+
+use crate::{
+    generate::declarations,
+    resolver::Resolver,
+    source_edit::ancestor,
+    state::State,
+    tree_sitter::{TYPE_DECLARATIONS, collect_type_declarations, package_name},
+};
+
+/// Declarations that contain calls and can be called
+pub const METHODS: [&str; 3] = [
+    "method_declaration",
+    "constructor_declaration",
+    "compact_constructor_declaration",
+];
+
+/// Expressions that invoke a method or a constructor
+const INVOCATIONS: [&str; 3] = [
+    "method_invocation",
+    "object_creation_expression",
+    "explicit_constructor_invocation",
+];
+
+/// Method or constructor that is declared in the sources
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Method {
+    /// Type that declares the method, nested types are separated by `.`
+    pub owner: String,
+    /// Name of the method, constructors are named like their type
+    pub name: String,
+    pub parameters: usize,
+    pub is_varargs: bool,
+}
+
+impl Method {
+    /// Whether an invocation with `arguments` arguments can invoke the method
+    pub fn accepts(&self, arguments: usize) -> bool {
+        arguments == self.parameters || (self.is_varargs && arguments + 1 >= self.parameters)
+    }
+}
+
+/// Invocation of a method or constructor of the sources
+pub struct Invocation {
+    /// Type that declares the invoked method
+    pub owner: String,
+    pub name: String,
+    pub arguments: usize,
+    /// Byte range of the name of the invoked method, of the created type or of `this` or
+    /// `super`
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Invocation {
+    pub fn invokes(&self, method: &Method) -> bool {
+        self.owner == method.owner && self.name == method.name && method.accepts(self.arguments)
+    }
+}
+
+/// Invocation in the body of a method or constructor
+pub struct Call {
+    pub caller: Method,
+    pub invocation: Invocation,
+}
+
+/// Methods and constructors of all types that are declared in the compilation unit `root`
+pub fn methods<'tree>(root: Node<'tree>, source: &str) -> Vec<(Method, Node<'tree>)> {
+    let package = package_name(root, source.as_bytes()).unwrap_or_default();
+
+    collect_type_declarations(root, source.as_bytes(), package)
+        .into_iter()
+        .filter_map(|(owner, declaration)| {
+            Some((
+                owner,
+                declarations(declaration.child_by_field_name("body")?),
+            ))
+        })
+        .flat_map(|(owner, members)| {
+            members
+                .into_iter()
+                .filter_map(|member| Some((method(member, &owner, source)?, member)))
+                .collect::<Vec<(Method, Node)>>()
+        })
+        .collect()
+}
+
+/// Method or constructor that `declaration` declares in the type `owner`
+pub fn method(declaration: Node, owner: &str, source: &str) -> Option<Method> {
+    if !METHODS.contains(&declaration.kind()) {
+        return None;
+    }
+
+    let name = declaration.child_by_field_name("name")?;
+    // Compact constructors take the components of their record
+    let parameters = match declaration.kind() {
+        "compact_constructor_declaration" => {
+            ancestor(declaration, &["record_declaration"])?.child_by_field_name("parameters")?
+        }
+        _ => declaration.child_by_field_name("parameters")?,
+    };
+    let mut cursor = parameters.walk();
+    let parameters = parameters
+        .named_children(&mut cursor)
+        .filter(|parameter| matches!(parameter.kind(), "formal_parameter" | "spread_parameter"))
+        .collect::<Vec<Node>>();
+
+    Some(Method {
+        owner: owner.to_string(),
+        name: source[name.byte_range()].to_string(),
+        parameters: parameters.len(),
+        is_varargs: parameters
+            .last()
+            .is_some_and(|parameter| parameter.kind() == "spread_parameter"),
+    })
+}
+
+/// Invocations of methods of the sources in all methods and constructors of `root`
+pub fn calls(state: &State, root: Node, source: &str) -> Vec<Call> {
+    methods(root, source)
+        .into_iter()
+        .flat_map(|(caller, declaration)| outgoing_calls(state, root, source, caller, declaration))
+        .collect()
+}
+
+/// Invocations of methods and constructors of the sources in the body of `declaration`,
+/// which declares `caller`. Invocations in local and anonymous classes belong to their own
+/// methods.
+pub fn outgoing_calls(
+    state: &State,
+    root: Node,
+    source: &str,
+    caller: Method,
+    declaration: Node,
+) -> Vec<Call> {
+    fn visit<'tree>(node: Node<'tree>, invocations: &mut Vec<Node<'tree>>) {
+        if node.kind() == "class_body" || TYPE_DECLARATIONS.contains(&node.kind()) {
+            return;
+        }
+        if INVOCATIONS.contains(&node.kind()) {
+            invocations.push(node);
+        }
+
+        let mut cursor = node.walk();
+        for child in node.named_children(&mut cursor) {
+            visit(child, invocations);
+        }
+    }
+
+    let mut invocations = Vec::new();
+    if let Some(body) = declaration.child_by_field_name("body") {
+        visit(body, &mut invocations);
+    }
+
+    let Some(resolver) = Resolver::new(state, root, source, declaration) else {
+        return Vec::new();
+    };
+    invocations
+        .into_iter()
+        .filter_map(|invocation| resolve(state, &resolver, source, invocation))
+        .map(|invocation| Call {
+            caller: caller.clone(),
+            invocation,
+        })
+        .collect()
+}
+
+/// Invocation of a method or constructor of the sources by the node `invocation`
+pub fn invocation(state: &State, root: Node, source: &str, invocation: Node) -> Option<Invocation> {
+    resolve(
+        state,
+        &Resolver::new(state, root, source, invocation)?,
+        source,
+        invocation,
+    )
+}
+
+fn resolve(
+    state: &State,
+    resolver: &Resolver,
+    source: &str,
+    invocation: Node,
+) -> Option<Invocation> {
+    // Constructors are named like their type
+    let (owner, name, range) = match invocation.kind() {
+        "method_invocation" => {
+            let name = invocation.child_by_field_name("name")?;
+            (
+                resolver.invoked_type(invocation)?,
+                source[name.byte_range()].to_string(),
+                name.byte_range(),
+            )
+        }
+        _ => {
+            let owner = resolver.constructed_type(invocation)?;
+            let name = owner.rsplit('.').next()?.to_string();
+            let node = invocation
+                .child_by_field_name("type")
+                .or_else(|| invocation.child_by_field_name("constructor"))?;
+            (owner, name, node.byte_range())
+        }
+    };
+    // Only methods of the sources are part of the call hierarchy
+    state.source(&owner)?;
+    let arguments = invocation.child_by_field_name("arguments")?;
+
+    Some(Invocation {
+        owner,
+        name,
+        arguments: arguments.named_child_count(),
+        start: range.start,
+        end: range.end,
+    })
+}
@@ -0,0 +1,499 @@
+use std::{
+    ffi::OsStr,
+    fs::{self, File},
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use jclassfile::{class_file::ClassFlags, fields::FieldFlags, methods::MethodFlags};
+use lsp_types::Range;
+use ropey::Rope;
+use tree_sitter::Parser;
+use zip::ZipArchive;
+
+use crate::{
+    classfile::{Classfile, JavaType},
+    classpath_indexer, configuration, jdk,
+    position::{self, PositionEncoding},
+    tree_sitter::{collect_members, collect_type_declarations, package_name},
+};
+
+/// Directory of the data directory that sources of the classpath are extracted to
+const SOURCES_DIRECTORY: &str = "sources";
+/// Directory of the data directory for the stubs of classes without sources
+const STUBS_DIRECTORY: &str = "stubs";
+
+/// Source file of a top level class of the classpath in the data directory
+pub enum SourceFile {
+    /// The file exists and is up to date
+    Written(PathBuf),
+    /// The file is extracted from the entry `entry` of the archive `sources`
+    Extracted {
+        sources: PathBuf,
+        entry: String,
+        target: PathBuf,
+    },
+    /// The file is the stub `stub`
+    Generated { stub: String, target: PathBuf },
+}
+
+impl SourceFile {
+    /// Path of the file, it only exists once the file is written
+    pub fn path(&self) -> &Path {
+        match self {
+            SourceFile::Written(path)
+            | SourceFile::Extracted { target: path, .. }
+            | SourceFile::Generated { target: path, .. } => path,
+        }
+    }
+
+    /// Write the file unless it is up to date already
+    pub fn write(self) -> Option<PathBuf> {
+        match self {
+            SourceFile::Written(path) => Some(path),
+            SourceFile::Extracted {
+                sources,
+                entry,
+                target,
+            } => {
+                let mut zip = File::open(&sources)
+                    .map_err(zip::result::ZipError::from)
+                    .and_then(ZipArchive::new)
+                    .ok()?;
+                let mut file = zip.by_name(&entry).ok()?;
+                let mut content = Vec::with_capacity(file.size() as usize);
+                file.read_to_end(&mut content).ok()?;
+
+                fs::create_dir_all(target.parent()?).ok()?;
+                fs::write(&target, content).ok()?;
+                Some(target)
+            }
+            SourceFile::Generated { stub, target } => {
+                fs::create_dir_all(target.parent()?).ok()?;
+                fs::write(&target, stub).ok()?;
+                Some(target)
+            }
+        }
+    }
+}
+
+/// File for the source of the top level class `class` from `archive`, a jar or a module of
+/// the JDK. The source is extracted from the sources of the archive, without them a stub is
+/// generated from `class` and its `nested` classes.
+pub fn source_file(archive: &Path, class: &Classfile, nested: &[&Classfile]) -> Option<SourceFile> {
+    extracted_source(archive, &class.fqdn).or_else(|| stub_file(archive, class, nested))
+}
+
+/// Range of the name of the type `fqdn` or of its `member` in the source file `path`, nested
+/// types are separated by `.`
+pub fn declaration_range(
+    path: &Path,
+    fqdn: &str,
+    member: Option<&str>,
+    encoding: PositionEncoding,
+) -> Option<Range> {
+    let content = fs::read_to_string(path).ok()?;
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_java::LANGUAGE.into())
+        .ok()?;
+    let tree = parser.parse(&content, None)?;
+    let root = tree.root_node();
+    let source = content.as_bytes();
+
+    let package = package_name(root, source).unwrap_or_default();
+    let (_, declaration) = collect_type_declarations(root, source, package)
+        .into_iter()
+        .find(|(name, _)| name == fqdn)?;
+    let type_name = declaration.child_by_field_name("name")?;
+    let name = member
+        .and_then(|member| {
+            collect_members(declaration.child_by_field_name("body")?, source)
+                .into_iter()
+                .find(|declared| declared.name == member)
+        })
+        .map(|member| member.name_node)
+        .unwrap_or(type_name);
+
+    let rope = Rope::from_str(&content);
+    Some(Range::new(
+        position::position(&rope, name.start_byte(), encoding),
+        position::position(&rope, name.end_byte(), encoding),
+    ))
+}
+
+/// Source of the top level class `fqdn` from the sources that belong to `archive`, a file
+/// that was extracted before is reused while it is up to date
+fn extracted_source(archive: &Path, fqdn: &str) -> Option<SourceFile> {
+    let entry = format!("{}.java", fqdn.replace('.', "/"));
+
+    sources_archives(archive, &entry)
+        .into_iter()
+        .find_map(|(sources, entry)| {
+            let directory = if sources.ends_with("lib/src.zip") {
+                // The sources of the JDK are named after its home directory
+                sources.parent()?.parent()?.file_name()?
+            } else {
+                sources.file_stem()?
+            };
+            let target = configuration::data_directory()
+                .join(SOURCES_DIRECTORY)
+                .join(directory)
+                .join(&entry);
+            if is_up_to_date(&target, &sources) {
+                return Some(SourceFile::Written(target));
+            }
+
+            Some(SourceFile::Extracted {
+                sources,
+                entry,
+                target,
+            })
+        })
+}
+
+/// Archives that can contain the sources of `archive` together with the name of the source
+/// file `entry` in them. Maven keeps the sources jar next to the jar, Gradle in a sibling
+/// directory named after the hash of the sources jar. The modules of the JDK share its
+/// `src.zip`, which has a directory for each module.
+fn sources_archives(archive: &Path, entry: &str) -> Vec<(PathBuf, String)> {
+    let Some(stem) = archive_name(archive).and_then(|stem| stem.to_str()) else {
+        return Vec::new();
+    };
+
+    if !classpath_indexer::is_jar(archive) {
+        return jdk::home()
+            .map(|home| (home.join("lib").join("src.zip"), format!("{stem}/{entry}")))
+            .into_iter()
+            .filter(|(sources, _)| sources.is_file())
+            .collect();
+    }
+
+    let name = format!("{stem}-sources.jar");
+    let mut archives = archive
+        .parent()
+        .map(|directory| vec![directory.join(&name)])
+        .unwrap_or_default();
+    if let Some(Ok(siblings)) = archive
+        .parent()
+        .and_then(|directory| directory.parent())
+        .map(fs::read_dir)
+    {
+        archives.extend(
+            siblings
+                .filter_map(Result::ok)
+                .map(|sibling| sibling.path().join(&name)),
+        );
+    }
+
+    archives.dedup();
+    archives
+        .into_iter()
+        .filter(|sources| sources.is_file())
+        .map(|sources| (sources, entry.to_string()))
+        .collect()
+}
+
+/// Stub of the top level class `class` and its `nested` classes in the data directory, a
+/// stub that was written before is reused while it is up to date
+pub fn stub_file(archive: &Path, class: &Classfile, nested: &[&Classfile]) -> Option<SourceFile> {
+    let target = configuration::data_directory()
+        .join(STUBS_DIRECTORY)
+        .join(archive_name(archive)?)
+        .join(format!("{}.java", class.fqdn.replace('.', "/")));
+    if is_up_to_date(&target, archive) {
+        return Some(SourceFile::Written(target));
+    }
+
+    Some(SourceFile::Generated {
+        stub: stub(archive, class, nested),
+        target,
+    })
+}
+
+/// Name of `archive` without its extension, modules of the runtime image of the JDK have none
+fn archive_name(archive: &Path) -> Option<&OsStr> {
+    match jdk::image_module(archive) {
+        Some((_, module)) => Some(OsStr::new(module)),
+        None => archive.file_stem(),
+    }
+}
+
+fn is_up_to_date(target: &Path, archive: &Path) -> bool {
+    match (
+        classpath_indexer::modified(target),
+        classpath_indexer::modified(archive),
+    ) {
+        (Some(target), Some(archive)) => target >= archive,
+        _ => false,
+    }
+}
+
+/// Java source that declares `class` with the signatures of its members, the bodies of
+/// methods are left out
+fn stub(archive: &Path, class: &Classfile, nested: &[&Classfile]) -> String {
+    let (package, _) = class.fqdn.rsplit_once('.').unwrap_or_default();
+    let mut classes = Vec::new();
+    let declaration = declaration(class, nested, 0, &mut classes);
+
+    let mut imports = classes
+        .into_iter()
+        .filter(|fqdn| {
+            fqdn.rsplit_once('.')
+                .is_some_and(|(qualifier, _)| qualifier != package && qualifier != "java.lang")
+        })
+        .collect::<Vec<String>>();
+    imports.sort();
+    imports.dedup();
+
+    let mut stub = format!(
+        "// Generated from {} without sources\n\n",
+        archive.file_name().unwrap_or_default().display()
+    );
+    if !package.is_empty() {
+        stub.push_str(&format!("package {package};\n\n"));
+    }
+    if !imports.is_empty() {
+        for import in imports {
+            stub.push_str(&format!("import {import};\n"));
+        }
+        stub.push('\n');
+    }
+    stub.push_str(&declaration);
+
+    stub
+}
+
+/// Declaration of `class` indented by `level`, the classes that its types refer to are added
+/// to `classes`
+fn declaration(
+    class: &Classfile,
+    nested: &[&Classfile],
+    level: usize,
+    classes: &mut Vec<String>,
+) -> String {
+    let indent = "    ".repeat(level);
+    let member_indent = "    ".repeat(level + 1);
+    let name = class.fqdn.rsplit(['.', '$']).next().unwrap_or(&class.fqdn);
+    let source = class.source_declaration();
+
+    let is_interface = class.flags.contains(ClassFlags::ACC_INTERFACE);
+    let is_enum = class.flags.contains(ClassFlags::ACC_ENUM);
+    let is_annotation = class.flags.contains(ClassFlags::ACC_ANNOTATION);
+    // The implicit interface of annotations is left out
+    if !is_annotation {
+        classes.extend(source.classes);
+    }
+    let (keyword, mut modifiers) = if is_annotation {
+        ("@interface", Vec::new())
+    } else if is_interface {
+        ("interface", Vec::new())
+    } else if is_enum {
+        ("enum", Vec::new())
+    } else {
+        let mut modifiers = Vec::new();
+        if class.flags.contains(ClassFlags::ACC_ABSTRACT) {
+            modifiers.push("abstract");
+        }
+        if class.flags.contains(ClassFlags::ACC_FINAL) {
+            modifiers.push("final");
+        }
+        ("class", modifiers)
+    };
+    if class.flags.contains(ClassFlags::ACC_PUBLIC) {
+        modifiers.insert(0, "public");
+    }
+
+    let mut header = indent.clone();
+    for modifier in modifiers {
+        header.push_str(&format!("{modifier} "));
+    }
+    header.push_str(&format!("{keyword} {name}"));
+    if !source.type_parameters.is_empty() {
+        header.push_str(&format!("<{}>", source.type_parameters.join(", ")));
+    }
+    // Interfaces, enums and annotations have an implicit superclass, annotations also an
+    // implicit interface
+    let (superclass, interfaces) = match source.supertypes.split_first() {
+        Some((superclass, interfaces)) => (Some(superclass), interfaces),
+        None => (None, &[][..]),
+    };
+    if let Some(superclass) = superclass
+        && !is_interface
+        && !is_enum
+        && superclass != "Object"
+    {
+        header.push_str(&format!(" extends {superclass}"));
+    }
+    if !interfaces.is_empty() && !is_annotation {
+        let keyword = if is_interface {
+            "extends"
+        } else {
+            "implements"
+        };
+        header.push_str(&format!(" {keyword} {}", interfaces.join(", ")));
+    }
+
+    let mut members = Vec::new();
+    let constants = class
+        .fields
+        .iter()
+        .filter(|field| field.flags.contains(FieldFlags::ACC_ENUM))
+        .map(|field| field.name.as_str())
+        .collect::<Vec<&str>>();
+    if is_enum {
+        members.push(format!("{member_indent}{};", constants.join(", ")));
+    }
+
+    let fields = class
+        .fields
+        .iter()
+        .filter(|field| {
+            !field.flags.intersects(
+                FieldFlags::ACC_ENUM | FieldFlags::ACC_SYNTHETIC | FieldFlags::ACC_PRIVATE,
+            )
+        })
+        .map(|field| {
+            // Constants of interfaces are implicitly public, static and final
+            let modifiers = if is_interface {
+                String::new()
+            } else {
+                field_modifiers(&field.flags)
+            };
+            import(&field.field_type, classes);
+            format!(
+                "{member_indent}{modifiers}{} {};",
+                field.field_type, field.name
+            )
+        })
+        .collect::<Vec<String>>();
+    if !fields.is_empty() {
+        members.push(fields.join("\n"));
+    }
+
+    for method in class.methods.iter().filter(|method| {
+        method.name != "<clinit>"
+            && !method.flags.intersects(
+                MethodFlags::ACC_SYNTHETIC | MethodFlags::ACC_BRIDGE | MethodFlags::ACC_PRIVATE,
+            )
+    }) {
+        let is_abstract = method.flags.contains(MethodFlags::ACC_ABSTRACT);
+        let is_static = method.flags.contains(MethodFlags::ACC_STATIC);
+        let mut line = member_indent.clone();
+        if is_interface {
+            if !is_abstract && !is_static {
+                line.push_str("default ");
+            } else if is_static {
+                line.push_str("static ");
+            }
+        } else {
+            line.push_str(&method_modifiers(&method.flags));
+        }
+
+        let (type_parameters, parameters, return_type, exceptions) = match method.source_signature()
+        {
+            Some(signature) => {
+                classes.extend(signature.classes);
+                (
+                    signature.type_parameters,
+                    signature.parameters,
+                    signature.return_type,
+                    signature.exceptions,
+                )
+            }
+            None => {
+                method
+                    .parameters
+                    .iter()
+                    .for_each(|parameter| import(parameter, classes));
+                import(&method.return_type, classes);
+                (
+                    Vec::new(),
+                    method.parameters.iter().map(ToString::to_string).collect(),
+                    method.return_type.to_string(),
+                    Vec::new(),
+                )
+            }
+        };
+        if !type_parameters.is_empty() {
+            line.push_str(&format!("<{}> ", type_parameters.join(", ")));
+        }
+        if method.name == "<init>" {
+            line.push_str(name);
+        } else {
+            line.push_str(&format!("{return_type} {}", method.name));
+        }
+        let parameters = parameters
+            .iter()
+            .enumerate()
+            .map(|(index, parameter)| format!("{parameter} arg{index}"))
+            .collect::<Vec<String>>();
+        line.push_str(&format!("({})", parameters.join(", ")));
+        if !exceptions.is_empty() {
+            line.push_str(&format!(" throws {}", exceptions.join(", ")));
+        }
+        if is_abstract || method.flags.contains(MethodFlags::ACC_NATIVE) {
+            line.push(';');
+        } else {
+            line.push_str(" { /* compiled code */ }");
+        }
+        members.push(line);
+    }
+
+    // Anonymous and local classes are named like `Outer$1` or `Outer$1Local`
+    for inner in nested.iter().filter(|inner| {
+        inner
+            .fqdn
+            .strip_prefix(&class.fqdn)
+            .and_then(|rest| rest.strip_prefix('$'))
+            .is_some_and(|rest| {
+                !rest.contains('$') && !rest.starts_with(|c: char| c.is_ascii_digit())
+            })
+    }) {
+        members.push(declaration(inner, nested, level + 1, classes));
+    }
+
+    if members.is_empty() {
+        format!("{header} {{\n{indent}}}\n")
+    } else {
+        format!("{header} {{\n{}\n{indent}}}\n", members.join("\n\n"))
+    }
+}
+
+fn field_modifiers(flags: &FieldFlags) -> String {
+    [
+        (FieldFlags::ACC_PUBLIC, "public "),
+        (FieldFlags::ACC_PROTECTED, "protected "),
+        (FieldFlags::ACC_STATIC, "static "),
+        (FieldFlags::ACC_FINAL, "final "),
+        (FieldFlags::ACC_VOLATILE, "volatile "),
+        (FieldFlags::ACC_TRANSIENT, "transient "),
+    ]
+    .into_iter()
+    .filter_map(|(flag, modifier)| flags.contains(flag).then_some(modifier))
+    .collect()
+}
+
+fn method_modifiers(flags: &MethodFlags) -> String {
+    [
+        (MethodFlags::ACC_PUBLIC, "public "),
+        (MethodFlags::ACC_PROTECTED, "protected "),
+        (MethodFlags::ACC_ABSTRACT, "abstract "),
+        (MethodFlags::ACC_STATIC, "static "),
+        (MethodFlags::ACC_FINAL, "final "),
+        (MethodFlags::ACC_SYNCHRONIZED, "synchronized "),
+        (MethodFlags::ACC_NATIVE, "native "),
+    ]
+    .into_iter()
+    .filter_map(|(flag, modifier)| flags.contains(flag).then_some(modifier))
+    .collect()
+}
+
+/// Add the outermost class of `java_type` to `classes`
+fn import(java_type: &JavaType, classes: &mut Vec<String>) {
+    match java_type {
+        JavaType::Array(component) => import(component, classes),
+        JavaType::Class(fqdn) => classes.push(fqdn.split('$').next().unwrap_or(fqdn).to_string()),
+        _ => {}
+    }
+}
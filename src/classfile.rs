@@ -1,20 +1,93 @@
-use jclassfile::{class_file::ClassFile, constant_pool::ConstantPool, methods::MethodFlags};
+use std::{fmt, iter::Peekable, str::Chars};
+
+use jclassfile::{
+    attributes::Attribute,
+    class_file::{ClassFile, ClassFlags},
+    constant_pool::ConstantPool,
+    fields::FieldFlags,
+    methods::MethodFlags,
+};
 
 #[derive(Debug)]
 pub struct Classfile {
+    /// Binary name, nested classes are separated by `$`
     pub fqdn: String,
+    pub flags: ClassFlags,
+    pub deprecated: bool,
+    /// Names of the type parameters like `T` of `Comparable<T>`
+    pub type_parameters: Vec<String>,
+    /// Generic signature, or the superclass and interfaces in signature form if the class is
+    /// not generic
+    pub signature: String,
+    pub fields: Vec<Field>,
     pub methods: Vec<Method>,
     // TODO: support inner classes / records / interfaces  etc.
 }
 
+// TODO: Remove once flags and types are used
+#[allow(dead_code)]
+#[derive(Debug)]
+pub struct Field {
+    pub flags: FieldFlags,
+    pub deprecated: bool,
+    pub name: String,
+    pub field_type: JavaType,
+}
+
+#[allow(dead_code)]
 #[derive(Debug)]
 pub struct Method {
     pub flags: MethodFlags,
+    pub deprecated: bool,
     pub name: String,
     pub parameters: Vec<JavaType>,
+    /// Names of the parameters, empty if the class was compiled without them
+    pub parameter_names: Vec<String>,
     pub return_type: JavaType,
+    /// Type parameter that is returned, the erased `return_type` is its bound
+    pub return_type_variable: Option<String>,
+    /// Type parameters of the parameters, empty if the method has no generic signature
+    pub parameter_type_variables: Vec<Option<String>>,
+    /// The method declares type parameters of its own
+    pub is_generic: bool,
+    /// Checked exceptions that are declared to be thrown
+    pub exceptions: Vec<String>,
+    /// Generic signature, or the descriptor if the method is not generic
+    pub signature: String,
+}
+
+/// Supertype of a class with the type arguments that the class passes to it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Supertype {
+    pub fqdn: String,
+    /// Type arguments written like in sources, they can refer to type parameters of the class
+    pub arguments: Vec<String>,
 }
 
+/// Type parameters and supertypes of a class written like in sources
+#[derive(Debug)]
+pub struct SourceDeclaration {
+    /// Type parameters with their bounds like `T extends Comparable<? super T>`
+    pub type_parameters: Vec<String>,
+    /// The superclass followed by the interfaces, interfaces extend `Object` as well
+    pub supertypes: Vec<String>,
+    /// Classes that the types refer to, nested classes through their outermost class
+    pub classes: Vec<String>,
+}
+
+/// Signature of a method with the types written like in sources
+#[derive(Debug)]
+pub struct SourceSignature {
+    /// Type parameters with their bounds like `T extends Comparable<? super T>`
+    pub type_parameters: Vec<String>,
+    pub parameters: Vec<String>,
+    pub return_type: String,
+    pub exceptions: Vec<String>,
+    /// Classes that the types refer to, nested classes through their outermost class
+    pub classes: Vec<String>,
+}
+
+#[allow(dead_code)]
 #[derive(Debug)]
 pub enum JavaType {
     Void,
@@ -30,15 +103,286 @@ pub enum JavaType {
     Array(Box<JavaType>),
 }
 
+/// Types are written like in sources, with the simple names of classes
+impl fmt::Display for JavaType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JavaType::Void => write!(f, "void"),
+            JavaType::Char => write!(f, "char"),
+            JavaType::Float => write!(f, "float"),
+            JavaType::Double => write!(f, "double"),
+            JavaType::Byte => write!(f, "byte"),
+            JavaType::Short => write!(f, "short"),
+            JavaType::Int => write!(f, "int"),
+            JavaType::Long => write!(f, "long"),
+            JavaType::Boolean => write!(f, "boolean"),
+            JavaType::Class(fqdn) => {
+                let name = fqdn.rsplit('.').next().unwrap_or(fqdn);
+                write!(f, "{}", name.replace('$', "."))
+            }
+            JavaType::Array(component) => write!(f, "{component}[]"),
+        }
+    }
+}
+
 impl Classfile {
     pub fn new(bytes: &[u8]) -> Option<Self> {
         let class = jclassfile::class_file::parse(bytes).ok()?;
+        let generic_signature = signature(&class, class.attributes());
 
         Some(Self {
             fqdn: parse_class_name(&class)?.replace("/", "."),
+            flags: ClassFlags::from_bits_truncate(class.access_flags().bits()),
+            deprecated: is_deprecated(class.attributes()),
+            type_parameters: generic_signature
+                .as_deref()
+                .map(type_parameters)
+                .unwrap_or_default(),
+            signature: generic_signature.unwrap_or_else(|| supertypes_signature(&class)),
+            fields: parse_fields(&class)?,
             methods: parse_methods(&class)?,
         })
     }
+
+    pub fn source_declaration(&self) -> SourceDeclaration {
+        let mut reader = SignatureReader::new(&self.signature);
+        let type_parameters = reader.type_parameters();
+
+        let mut supertypes = Vec::new();
+        while reader.chars.next_if_eq(&'L').is_some() {
+            let Some((_, text, _)) = reader.class_type() else {
+                break;
+            };
+            supertypes.push(text);
+        }
+
+        SourceDeclaration {
+            type_parameters,
+            supertypes,
+            classes: reader.classes,
+        }
+    }
+
+    /// Superclass and interfaces, `Object` has none
+    pub fn supertypes(&self) -> Vec<Supertype> {
+        let mut reader = SignatureReader::new(&self.signature);
+        reader.type_parameters();
+
+        let mut supertypes = Vec::new();
+        while reader.chars.next_if_eq(&'L').is_some() {
+            let Some((fqdn, _, arguments)) = reader.class_type() else {
+                break;
+            };
+            supertypes.push(Supertype { fqdn, arguments });
+        }
+
+        supertypes
+    }
+}
+
+impl Method {
+    pub fn source_signature(&self) -> Option<SourceSignature> {
+        let mut reader = SignatureReader::new(&self.signature);
+        let type_parameters = reader.type_parameters();
+
+        reader.chars.next_if_eq(&'(')?;
+        let mut parameters = Vec::new();
+        while reader.chars.next_if_eq(&')').is_none() {
+            parameters.push(reader.java_type()?);
+        }
+        if self.flags.contains(MethodFlags::ACC_VARARGS)
+            && let Some(last) = parameters.last_mut()
+            && let Some(component) = last.strip_suffix("[]")
+        {
+            *last = format!("{component}...");
+        }
+        let return_type = reader.java_type()?;
+
+        let mut exceptions = Vec::new();
+        while reader.chars.next_if_eq(&'^').is_some() {
+            exceptions.push(reader.java_type()?);
+        }
+        // Signatures only list the exceptions if one of them is a type variable
+        if exceptions.is_empty() {
+            for exception in &self.exceptions {
+                reader.classes.push(exception.clone());
+                exceptions.push(
+                    exception
+                        .rsplit('.')
+                        .next()
+                        .unwrap_or(exception)
+                        .to_string(),
+                );
+            }
+        }
+
+        Some(SourceSignature {
+            type_parameters,
+            parameters,
+            return_type,
+            exceptions,
+            classes: reader.classes,
+        })
+    }
+}
+
+/// Reads the types of generic signatures and writes them like in sources, with the simple
+/// names of classes
+struct SignatureReader<'a> {
+    chars: Peekable<Chars<'a>>,
+    /// Classes that the types refer to
+    classes: Vec<String>,
+}
+
+impl<'a> SignatureReader<'a> {
+    fn new(signature: &'a str) -> Self {
+        Self {
+            chars: signature.chars().peekable(),
+            classes: Vec::new(),
+        }
+    }
+
+    /// Type parameters like `<T:Ljava/lang/Object;>`, bounds other than `Object` are kept
+    fn type_parameters(&mut self) -> Vec<String> {
+        let mut parameters = Vec::new();
+        if self.chars.next_if_eq(&'<').is_none() {
+            return parameters;
+        }
+
+        while self.chars.next_if_eq(&'>').is_none() {
+            let name = self
+                .chars
+                .by_ref()
+                .take_while(|c| *c != ':')
+                .collect::<String>();
+            // The class bound is empty if there are only interface bounds
+            let mut bounds = Vec::new();
+            if matches!(self.chars.peek(), Some('L' | 'T' | '[')) {
+                bounds.push(self.java_type());
+            }
+            while self.chars.next_if_eq(&':').is_some() {
+                bounds.push(self.java_type());
+            }
+            let Some(bounds) = bounds
+                .into_iter()
+                .filter(|bound| bound.as_deref() != Some("Object"))
+                .collect::<Option<Vec<String>>>()
+            else {
+                return parameters;
+            };
+
+            if bounds.is_empty() {
+                parameters.push(name);
+            } else {
+                parameters.push(format!("{name} extends {}", bounds.join(" & ")));
+            }
+        }
+
+        parameters
+    }
+
+    fn java_type(&mut self) -> Option<String> {
+        let java_type = match self.chars.next()? {
+            'B' => "byte".to_string(),
+            'C' => "char".to_string(),
+            'D' => "double".to_string(),
+            'F' => "float".to_string(),
+            'I' => "int".to_string(),
+            'J' => "long".to_string(),
+            'S' => "short".to_string(),
+            'Z' => "boolean".to_string(),
+            'V' => "void".to_string(),
+            'T' => self.chars.by_ref().take_while(|c| *c != ';').collect(),
+            '[' => format!("{}[]", self.java_type()?),
+            'L' => self.class_type()?.1,
+            _ => return None,
+        };
+
+        Some(java_type)
+    }
+
+    /// Binary name, source text and type arguments of a class type after its `L`, like
+    /// `java/util/Map$Entry<TK;TV;>;` or `java/util/Map<TK;TV;>.Entry<TK;TV;>;`
+    fn class_type(&mut self) -> Option<(String, String, Vec<String>)> {
+        let mut fqdn = String::new();
+        let mut text = String::new();
+        let mut arguments = Vec::new();
+        let mut name = String::new();
+
+        loop {
+            match self.chars.next()? {
+                c @ ('<' | '.' | ';') => {
+                    if !name.is_empty() {
+                        let simple_name = name.rsplit('/').next().unwrap_or(&name);
+                        if !fqdn.is_empty() {
+                            fqdn.push('$');
+                            text.push('.');
+                        }
+                        fqdn.push_str(&name.replace('/', "."));
+                        text.push_str(&simple_name.replace('$', "."));
+                        name.clear();
+                    }
+                    match c {
+                        '<' => {
+                            arguments = self.type_arguments()?;
+                            text.push_str(&format!("<{}>", arguments.join(", ")));
+                        }
+                        ';' => break,
+                        _ => {}
+                    }
+                }
+                c => name.push(c),
+            }
+        }
+
+        let outer = fqdn.split('$').next().unwrap_or(&fqdn).to_string();
+        if !self.classes.contains(&outer) {
+            self.classes.push(outer);
+        }
+        Some((fqdn, text, arguments))
+    }
+
+    fn type_arguments(&mut self) -> Option<Vec<String>> {
+        let mut arguments = Vec::new();
+        while self.chars.next_if_eq(&'>').is_none() {
+            let argument = match self.chars.peek()? {
+                '*' => {
+                    self.chars.next();
+                    "?".to_string()
+                }
+                '+' => {
+                    self.chars.next();
+                    format!("? extends {}", self.java_type()?)
+                }
+                '-' => {
+                    self.chars.next();
+                    format!("? super {}", self.java_type()?)
+                }
+                _ => self.java_type()?,
+            };
+            arguments.push(argument);
+        }
+
+        Some(arguments)
+    }
+}
+
+fn parse_fields(class: &ClassFile) -> Option<Vec<Field>> {
+    class
+        .fields()
+        .iter()
+        .map(|field| {
+            let descriptor = parse_string(class, field.descriptor_index() as usize)?;
+            let mut descriptor_iter = descriptor.chars();
+
+            Some(Field {
+                flags: FieldFlags::from_bits(field.access_flags().bits())?,
+                deprecated: is_deprecated(field.attributes()),
+                name: parse_string(class, field.name_index() as usize)?,
+                field_type: parse_field(descriptor_iter.next(), &mut descriptor_iter)?,
+            })
+        })
+        .collect()
 }
 
 fn parse_methods(class: &ClassFile) -> Option<Vec<Method>> {
@@ -60,11 +404,30 @@ fn parse_methods(class: &ClassFile) -> Option<Vec<Method>> {
             }
         }
 
+        let generic_signature = signature(class, method.attributes());
+        let flags = MethodFlags::from_bits(method.access_flags().bits())?;
         methods.push(Method {
-            flags: MethodFlags::from_bits(method.access_flags().bits())?,
+            signature: generic_signature.clone().unwrap_or(descriptor),
+            parameter_names: parameter_names(
+                class,
+                method.attributes(),
+                flags.contains(MethodFlags::ACC_STATIC),
+                &parameters,
+            ),
+            flags,
+            deprecated: is_deprecated(method.attributes()),
             name: parse_string(class, method.name_index() as usize)?,
             parameters,
             return_type: return_type?,
+            return_type_variable: generic_signature.as_deref().and_then(return_type_variable),
+            parameter_type_variables: generic_signature
+                .as_deref()
+                .map(parameter_type_variables)
+                .unwrap_or_default(),
+            is_generic: generic_signature
+                .as_deref()
+                .is_some_and(|signature| signature.starts_with('<')),
+            exceptions: exceptions(class, method.attributes()),
         });
     }
 
@@ -93,8 +456,200 @@ fn parse_field(c: Option<char>, chars: &mut impl Iterator<Item = char>) -> Optio
     }
 }
 
+/// Superclass and interfaces of a class that is not generic in the form of a signature
+fn supertypes_signature(class: &ClassFile) -> String {
+    let superclass = parse_class(class, class.super_class() as usize);
+    let interfaces = class
+        .interfaces()
+        .iter()
+        .filter_map(|index| parse_class(class, *index as usize));
+
+    superclass
+        .into_iter()
+        .chain(interfaces)
+        .map(|name| format!("L{name};"))
+        .collect()
+}
+
+/// Generic signature of a class or method, it is only written if it differs from the
+/// descriptor
+fn signature(class: &ClassFile, attributes: &[Attribute]) -> Option<String> {
+    attributes.iter().find_map(|attribute| match attribute {
+        Attribute::Signature { signature_index } => parse_string(class, *signature_index as usize),
+        _ => None,
+    })
+}
+
+/// Names of the type parameters of a class signature like
+/// `<K:Ljava/lang/Object;V::Ljava/lang/Comparable<TV;>;>Ljava/lang/Object;`
+fn type_parameters(signature: &str) -> Vec<String> {
+    let Some(rest) = signature.strip_prefix('<') else {
+        return Vec::new();
+    };
+    let mut parameters = Vec::new();
+    let mut name = String::new();
+    let mut in_name = true;
+    let mut depth = 0;
+
+    for c in rest.chars() {
+        match c {
+            '>' if depth == 0 => break,
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            // Interface bounds follow the class bound with another colon
+            ':' if in_name => {
+                if !name.is_empty() {
+                    parameters.push(std::mem::take(&mut name));
+                }
+                in_name = false;
+            }
+            ';' if depth == 0 => in_name = true,
+            c if in_name => name.push(c),
+            _ => {}
+        }
+    }
+
+    parameters
+}
+
+/// Type parameter that a method signature like `()TV;` returns
+fn return_type_variable(signature: &str) -> Option<String> {
+    let (_, return_type) = signature.rsplit_once(')')?;
+    let (variable, _) = return_type.strip_prefix('T')?.split_once(';')?;
+
+    Some(variable.to_string())
+}
+
+/// Type parameters of the parameters of a method signature like
+/// `(TE;Ljava/util/Collection<+TE;>;I)V`, `None` for parameters of other types
+fn parameter_type_variables(signature: &str) -> Vec<Option<String>> {
+    let Some((_, parameters)) = signature.split_once('(') else {
+        return Vec::new();
+    };
+    let mut variables = Vec::new();
+    let mut chars = parameters.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            ')' => break,
+            '[' => {
+                // Arrays of type parameters are erased to arrays of their bounds
+                while chars.next_if_eq(&'[').is_some() {}
+                skip_type(chars.next(), &mut chars);
+                variables.push(None);
+            }
+            'T' => variables.push(Some(chars.by_ref().take_while(|c| *c != ';').collect())),
+            c => {
+                skip_type(Some(c), &mut chars);
+                variables.push(None);
+            }
+        }
+    }
+
+    variables
+}
+
+/// Skip the rest of the type signature that starts with `c`
+fn skip_type(c: Option<char>, chars: &mut impl Iterator<Item = char>) {
+    if !matches!(c, Some('L' | 'T')) {
+        return;
+    }
+
+    let mut depth = 0;
+    for c in chars {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            ';' if depth == 0 => return,
+            _ => {}
+        }
+    }
+}
+
+fn exceptions(class: &ClassFile, attributes: &[Attribute]) -> Vec<String> {
+    attributes
+        .iter()
+        .find_map(|attribute| match attribute {
+            Attribute::Exceptions {
+                exception_index_table,
+            } => Some(
+                exception_index_table
+                    .iter()
+                    .filter_map(|index| parse_class(class, *index as usize))
+                    .map(|name| name.replace(['/', '$'], "."))
+                    .collect(),
+            ),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+/// Names of the parameters from the `MethodParameters` attribute of `javac -parameters`, or
+/// from the local variables of the debug information of `javac -g`
+fn parameter_names(
+    class: &ClassFile,
+    attributes: &[Attribute],
+    is_static: bool,
+    parameters: &[JavaType],
+) -> Vec<String> {
+    let declared = attributes.iter().find_map(|attribute| match attribute {
+        Attribute::MethodParameters { parameters } => parameters
+            .iter()
+            .map(|parameter| parse_string(class, parameter.name_index() as usize))
+            .collect::<Option<Vec<String>>>(),
+        _ => None,
+    });
+    if let Some(names) = declared.filter(|names| names.len() == parameters.len()) {
+        return names;
+    }
+
+    let Some(variables) = attributes.iter().find_map(|attribute| match attribute {
+        Attribute::Code { attributes, .. } => {
+            attributes.iter().find_map(|attribute| match attribute {
+                Attribute::LocalVariableTable {
+                    local_variable_table,
+                } => Some(local_variable_table),
+                _ => None,
+            })
+        }
+        _ => None,
+    }) else {
+        return Vec::new();
+    };
+
+    // Parameters are the first local variables, after `this` of instance methods, and long
+    // and double values take two slots
+    let mut slot = if is_static { 0 } else { 1 };
+    parameters
+        .iter()
+        .map(|parameter| {
+            let name = variables
+                .iter()
+                .find(|variable| variable.index() == slot && variable.start_pc() == 0)
+                .and_then(|variable| parse_string(class, variable.name_index() as usize));
+            slot += match parameter {
+                JavaType::Long | JavaType::Double => 2,
+                _ => 1,
+            };
+            name
+        })
+        .collect::<Option<Vec<String>>>()
+        .unwrap_or_default()
+}
+
+/// javac marks everything that is annotated with `@Deprecated` with an attribute
+fn is_deprecated(attributes: &[Attribute]) -> bool {
+    attributes
+        .iter()
+        .any(|attribute| matches!(attribute, Attribute::Deprecated))
+}
+
 fn parse_class_name(class: &ClassFile) -> Option<String> {
-    let name_index = match class.constant_pool().get(class.this_class() as usize)? {
+    parse_class(class, class.this_class() as usize)
+}
+
+fn parse_class(class: &ClassFile, index: usize) -> Option<String> {
+    let name_index = match class.constant_pool().get(index)? {
         ConstantPool::Class { name_index } => Some(*name_index as usize),
         _ => None,
     }?;
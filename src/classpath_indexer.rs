@@ -1,32 +1,87 @@
-use crate::classfile::Classfile;
-use std::{
-    collections::HashMap,
-    fs::{self, File},
-    io::Read,
-};
+use crate::{classfile::Classfile, jdk, jimage::Image};
+use std::{fs::File, io::Read, path::Path, time::SystemTime};
 use zip::ZipArchive;
 
-pub fn index(classpath: &str) -> HashMap<String, Classfile> {
-    let mut classes = HashMap::new();
-
-    // for path in classpath.split(":") {
-    //     if fs::exists(path).unwrap_or(false) {
-    //         if path.ends_with(".jar") {
-    //             let mut zip = ZipArchive::new(File::open(path).unwrap()).unwrap();
-    //             for index in 0..zip.len() {
-    //                 if let Ok(mut file) = zip.by_index(index)
-    //                     && file.name().ends_with(".class")
-    //                 {
-    //                     let mut bytes = vec![0; file.size() as usize];
-    //                     file.read_exact(&mut bytes).unwrap();
-    //                     if let Some(class) = Classfile::new(&bytes) {
-    //                         classes.insert(class.fqdn.to_owned(), class);
-    //                     }
-    //                 }
-    //             }
-    //         }
-    //     }
-    // }
+/// Packages of the modules of the JDK that contain the public API
+const JDK_API_PACKAGES: [&str; 2] = ["java/", "javax/"];
+
+/// Jar whose classes are part of the index
+pub struct IndexedJar {
+    pub modified: Option<SystemTime>,
+    pub classes: Vec<String>,
+}
+
+pub fn is_jar(path: &Path) -> bool {
+    path.extension().map(|ext| ext == "jar").unwrap_or(false)
+}
+
+/// Time `path` was modified, modules of the runtime image of the JDK share the one of the image
+pub fn modified(path: &Path) -> Option<SystemTime> {
+    let path = jdk::image_module(path).map_or(path, |(image, _)| image);
+    path.metadata()
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Index the classes of a jar or a module of the JDK, only the packages of the public API are
+/// read from the latter
+pub fn index(path: &Path) -> Vec<Classfile> {
+    let is_api = |name: &str| {
+        JDK_API_PACKAGES
+            .iter()
+            .any(|package| name.starts_with(package))
+    };
+
+    if let Some((image, module)) = jdk::image_module(path) {
+        index_image(image, module, is_api)
+    } else if path.extension().map(|ext| ext == "jmod").unwrap_or(false) {
+        index_archive(path, |name| {
+            name.strip_prefix("classes/").is_some_and(is_api)
+        })
+    } else {
+        index_archive(path, |_| true)
+    }
+}
+
+fn index_image(path: &Path, module: &str, filter: impl Fn(&str) -> bool) -> Vec<Classfile> {
+    let Some(image) = Image::open(path) else {
+        eprintln!("Could not index {}", path.display());
+        return Vec::new();
+    };
+
+    image
+        .resources()
+        .iter()
+        .filter(|resource| {
+            resource.module == module && resource.path.ends_with(".class") && filter(&resource.path)
+        })
+        .filter_map(|resource| Classfile::new(&image.read(resource)?))
+        .collect()
+}
+
+fn index_archive(path: &Path, filter: impl Fn(&str) -> bool) -> Vec<Classfile> {
+    let mut classes = Vec::new();
+    let Ok(mut zip) = File::open(path)
+        .map_err(zip::result::ZipError::from)
+        .and_then(ZipArchive::new)
+    else {
+        eprintln!("Could not index {}", path.display());
+        return classes;
+    };
+
+    for index in 0..zip.len() {
+        if let Ok(mut file) = zip.by_index(index)
+            && file.name().ends_with(".class")
+            && filter(file.name())
+        {
+            let mut bytes = Vec::with_capacity(file.size() as usize);
+            if file.read_to_end(&mut bytes).is_ok()
+                && let Some(class) = Classfile::new(&bytes)
+            {
+                classes.push(class);
+            }
+        }
+    }
 
     classes
 }
@@ -1,9 +1,19 @@
-use std::{collections::HashMap, path::Path, process::Command};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
 
 use lsp_types::Url;
 use walkdir::{DirEntry, WalkDir};
 
-use crate::{gradle, maven};
+use crate::{
+    configuration::Configuration,
+    errors::ProjectError,
+    gradle, maven,
+    project::{self, Module, Project},
+};
 
 enum ProjectKind {
     Maven,
@@ -11,109 +21,229 @@ enum ProjectKind {
     Javac,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Default)]
 pub struct CompileError {
     pub row: u32,
+    /// Column in UTF-16 code units, like javac counts them
     pub column: u32,
     pub error_message: String,
+    /// Key of the javac diagnostic like `cant.resolve`, only known for some errors
+    pub code: Option<&'static str>,
+    /// Lines like `symbol: class Foo` that javac prints below the source line
+    pub details: Vec<(String, String)>,
+}
+
+/// Keys of javac diagnostics and the start of their English messages
+const ERROR_CODES: [(&str, &str); 4] = [
+    ("cant.resolve", "cannot find symbol"),
+    ("unreported.exception", "unreported exception "),
+    (
+        "does.not.override.abstract",
+        " does not override abstract method ",
+    ),
+    ("prob.found.req", "incompatible types: "),
+];
+
+/// Key of the diagnostic with the English `message`
+fn error_code(message: &str) -> Option<&'static str> {
+    ERROR_CODES
+        .iter()
+        .find(|(_, text)| {
+            if text.starts_with(' ') {
+                message.contains(text)
+            } else {
+                message.starts_with(text)
+            }
+        })
+        .map(|(code, _)| *code)
 }
 
 pub struct Compiler {
-    project_kind: ProjectKind,
-    classpath: String,
+    modules: Vec<Module>,
+    build_errors: HashMap<Url, Vec<CompileError>>,
 }
 
 impl Compiler {
-    pub fn new() -> Self {
-        let project_kind = determine_project_kind();
-        let classpath = determine_classpath(&project_kind);
+    pub fn new(configuration: &Configuration) -> Result<Self, ProjectError> {
+        let root = std::env::current_dir().unwrap_or_default();
+        let project_kind = determine_project_kind(&root);
+
+        let project = determine_project(&project_kind, &root, configuration)?;
 
+        Ok(Compiler {
+            modules: project::sort_by_dependencies(project.modules),
+            build_errors: project.errors,
+        })
+    }
+
+    /// Compiler that compiles all sources of the working directory with plain javac
+    pub fn without_build_tool() -> Self {
         Compiler {
-            project_kind,
-            classpath,
+            modules: vec![javac_module(&std::env::current_dir().unwrap_or_default())],
+            build_errors: HashMap::new(),
+        }
+    }
+
+    /// All classpath entries of all modules without duplicates
+    pub fn classpath(&self) -> Vec<PathBuf> {
+        let mut classpath: Vec<PathBuf> = Vec::new();
+
+        for path in self.modules.iter().flat_map(|module| &module.classpath) {
+            if !classpath.contains(path) {
+                classpath.push(path.clone());
+            }
         }
+
+        classpath
+    }
+
+    /// Source roots of all modules, including the directories of generated sources
+    pub fn source_roots(&self) -> Vec<PathBuf> {
+        self.modules
+            .iter()
+            .flat_map(|module| {
+                module
+                    .source_roots
+                    .iter()
+                    .chain([&module.generated_sources])
+            })
+            .cloned()
+            .collect()
     }
 
-    pub fn classpath(&self) -> &str {
-        &self.classpath
+    pub fn generated_sources(&self) -> Vec<PathBuf> {
+        self.modules
+            .iter()
+            .map(|module| module.generated_sources.clone())
+            .collect()
     }
 
+    pub fn build_errors(&self) -> &HashMap<Url, Vec<CompileError>> {
+        &self.build_errors
+    }
+
+    /// Compile all modules, every module is compiled after the modules it depends on.
+    ///
+    /// Problems of the build files are part of the returned errors.
     pub fn compile(&self, force_all: bool) -> HashMap<Url, Vec<CompileError>> {
-        let mut errors: HashMap<Url, Vec<CompileError>> = HashMap::new();
-        let class_file_directory = self.class_file_target_directory();
-
-        // TODO: Support annotation processing
-        let output = Command::new("javac")
-            .arg("--class-path")
-            .arg(&self.classpath)
-            .arg("-d")
-            .arg(&class_file_directory)
-            // .arg("-Xlint:all")
-            // .arg("-Xdoclint:all")
-            .arg("-Xdiags:verbose")
-            .args(find_files_to_compile(class_file_directory, force_all))
-            .output();
-
-        if let Ok(output) = output
-            && let Ok(stderr) = str::from_utf8(&output.stderr)
-        {
-            let mut lines = stderr.lines();
-            while let Some(line) = lines.next() {
-                if !line
-                    .chars()
-                    .next()
-                    .map(|c| c.is_whitespace())
-                    .unwrap_or(false)
-                    && line.contains(".java:")
-                {
-                    let mut parts = line.split(":");
-                    lines.next();
-
-                    if let Some(path) = parts.nth(0)
-                        && let Ok(url) = Url::parse(&(String::from("file://") + path))
-                        && let Some(column_line) = lines.next()
-                    {
-                        let compilation_error = CompileError {
-                            row: parts.nth(0).unwrap_or("0").parse::<u32>().unwrap_or(0),
-                            column: column_line
-                                .split_once("^")
-                                .unwrap_or_default()
-                                .0
-                                .chars()
-                                .take_while(|&c| c.is_whitespace())
-                                .count() as u32,
-                            error_message: parts.last().unwrap_or("").to_string(),
-                        };
-
-                        if let Some(error) = errors.get_mut(&url) {
-                            error.push(compilation_error);
-                        } else {
-                            errors.insert(url, vec![compilation_error]);
-                        }
-                    }
-                }
+        let mut errors = self.build_errors.clone();
+
+        for module in &self.modules {
+            for (url, module_errors) in compile_module(module, force_all) {
+                errors.entry(url).or_default().extend(module_errors);
             }
-        } else {
-            eprintln!("Compilation was unsuccessfull");
         }
 
         errors
     }
+}
+
+fn compile_module(module: &Module, force_all: bool) -> HashMap<Url, Vec<CompileError>> {
+    let mut errors: HashMap<Url, Vec<CompileError>> = HashMap::new();
+    let files = find_files_to_compile(module, force_all);
+
+    if files.is_empty() {
+        return errors;
+    }
+
+    let mut command = Command::new("javac");
+    command
+        .current_dir(&module.root)
+        .arg("--class-path")
+        .arg(project::join_paths(&module.classpath))
+        .arg("-d")
+        .arg(&module.output_directory);
+
+    if let Some(release) = &module.release {
+        command.arg("--release").arg(release);
+    }
+
+    // Without an explicit processor path javac discovers processors on the classpath
+    if !module.processor_path.is_empty() {
+        command
+            .arg("-processorpath")
+            .arg(project::join_paths(&module.processor_path));
+    }
+
+    // javac does not create the directory for generated sources on its own
+    if fs::create_dir_all(&module.generated_sources).is_ok() {
+        command.arg("-s").arg(&module.generated_sources);
+    }
+
+    let output = command
+        // .arg("-Xlint:all")
+        // .arg("-Xdoclint:all")
+        .arg("-Xdiags:verbose")
+        // Messages are classified by their text, which is localized
+        .arg("-J-Duser.language=en")
+        .arg("-J-Duser.country=US")
+        .args(files)
+        .output();
+
+    if let Ok(output) = output
+        && let Ok(stderr) = str::from_utf8(&output.stderr)
+    {
+        let mut lines = stderr.lines().peekable();
+        while let Some(line) = lines.next() {
+            if !line
+                .chars()
+                .next()
+                .map(|c| c.is_whitespace())
+                .unwrap_or(false)
+                && line.contains(".java:")
+            {
+                let mut parts = line.splitn(4, ":");
+                lines.next();
+
+                if let Some(path) = parts.next()
+                    && let Ok(url) = Url::parse(&(String::from("file://") + path))
+                    && let Some(column_line) = lines.next()
+                {
+                    let row = parts.next().unwrap_or("0").parse::<u32>().unwrap_or(0);
+                    // The message itself can contain colons
+                    let error_message = parts.nth(1).unwrap_or("").trim().to_string();
+                    let mut details = Vec::new();
+                    while let Some(detail) = lines.next_if(|line| line.starts_with("  ")) {
+                        if let Some((key, value)) = detail.split_once(':') {
+                            details.push((key.trim().to_string(), value.trim().to_string()));
+                        }
+                    }
+
+                    let compilation_error = CompileError {
+                        row,
+                        column: column_line
+                            .split_once("^")
+                            .unwrap_or_default()
+                            .0
+                            .chars()
+                            .take_while(|&c| c.is_whitespace())
+                            .count() as u32,
+                        code: error_code(&error_message),
+                        error_message,
+                        details,
+                    };
 
-    fn class_file_target_directory(&self) -> String {
-        match self.project_kind {
-            ProjectKind::Maven | ProjectKind::Javac => String::from("target/classes"),
-            ProjectKind::Gradle => String::from("build/classes"),
+                    if let Some(error) = errors.get_mut(&url) {
+                        error.push(compilation_error);
+                    } else {
+                        errors.insert(url, vec![compilation_error]);
+                    }
+                }
+            }
         }
+    } else {
+        eprintln!("Compilation was unsuccessfull");
     }
+
+    errors
 }
 
-fn determine_project_kind() -> ProjectKind {
-    if Path::new("./pom.xml").exists() {
+fn determine_project_kind(root: &Path) -> ProjectKind {
+    if root.join("pom.xml").exists() {
         ProjectKind::Maven
-    } else if Path::new("./build.gradle").exists()
-        || Path::new("./build.gradle.kt").exists()
-        || Path::new("./build.gradle.kts").exists()
+    } else if root.join("build.gradle").exists()
+        || root.join("build.gradle.kt").exists()
+        || root.join("build.gradle.kts").exists()
     {
         ProjectKind::Gradle
     } else {
@@ -121,16 +251,36 @@ fn determine_project_kind() -> ProjectKind {
     }
 }
 
-fn determine_classpath(kind: &ProjectKind) -> String {
+fn determine_project(
+    kind: &ProjectKind,
+    root: &Path,
+    configuration: &Configuration,
+) -> Result<Project, ProjectError> {
     match kind {
-        ProjectKind::Maven => maven::generate_claspath(),
-        ProjectKind::Gradle => gradle::generate_claspath(),
-        ProjectKind::Javac => String::new(),
+        ProjectKind::Maven => maven::project(root, configuration),
+        ProjectKind::Gradle => gradle::project(root, configuration),
+        ProjectKind::Javac => Ok(Project::new(vec![javac_module(root)])),
     }
 }
 
-fn find_files_to_compile(class_file_directory: String, force_all: bool) -> Vec<String> {
-    let class_files = WalkDir::new(class_file_directory)
+fn javac_module(root: &Path) -> Module {
+    Module {
+        name: root
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        root: root.to_path_buf(),
+        source_roots: vec![root.to_path_buf()],
+        output_directory: root.join("target/classes"),
+        classpath: vec![root.join("target/classes")],
+        release: None,
+        processor_path: Vec::new(),
+        generated_sources: root.join("target/generated-sources/annotations"),
+    }
+}
+
+fn find_files_to_compile(module: &Module, force_all: bool) -> Vec<String> {
+    let class_files = WalkDir::new(&module.output_directory)
         .into_iter()
         .filter_map(Result::ok)
         .filter(|entry| {
@@ -143,12 +293,15 @@ fn find_files_to_compile(class_file_directory: String, force_all: bool) -> Vec<S
         })
         .collect::<Vec<DirEntry>>();
 
-    // TODO: Should we handle this? If yes then it should probably be done in the earlier stages
-    WalkDir::new(std::env::current_dir().unwrap_or_default())
-        .into_iter()
+    module
+        .source_roots
+        .iter()
+        .flat_map(WalkDir::new)
         .filter_map(Result::ok)
         .filter(|entry| {
-            entry.path().is_file()
+            // Generated sources are written by javac itself
+            !entry.path().starts_with(&module.generated_sources)
+                && entry.path().is_file()
                 && entry
                     .path()
                     .extension()
@@ -160,7 +313,7 @@ fn find_files_to_compile(class_file_directory: String, force_all: bool) -> Vec<S
         .collect()
 }
 
-fn should_build_file(class_files: &Vec<DirEntry>, java_file: &DirEntry) -> bool {
+fn should_build_file(class_files: &[DirEntry], java_file: &DirEntry) -> bool {
     let mut should_build = true;
     let java_path = java_file.path().with_extension("");
 
@@ -1,21 +1,202 @@
+use serde::Deserialize;
 use std::env;
 use std::fs::File;
 use std::io::Write;
 use std::path::PathBuf;
 
+/// Configuration of the server, provided by the client as `initializationOptions`
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Configuration {
+    pub maven: MavenConfiguration,
+    pub gradle: BuildToolConfiguration,
+    pub formatter: FormatterConfiguration,
+    pub inlay_hints: InlayHintsConfiguration,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct MavenConfiguration {
+    /// Executable that is used instead of the wrapper or the executable from `PATH`
+    pub executable: Option<PathBuf>,
+    pub resolver: MavenResolver,
+}
+
+/// How the dependencies of maven projects are resolved
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MavenResolver {
+    /// Resolve dependencies from the local repository without running maven
+    #[default]
+    Native,
+    /// Let maven resolve the dependencies, this might download artifacts
+    Maven,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct BuildToolConfiguration {
+    /// Executable that is used instead of the wrapper or the executable from `PATH`
+    pub executable: Option<PathBuf>,
+}
+
+/// Kinds of inlay hints that are shown, all of them are enabled by default
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct InlayHintsConfiguration {
+    /// Names of the parameters for literal arguments
+    pub parameter_names: bool,
+    /// Inferred types of variables that are declared with `var`
+    pub variable_types: bool,
+    /// Inferred types of lambda parameters without declared types
+    pub lambda_parameter_types: bool,
+}
+
+impl Default for InlayHintsConfiguration {
+    fn default() -> Self {
+        Self {
+            parameter_names: true,
+            variable_types: true,
+            lambda_parameter_types: true,
+        }
+    }
+}
+
+/// Style of the formatter, a preset whose settings can be overridden one by one
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct FormatterConfiguration {
+    pub preset: FormatterPreset,
+    /// Spaces per level of indentation
+    pub indent_width: Option<usize>,
+    /// Additional spaces for lines that continue a statement or declaration
+    pub continuation_indent_width: Option<usize>,
+    /// Lines that are longer are wrapped if possible
+    pub line_width: Option<usize>,
+    pub brace_style: Option<BraceStyle>,
+    pub sort_imports: Option<bool>,
+    pub import_order: ImportOrder,
+}
+
+/// How imports are grouped and sorted, by default all static imports are followed by
+/// all other imports as in the Google Java Style
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ImportOrder {
+    /// Prefixes of the groups of imports in their order, e.g. `["java", "javax", "*"]`.
+    /// `*` matches imports without a group, they are last if it is missing.
+    pub groups: Vec<String>,
+    pub static_imports: StaticImports,
+}
+
+/// Position of the block of static imports
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StaticImports {
+    #[default]
+    First,
+    Last,
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FormatterPreset {
+    /// Google Java Style
+    #[default]
+    Google,
+    /// Google Java Style with the indentation of the Android Open Source Project
+    Aosp,
+}
+
+/// Where the opening brace of a body is placed
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BraceStyle {
+    /// At the end of the line of the declaration or statement
+    EndOfLine,
+    /// On a line of its own
+    NextLine,
+}
+
+impl Configuration {
+    pub fn from_initialization_options(options: Option<serde_json::Value>) -> Self {
+        options
+            .and_then(|options| serde_json::from_value(options).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// Files that define the structure or classpath of a project
+pub const BUILD_FILE_PATTERNS: [&str; 7] = [
+    "**/pom.xml",
+    "**/build.gradle",
+    "**/build.gradle.kts",
+    "**/settings.gradle",
+    "**/settings.gradle.kts",
+    "**/gradle/wrapper/gradle-wrapper.properties",
+    "**/.mvn/wrapper/maven-wrapper.properties",
+];
+
 pub const GRADLE_CLASSPATH_TASK_NAME: &str = "generateClasspath";
 pub const GRADLE_INIT_SCRIPT_FILE_NAME: &str = "gradle-init-script.gradle";
+/// Prefix of every line that the init script prints for a source set
+pub const GRADLE_SOURCE_SET_PREFIX: &str = "java-lsp-source-set:";
 pub const GRADLE_INIT_SCRIPT: &str = r#"
 gradle.projectsEvaluated {
     allprojects {
         if (plugins.hasPlugin('java')) {
             tasks.register("generateClasspath") {
                 doLast {
-                    def mainClasspath = configurations.findByName('compileClasspath') ?: files()
-                    def testClasspath = configurations.findByName('testCompileClasspath') ?: files()
-                    def classpath = (mainClasspath.files + testClasspath.files).toSet()
+                    def java = project.extensions.getByType(JavaPluginExtension)
+                    def toolchainVersion = java.toolchain.languageVersion.getOrNull()
+                    def javaVersion = toolchainVersion != null
+                        ? toolchainVersion.asInt().toString()
+                        : java.targetCompatibility.majorVersion
+
+                    def outputDirectories = { sourceSet ->
+                        def directories = [sourceSet.java.destinationDirectory.get().asFile]
+                        directories.addAll(sourceSet.output.classesDirs.files)
+                        directories.unique()
+                    }
+
+                    // Jars of other projects of the build are replaced by the output of the
+                    // source set that they are built from, so that modules can be matched
+                    def compileClasspath = { sourceSet ->
+                        def projectOutputs = [:]
+                        def configuration = project.configurations[sourceSet.compileClasspathConfigurationName]
+                        configuration.incoming.artifacts.artifacts.each { artifact ->
+                            def id = artifact.id.componentIdentifier
+                            if (id instanceof org.gradle.api.artifacts.component.ProjectComponentIdentifier) {
+                                def dependency = rootProject.findProject(id.projectPath)
+                                if (dependency != null && dependency.plugins.hasPlugin('java')) {
+                                    def dependencySourceSet = dependency.sourceSets.find { candidate ->
+                                        dependency.tasks.findByName(candidate.jarTaskName)?.archiveFile?.get()?.asFile == artifact.file
+                                    }
+                                    if (dependencySourceSet != null) {
+                                        projectOutputs[artifact.file] = outputDirectories(dependencySourceSet)
+                                    }
+                                }
+                            }
+                        }
+                        sourceSet.compileClasspath.files.collectMany { file -> projectOutputs[file] ?: [file] }
+                    }
+
+                    project.sourceSets.each { sourceSet ->
+                        def compileTask = tasks.findByName(sourceSet.compileJavaTaskName)
+                        def generatedSources = compileTask?.options?.generatedSourceOutputDirectory?.getOrNull()
 
-                    println classpath.collect { it.absolutePath }.join(File.pathSeparator)
+                        println "java-lsp-source-set:" + groovy.json.JsonOutput.toJson([
+                            project: project.path,
+                            sourceSet: sourceSet.name,
+                            projectDirectory: project.projectDir.absolutePath,
+                            sourceDirectories: sourceSet.java.srcDirs.collect { it.absolutePath },
+                            outputDirectories: outputDirectories(sourceSet).collect { it.absolutePath },
+                            compileClasspath: compileClasspath(sourceSet).unique().collect { it.absolutePath },
+                            annotationProcessorPath: sourceSet.annotationProcessorPath.files.collect { it.absolutePath },
+                            generatedSourcesDirectory: generatedSources?.asFile?.absolutePath,
+                            javaVersion: javaVersion,
+                        ])
+                    }
                 }
             }
         }
@@ -1,31 +1,77 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fs};
 
 use crossbeam_channel::Sender;
 use lsp_server::Message;
 use lsp_types::{
-    Diagnostic, Position, PublishDiagnosticsParams, Range, Url,
-    notification::{Notification, PublishDiagnostics},
+    Diagnostic, MessageType, NumberOrString, Position, PublishDiagnosticsParams, Range,
+    ShowMessageParams, Url,
+    notification::{Notification, PublishDiagnostics, ShowMessage},
 };
 
-use crate::compiler::CompileError;
+use crate::{
+    compiler::CompileError,
+    position::{PositionEncoding, line_content},
+};
 
 pub fn publish_compile_errors(
     sender: &Sender<Message>,
+    encoding: PositionEncoding,
+    versions: &HashMap<Url, i32>,
     compile_errors: HashMap<Url, Vec<CompileError>>,
 ) {
     for (uri, errors) in compile_errors {
+        // Clients can drop diagnostics of outdated versions of open documents
+        let version = versions.get(&uri).copied();
+        // Columns only have to be converted if the client does not use UTF-16 as well
+        let content = (encoding != PositionEncoding::Utf16 && !errors.is_empty())
+            .then(|| uri.to_file_path().ok())
+            .flatten()
+            .and_then(|path| fs::read_to_string(path).ok());
+
         let errors = errors
             .iter()
             .map(|error| {
-                let position = Position::new(error.row.saturating_sub(1), error.column);
-                Diagnostic::new_simple(Range::new(position, position), error.error_message.clone())
+                let row = error.row.saturating_sub(1);
+                let column = content
+                    .as_ref()
+                    .and_then(|content| content.lines().nth(row as usize))
+                    .and_then(|line| convert_column(line, error.column, encoding))
+                    .unwrap_or(error.column);
+                let position = Position::new(row, column);
+                let mut message = error.error_message.clone();
+                for (key, value) in &error.details {
+                    message.push_str(&format!("\n{key}: {value}"));
+                }
+
+                Diagnostic {
+                    range: Range::new(position, position),
+                    code: error
+                        .code
+                        .map(|code| NumberOrString::String(code.to_string())),
+                    source: Some("javac".to_string()),
+                    message,
+                    // Quick fixes read the details and the version of the document that the
+                    // positions belong to from the diagnostics the client sends back
+                    data: (!error.details.is_empty() || version.is_some()).then(|| {
+                        serde_json::Value::Object(
+                            error
+                                .details
+                                .iter()
+                                .map(|(key, value)| (key.clone(), value.clone().into()))
+                                .chain(
+                                    version.map(|version| ("version".to_string(), version.into())),
+                                )
+                                .collect(),
+                        )
+                    }),
+                    ..Default::default()
+                }
             })
             .collect::<Vec<Diagnostic>>();
 
-        if let Ok(params) = serde_json::to_value(PublishDiagnosticsParams::new(
-            uri, errors, // TODO: Do we care about this?
-            None,
-        )) {
+        if let Ok(params) =
+            serde_json::to_value(PublishDiagnosticsParams::new(uri, errors, version))
+        {
             // TODO: This can fail and try_send will return the method that
             // could not be sent. We could probably try to send it again
             // later
@@ -36,3 +82,20 @@ pub fn publish_compile_errors(
         }
     }
 }
+
+/// Convert a UTF-16 column of `line` to `encoding`
+fn convert_column(line: &str, column: u32, encoding: PositionEncoding) -> Option<u32> {
+    let line = line_content(line);
+    let offset = PositionEncoding::Utf16.byte_offset(line, column)?;
+
+    Some(encoding.len(&line[..offset]) as u32)
+}
+
+pub fn show_message(sender: &Sender<Message>, typ: MessageType, message: String) {
+    if let Ok(params) = serde_json::to_value(ShowMessageParams { typ, message }) {
+        let _ = sender.try_send(Message::Notification(lsp_server::Notification {
+            method: ShowMessage::METHOD.to_string(),
+            params,
+        }));
+    }
+}
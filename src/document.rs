@@ -1,22 +1,45 @@
-use std::ops::Range;
+use std::fs;
 
-use lsp_types::{Position, Url};
+use lsp_types::{
+    FoldingRange, Position, Range, SelectionRange, SemanticToken, SemanticTokens, TextEdit, Url,
+};
 use ropey::Rope;
-use tree_sitter::{InputEdit, Parser, Point, Tree};
+use tree_sitter::{InputEdit, Node, Parser, Point, Tree};
 
+use crate::configuration::ImportOrder;
 use crate::errors::DocumentError;
-use crate::tree_sitter::{collect_imports, find_node_by_point};
+use crate::folding;
+use crate::formatter::{self, Replacement, Style};
+use crate::imports::{self, Resolution, Scope};
+use crate::organize_imports;
+use crate::position::{self, PositionEncoding};
+use crate::semantic_tokens;
+use crate::symbols::TypeIndex;
+use crate::tree_sitter::find_node_by_point;
 
 pub struct Document {
     version: i32,
+    /// Edits might have been missed, the content can differ from the one of the client
+    dirty: bool,
     content: Rope,
+    /// Content as one string for the analyses, edits are applied to it in place
+    text: String,
     tree: Tree,
     parser: Parser,
+    encoding: PositionEncoding,
+    /// Semantic tokens that were sent last, deltas are computed against them
+    semantic_tokens: Option<SemanticTokens>,
+    semantic_tokens_id: u32,
     pub uri: Url,
 }
 
 impl Document {
-    pub fn new(uri: Url, content: &str) -> Result<Self, DocumentError> {
+    pub fn new(
+        uri: Url,
+        version: i32,
+        content: &str,
+        encoding: PositionEncoding,
+    ) -> Result<Self, DocumentError> {
         let mut parser = Parser::new();
         parser.set_language(&tree_sitter_java::LANGUAGE.into())?;
         let tree = parser
@@ -27,8 +50,13 @@ impl Document {
             uri,
             parser,
             tree,
-            version: 0,
+            version,
+            dirty: false,
             content: Rope::from_str(content),
+            text: text(content),
+            encoding,
+            semantic_tokens: None,
+            semantic_tokens_id: 0,
         })
     }
 
@@ -38,81 +66,516 @@ impl Document {
         end: Position,
         updated_content: &str,
     ) -> Result<(), DocumentError> {
-        let start_index = self.position_index(start);
-        let end_index = self.position_index(end);
-
-        if start_index < end_index {
-            self.content.remove(start_index..end_index);
-            if !updated_content.is_empty() {
-                self.content.insert(start_index, updated_content);
-            }
-            self.tree.edit(&InputEdit {
-                start_byte: start_index,
-                old_end_byte: end_index,
-                new_end_byte: start_index + updated_content.len(),
-                start_position: Point::new(0, 0),
-                old_end_position: Point::new(0, 0),
-                new_end_position: Point::new(0, 0),
-            });
+        let start_index = self.byte_index(start)?;
+        let end_index = self.byte_index(end)?;
+        let (start_index, end_index) = if start_index < end_index {
+            (start_index, end_index)
         } else {
-            self.content.remove(end_index..start_index);
-            if !updated_content.is_empty() {
-                self.content.insert(end_index, updated_content);
-            }
-            self.tree.edit(&InputEdit {
-                start_byte: end_index,
-                old_end_byte: start_index,
-                new_end_byte: end_index + updated_content.len(),
-                start_position: Point::new(0, 0),
-                old_end_position: Point::new(0, 0),
-                new_end_position: Point::new(0, 0),
-            });
+            (end_index, start_index)
+        };
+
+        // Points of the old content have to be taken before the rope is modified
+        let start_position = self.byte_point(start_index);
+        let old_end_position = self.byte_point(end_index);
+
+        let start_char = self.content.byte_to_char(start_index);
+        let end_char = self.content.byte_to_char(end_index);
+        self.content.remove(start_char..end_char);
+        if !updated_content.is_empty() {
+            self.content.insert(start_char, updated_content);
         }
 
+        self.text
+            .replace_range(start_index..end_index, updated_content);
+
+        let new_end_index = start_index + updated_content.len();
+        self.tree.edit(&InputEdit {
+            start_byte: start_index,
+            old_end_byte: end_index,
+            new_end_byte: new_end_index,
+            start_position,
+            old_end_position,
+            new_end_position: self.byte_point(new_end_index),
+        });
+
+        // Feed the chunks of the rope to the parser, so that the content is never copied
+        let content = &self.content;
         self.tree = self
             .parser
-            // TODO: This seems to be expensive, can it be done better?
-            .parse(self.content.bytes().collect::<Vec<u8>>(), Some(&self.tree))
+            .parse_with_options(
+                &mut |index, _| chunk(content, index),
+                Some(&self.tree),
+                None,
+            )
             .ok_or(DocumentError::TreeSitterParseError(self.uri.to_string()))?;
 
         Ok(())
     }
 
+    /// Replace the whole content, this brings a dirty document back in sync
+    pub fn replace(&mut self, content: &str) -> Result<(), DocumentError> {
+        self.tree = self
+            .parser
+            .parse(content, None)
+            .ok_or(DocumentError::TreeSitterParseError(self.uri.to_string()))?;
+        self.content = Rope::from_str(content);
+        self.text = text(content);
+        self.dirty = false;
+
+        Ok(())
+    }
+
     pub fn should_update(&self, version: i32) -> bool {
         self.version < version
     }
 
-    pub fn symbol_at_position(&self, position: Position) -> Option<&str> {
-        let imports = collect_imports(self.tree.walk())
-            .iter()
-            .map(|node| self.slice_by_range(node.byte_range()))
-            .collect::<Vec<&str>>();
-        let node = find_node_by_point(
-            self.tree.walk(),
-            Point::new(position.line as usize, position.character as usize),
-        );
+    pub fn version(&self) -> i32 {
+        self.version
+    }
 
-        match node.kind() {
-            "type_identifier" => {
-                imports.iter().find(|item| {
-                    item.ends_with(&format!(".{}", self.slice_by_range(node.byte_range())))
-                }).map(|element| *element)
-            }
-            "identifier" => {
-                None
+    pub fn set_version(&mut self, version: i32) {
+        self.version = version;
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Whether the content is the one of the file on disk
+    pub fn is_saved(&self) -> bool {
+        self.uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .is_some_and(|content| self.content == content.as_str())
+    }
+
+    /// Resolve the name at `position` to the type or member it refers to
+    pub fn resolve(&self, position: Position, index: &impl TypeIndex) -> Option<Resolution> {
+        let source = self.text();
+        let node = find_node_by_point(self.tree.walk(), self.point(position).ok()?);
+        let scope = Scope::new(self.tree.root_node(), source.as_bytes());
+
+        imports::resolve(node, source.as_bytes(), &scope, index)
+    }
+
+    pub fn folding_ranges(&self) -> Vec<FoldingRange> {
+        folding::folding_ranges(self.tree.root_node(), &self.content)
+    }
+
+    /// Edits that format the whole document or only the lines of `range`
+    pub fn format(&self, range: Option<Range>, style: &Style) -> Vec<TextEdit> {
+        let range = match range {
+            Some(range) => match (self.byte_index(range.start), self.byte_index(range.end)) {
+                (Ok(start), Ok(end)) => Some((start, end)),
+                _ => return Vec::new(),
             },
-            _ => None
+            None => None,
+        };
+
+        self.format_bytes(range, style)
+    }
+
+    /// Edits that format the body that was closed by typing `}` or the statement that was
+    /// ended by typing `;` before `position`
+    pub fn format_on_type(&self, position: Position, typed: &str, style: &Style) -> Vec<TextEdit> {
+        let Some(index) = self
+            .byte_index(position)
+            .ok()
+            .and_then(|index| index.checked_sub(typed.len()))
+        else {
+            return Vec::new();
+        };
+        let Some(node) = self
+            .tree
+            .root_node()
+            .descendant_for_byte_range(index, index + typed.len())
+            .filter(|node| node.kind() == typed)
+            .and_then(|node| node.parent())
+        else {
+            return Vec::new();
+        };
+
+        self.format_bytes(Some((node.start_byte(), node.end_byte())), style)
+    }
+
+    /// Edit that removes unused imports, adds missing ones and sorts them
+    pub fn organize_imports(
+        &self,
+        order: &ImportOrder,
+        index: &impl TypeIndex,
+    ) -> Option<TextEdit> {
+        let replacement =
+            organize_imports::organize(self.tree.root_node(), self.text(), order, index)?;
+
+        Some(self.text_edit(replacement))
+    }
+
+    /// Run `analysis` on the syntax tree, the source and the byte index of `position`
+    pub fn analyze<T>(
+        &self,
+        position: Position,
+        analysis: impl FnOnce(Node, &str, usize) -> Option<T>,
+    ) -> Option<T> {
+        let offset = self.byte_index(position).ok()?;
+
+        analysis(self.tree.root_node(), self.text(), offset)
+    }
+
+    /// Run `analysis` on the syntax tree, the source and the byte indices of `range`
+    pub fn analyze_range<T>(
+        &self,
+        range: Range,
+        analysis: impl FnOnce(Node, &str, usize, usize) -> Option<T>,
+    ) -> Option<T> {
+        let start = self.byte_index(range.start).ok()?;
+        let end = self.byte_index(range.end).ok()?;
+
+        analysis(self.tree.root_node(), self.text(), start, end.max(start))
+    }
+
+    fn format_bytes(&self, range: Option<(usize, usize)>, style: &Style) -> Vec<TextEdit> {
+        formatter::format(self.tree.root_node(), self.text(), style)
+            .into_iter()
+            .filter(|replacement| {
+                range.is_none_or(|(start, end)| replacement.end >= start && replacement.start < end)
+            })
+            .map(|replacement| self.text_edit(replacement))
+            .collect()
+    }
+
+    pub fn text_edit(&self, replacement: Replacement) -> TextEdit {
+        TextEdit::new(
+            self.range(replacement.start, replacement.end),
+            replacement.text,
+        )
+    }
+
+    /// Range of the bytes between `start` and `end`
+    pub fn range(&self, start: usize, end: usize) -> Range {
+        Range::new(
+            position::position(&self.content, start, self.encoding),
+            position::position(&self.content, end, self.encoding),
+        )
+    }
+
+    /// Ranges of the node at `position` and all of its ancestors, the innermost one first
+    pub fn selection_range(&self, position: Position) -> Option<SelectionRange> {
+        let point = self.point(position).ok()?;
+        let mut node = self
+            .tree
+            .root_node()
+            .descendant_for_point_range(point, point);
+
+        let mut ranges: Vec<Range> = Vec::new();
+        while let Some(current) = node {
+            let range = Range::new(
+                position::position(&self.content, current.start_byte(), self.encoding),
+                position::position(&self.content, current.end_byte(), self.encoding),
+            );
+            // Nodes that span the same text as their child do not extend the selection
+            if ranges.last() != Some(&range) {
+                ranges.push(range);
+            }
+            node = current.parent();
+        }
+
+        ranges.into_iter().rev().fold(None, |parent, range| {
+            Some(SelectionRange {
+                range,
+                parent: parent.map(Box::new),
+            })
+        })
+    }
+
+    /// Classify the names of the document
+    pub fn semantic_tokens(&self, index: &impl TypeIndex) -> Vec<SemanticToken> {
+        semantic_tokens::tokens(
+            self.tree.root_node(),
+            self.text(),
+            &self.content,
+            self.encoding,
+            index,
+        )
+    }
+
+    /// Classify the names of the document that start within `range`
+    pub fn semantic_tokens_in_range(
+        &self,
+        range: Range,
+        index: &impl TypeIndex,
+    ) -> Vec<SemanticToken> {
+        semantic_tokens::tokens_in_range(
+            self.tree.root_node(),
+            self.text(),
+            &self.content,
+            self.encoding,
+            range,
+            index,
+        )
+    }
+
+    /// Remember `data` as the last tokens that were sent to the client under a new id
+    pub fn store_semantic_tokens(&mut self, data: Vec<SemanticToken>) -> SemanticTokens {
+        self.semantic_tokens_id += 1;
+        let tokens = SemanticTokens {
+            result_id: Some(self.semantic_tokens_id.to_string()),
+            data,
+        };
+        self.semantic_tokens = Some(tokens.clone());
+
+        tokens
+    }
+
+    /// Tokens that were sent last if their id is `result_id`
+    pub fn previous_semantic_tokens(&self, result_id: &str) -> Option<&[SemanticToken]> {
+        self.semantic_tokens
+            .as_ref()
+            .filter(|tokens| tokens.result_id.as_deref() == Some(result_id))
+            .map(|tokens| tokens.data.as_slice())
+    }
+
+    /// Content of the document, the string is shared by all requests until the next edit
+    fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Get the byte index of a [`Position`] in the negotiated encoding
+    fn byte_index(&self, position: Position) -> Result<usize, DocumentError> {
+        position::byte_index(&self.content, position, self.encoding)
+    }
+
+    /// Get the tree sitter [`Point`] (column in bytes) of a [`Position`]
+    fn point(&self, position: Position) -> Result<Point, DocumentError> {
+        Ok(self.byte_point(self.byte_index(position)?))
+    }
+
+    /// Get the tree sitter [`Point`] of a byte index
+    fn byte_point(&self, index: usize) -> Point {
+        let line = self.content.byte_to_line(index);
+
+        Point::new(line, index - self.content.line_to_byte(line))
+    }
+}
+
+/// String for the analyses with room for edits, so that typing does not reallocate it
+fn text(content: &str) -> String {
+    let mut text = String::with_capacity(content.len() + content.len() / 8 + 1024);
+    text.push_str(content);
+    text
+}
+
+/// Rest of the chunk of `content` that contains the byte `index`, empty past the end
+fn chunk(content: &Rope, index: usize) -> &[u8] {
+    if index >= content.len_bytes() {
+        return &[];
+    }
+
+    let (chunk, chunk_index, _, _) = content.chunk_at_byte(index);
+    &chunk.as_bytes()[index - chunk_index..]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        cell::Cell,
+    };
+
+    use lsp_types::{FoldingRange, Position, Url};
+    use tree_sitter::{Node, Parser};
+
+    use super::Document;
+    use crate::position::{self, PositionEncoding};
+
+    thread_local! {
+        /// Size of the largest allocation of the thread since it was last reset
+        static LARGEST_ALLOCATION: Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// Allocator of the tests that records the largest allocation, so that tests can check
+    /// that a document is not copied as a whole
+    struct Counting;
+
+    unsafe impl GlobalAlloc for Counting {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            record(layout.size());
+            unsafe { System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            unsafe { System.dealloc(ptr, layout) }
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            record(new_size);
+            unsafe { System.realloc(ptr, layout, new_size) }
         }
     }
 
-    fn slice_by_range(&self, range: Range<usize>) -> &str {
-        self.content.byte_slice(range).as_str().unwrap()
+    fn record(size: usize) {
+        let _ = LARGEST_ALLOCATION.try_with(|largest| largest.set(largest.get().max(size)));
     }
 
-    /// Get document index from [`Position`]
-    fn position_index(&self, position: Position) -> usize {
-        // TODO: This can panic and should be handled better but I would like to see when
-        // this actually happens.
-        self.content.line_to_char(position.line as usize) + position.character as usize
+    #[global_allocator]
+    static ALLOCATOR: Counting = Counting;
+
+    const SOURCE: &str = "package demo;
+
+import java.util.List;
+
+/** Grüße, 世界 😀 */
+public class Demo {
+    private final List<String> names = List.of(\"ä\", \"😀\");
+
+    int count(int a) {
+        // 漢字
+        return a + names.size();
+    }
+}
+";
+
+    /// Snippets that are inserted at random positions, they contain multi byte characters
+    /// and line breaks so that rows, columns and byte offsets diverge
+    const INSERTIONS: [&str; 10] = [
+        "x",
+        "\n",
+        "ö",
+        "😀",
+        "{ int y = 1; }",
+        "\"漢\"",
+        "/* é\n */",
+        "class Inner { void run() {} }\n",
+        "}",
+        "",
+    ];
+
+    /// Small xorshift generator, the sequences only have to be reproducible
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self, bound: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % bound as u64) as usize
+        }
+    }
+
+    /// All nodes of a tree with their byte ranges and points, `to_sexp` alone would not
+    /// reveal wrong positions
+    fn describe(node: Node, nodes: &mut Vec<String>) {
+        nodes.push(format!(
+            "{} {:?} {} {}",
+            node.kind(),
+            node.byte_range(),
+            node.start_position(),
+            node.end_position()
+        ));
+
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            describe(child, nodes);
+        }
+    }
+
+    fn fresh_parse(content: &str) -> Vec<String> {
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_java::LANGUAGE.into())
+            .unwrap();
+
+        let mut nodes = Vec::new();
+        describe(parser.parse(content, None).unwrap().root_node(), &mut nodes);
+        nodes
+    }
+
+    #[test]
+    fn incremental_parse_matches_fresh_parse() {
+        for encoding in [
+            PositionEncoding::Utf8,
+            PositionEncoding::Utf16,
+            PositionEncoding::Utf32,
+        ] {
+            for seed in 1..=10 {
+                let mut random = Random(seed * 7919);
+                let uri = Url::parse("file:///demo/Demo.java").unwrap();
+                let mut document = Document::new(uri, 0, SOURCE, encoding).unwrap();
+
+                for step in 0..40 {
+                    let chars = document.content.len_chars();
+                    let start = random.next(chars + 1);
+                    let end = (start + random.next(8)).min(chars);
+                    let text = INSERTIONS[random.next(INSERTIONS.len())];
+
+                    let start = document.content.char_to_byte(start);
+                    let end = document.content.char_to_byte(end);
+                    document
+                        .update(
+                            position::position(&document.content, start, encoding),
+                            position::position(&document.content, end, encoding),
+                            text,
+                        )
+                        .unwrap();
+
+                    let content = document.content.to_string();
+                    let mut nodes = Vec::new();
+                    describe(document.tree.root_node(), &mut nodes);
+                    assert_eq!(
+                        nodes,
+                        fresh_parse(&content),
+                        "{encoding:?}, seed {seed}, step {step}:\n{content}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn edits_of_large_documents_do_not_copy_the_content() {
+        let mut source = String::from("package demo;\n\npublic class Generated {\n");
+        for i in 0..2_400 {
+            source.push_str(&format!("    void method{i}() {{\n"));
+            for j in 0..8 {
+                source.push_str(&format!("        int value{j} = {j};\n"));
+            }
+            source.push_str("    }\n\n");
+        }
+        source.push_str("}\n");
+
+        let uri = Url::parse("file:///demo/Generated.java").unwrap();
+        let mut document = Document::new(uri, 0, &source, PositionEncoding::Utf16).unwrap();
+
+        // Type and delete characters in the middle of the file like a user would
+        LARGEST_ALLOCATION.set(0);
+        for i in 0..20 {
+            let position = Position::new(12_004, 8);
+            if i % 2 == 0 {
+                document.update(position, position, "x").unwrap();
+            } else {
+                document
+                    .update(position, Position::new(12_004, 9), "")
+                    .unwrap();
+            }
+        }
+        let largest = LARGEST_ALLOCATION.get();
+        assert!(largest < source.len() / 10, "allocated {largest} bytes");
+
+        // Readers take slices of the rope, only their result is allocated
+        LARGEST_ALLOCATION.set(0);
+        let ranges = document.folding_ranges();
+        let largest = LARGEST_ALLOCATION.get();
+        assert!(
+            largest <= ranges.capacity() * size_of::<FoldingRange>(),
+            "allocated {largest} bytes"
+        );
+
+        assert!(!document.tree.root_node().has_error());
+        assert_eq!(document.text, source);
+        assert_eq!(document.content.to_string(), source);
     }
 }
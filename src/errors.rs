@@ -6,4 +6,14 @@ pub enum DocumentError {
     TreeSitterParserError(#[from] tree_sitter::LanguageError),
     #[error("Could not parse source code for {0}")]
     TreeSitterParseError(String),
+    #[error("Position {0}:{1} is outside of the document")]
+    InvalidPosition(u32, u32),
+}
+
+#[derive(Debug, Error)]
+pub enum ProjectError {
+    #[error("Could not find {0}, neither a wrapper script nor an executable in PATH exists")]
+    BuildToolNotFound(&'static str),
+    #[error("Could not find the configured {0} executable {1}")]
+    ConfiguredBuildToolNotFound(&'static str, String),
 }
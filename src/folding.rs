@@ -0,0 +1,106 @@
+use std::borrow::Cow;
+
+use lsp_types::{FoldingRange, FoldingRangeKind};
+use ropey::Rope;
+use tree_sitter::Node;
+
+/// Kinds of nodes whose body can be folded
+const BODIES: [&str; 6] = [
+    "class_body",
+    "interface_body",
+    "enum_body",
+    "annotation_type_body",
+    "constructor_body",
+    "block",
+];
+
+/// Collect the ranges of the type and method bodies, the imports, the multi line comments
+/// and the `// region` markers below `root`
+pub fn folding_ranges(root: Node, content: &Rope) -> Vec<FoldingRange> {
+    let mut ranges = Vec::new();
+    let mut regions = Vec::new();
+    let mut cursor = root.walk();
+
+    // Imports are folded together if they directly follow each other
+    let mut imports: Option<(usize, usize)> = None;
+    for child in root.named_children(&mut cursor) {
+        match (child.kind(), imports) {
+            ("import_declaration", Some((start, _))) => {
+                imports = Some((start, child.end_position().row))
+            }
+            ("import_declaration", None) => {
+                imports = Some((child.start_position().row, child.end_position().row))
+            }
+            // Comments between imports do not end the block
+            ("line_comment" | "block_comment", _) => {}
+            (_, Some((start, end))) => {
+                ranges.extend(range(start, end, Some(FoldingRangeKind::Imports)));
+                imports = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some((start, end)) = imports {
+        ranges.extend(range(start, end, Some(FoldingRangeKind::Imports)));
+    }
+
+    collect(root, content, &mut ranges, &mut regions);
+    ranges.sort_by_key(|range| range.start_line);
+
+    ranges
+}
+
+fn collect(node: Node, content: &Rope, ranges: &mut Vec<FoldingRange>, regions: &mut Vec<usize>) {
+    let start = node.start_position().row;
+    let end = node.end_position().row;
+
+    match node.kind() {
+        "block_comment" => {
+            ranges.extend(range(start, end, Some(FoldingRangeKind::Comment)));
+            return;
+        }
+        "line_comment" => {
+            let text = content
+                .get_byte_slice(node.byte_range())
+                .map(Cow::from)
+                .unwrap_or_default();
+            let marker = text.trim_start_matches('/').trim_start();
+            if marker.starts_with("region") {
+                regions.push(start);
+            } else if marker.starts_with("endregion")
+                && let Some(region) = regions.pop()
+            {
+                ranges.extend(range(region, end, Some(FoldingRangeKind::Region)));
+            }
+            return;
+        }
+        // Blocks of statements are only folded as the body of a method or lambda
+        "block"
+            if !node.parent().is_some_and(|parent| {
+                matches!(
+                    parent.kind(),
+                    "method_declaration" | "lambda_expression" | "static_initializer"
+                )
+            }) => {}
+        kind if BODIES.contains(&kind) => {
+            // The line of the closing brace stays visible
+            ranges.extend(range(start, end.saturating_sub(1), None));
+        }
+        _ => {}
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect(child, content, ranges, regions);
+    }
+}
+
+/// Folding range over whole lines, `None` if there is nothing to fold
+fn range(start: usize, end: usize, kind: Option<FoldingRangeKind>) -> Option<FoldingRange> {
+    (end > start).then(|| FoldingRange {
+        start_line: start as u32,
+        end_line: end as u32,
+        kind,
+        ..Default::default()
+    })
+}
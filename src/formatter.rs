@@ -0,0 +1,744 @@
+use tree_sitter::Node;
+
+use crate::{
+    configuration::{BraceStyle, FormatterConfiguration, FormatterPreset, ImportOrder},
+    organize_imports::import_block,
+    tree_sitter::collect_imports,
+};
+
+/// Settings of the formatter, resolved from the preset and the overrides of the
+/// [`FormatterConfiguration`]
+#[derive(Debug, Clone)]
+pub struct Style {
+    pub indent_width: usize,
+    pub continuation_indent_width: usize,
+    pub line_width: usize,
+    pub brace_style: BraceStyle,
+    pub sort_imports: bool,
+    pub import_order: ImportOrder,
+}
+
+impl Style {
+    pub fn google() -> Self {
+        Style {
+            indent_width: 2,
+            continuation_indent_width: 4,
+            line_width: 100,
+            brace_style: BraceStyle::EndOfLine,
+            sort_imports: true,
+            import_order: ImportOrder::default(),
+        }
+    }
+
+    pub fn aosp() -> Self {
+        Style {
+            indent_width: 4,
+            continuation_indent_width: 8,
+            ..Style::google()
+        }
+    }
+}
+
+impl From<&FormatterConfiguration> for Style {
+    fn from(configuration: &FormatterConfiguration) -> Self {
+        let preset = match configuration.preset {
+            FormatterPreset::Google => Style::google(),
+            FormatterPreset::Aosp => Style::aosp(),
+        };
+
+        Style {
+            indent_width: configuration.indent_width.unwrap_or(preset.indent_width),
+            continuation_indent_width: configuration
+                .continuation_indent_width
+                .unwrap_or(preset.continuation_indent_width),
+            line_width: configuration.line_width.unwrap_or(preset.line_width),
+            brace_style: configuration.brace_style.unwrap_or(preset.brace_style),
+            sort_imports: configuration.sort_imports.unwrap_or(preset.sort_imports),
+            import_order: configuration.import_order.clone(),
+        }
+    }
+}
+
+/// Text that replaces the bytes from `start` to `end` of the source
+#[derive(Debug, PartialEq)]
+pub struct Replacement {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+/// Bodies whose members are always placed on lines of their own
+const BODIES: [&str; 7] = [
+    "block",
+    "class_body",
+    "interface_body",
+    "annotation_type_body",
+    "constructor_body",
+    "switch_block",
+    "module_body",
+];
+
+/// Nodes that are formatted as a whole, their content is not touched
+const ATOMIC: [&str; 4] = [
+    "string_literal",
+    "character_literal",
+    "line_comment",
+    "block_comment",
+];
+
+/// Nodes whose anonymous children are operators that are surrounded by spaces
+const OPERATOR_PARENTS: [&str; 8] = [
+    "binary_expression",
+    "assignment_expression",
+    "ternary_expression",
+    "lambda_expression",
+    "variable_declarator",
+    "switch_rule",
+    "enhanced_for_statement",
+    "element_value_pair",
+];
+
+/// Keywords that are separated from the following parenthesis
+const CONTROL_KEYWORDS: [&str; 7] = [
+    "if",
+    "for",
+    "while",
+    "switch",
+    "catch",
+    "synchronized",
+    "try",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Container {
+    /// Members are placed on lines of their own
+    Body,
+    /// Line breaks between the elements are kept, e.g. array initializers
+    List,
+}
+
+/// Compute the replacements that format the compilation unit below `root`. Only the
+/// whitespace between tokens is changed, except for the order of the imports, so the
+/// replacements stay small. Whitespace next to syntax errors is kept as it is.
+pub fn format(root: Node, source: &str, style: &Style) -> Vec<Replacement> {
+    let mut leaves = Vec::new();
+    collect_leaves(root, &mut leaves);
+    if leaves.is_empty() {
+        return Vec::new();
+    }
+
+    let formatter = Formatter { source, style };
+    let mut separators = leaves
+        .iter()
+        .enumerate()
+        .map(|(i, leaf)| {
+            let start = if i == 0 { 0 } else { leaves[i - 1].end_byte() };
+            let original = &source[start..leaf.start_byte()];
+            let text = match i.checked_sub(1) {
+                Some(previous) => formatter.separator(leaves[previous], *leaf, original),
+                // Leading whitespace is dropped
+                None if original.trim().is_empty() => String::new(),
+                None => original.to_string(),
+            };
+            (start, text)
+        })
+        .collect::<Vec<(usize, String)>>();
+    formatter.wrap(&leaves, &mut separators);
+
+    let imports = style
+        .sort_imports
+        .then(|| sort_imports(root, source, &style.import_order))
+        .flatten();
+    let mut replacements = Vec::new();
+    for (i, (start, text)) in separators.into_iter().enumerate() {
+        let leaf = leaves[i];
+        // Separators within the imports are replaced with the imports
+        let in_imports = imports
+            .as_ref()
+            .is_some_and(|imports| start > imports.start && leaf.start_byte() < imports.end);
+        if in_imports {
+            continue;
+        }
+
+        if source[start..leaf.start_byte()] != text {
+            replacements.push(Replacement {
+                start,
+                end: leaf.start_byte(),
+                text: text.clone(),
+            });
+        }
+        if leaf.kind() == "block_comment" && text.contains('\n') {
+            let indent = text.rsplit('\n').next().unwrap_or_default();
+            replacements.extend(formatter.align_comment(leaf, indent));
+        }
+    }
+    replacements.extend(imports);
+
+    // Files end with exactly one line break
+    let last = leaves[leaves.len() - 1].end_byte();
+    if &source[last..] != "\n" && source[last..].trim().is_empty() {
+        replacements.push(Replacement {
+            start: last,
+            end: source.len(),
+            text: "\n".to_string(),
+        });
+    }
+    replacements.sort_by_key(|replacement| replacement.start);
+
+    replacements
+}
+
+fn collect_leaves<'tree>(node: Node<'tree>, leaves: &mut Vec<Node<'tree>>) {
+    if node.child_count() == 0 || ATOMIC.contains(&node.kind()) {
+        // Missing nodes that were inserted by error recovery have no text
+        if node.start_byte() < node.end_byte() {
+            leaves.push(node);
+        }
+        return;
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_leaves(child, leaves);
+    }
+}
+
+struct Formatter<'a> {
+    source: &'a str,
+    style: &'a Style,
+}
+
+impl Formatter<'_> {
+    /// Whitespace between the tokens `previous` and `next`
+    fn separator(&self, previous: Node, next: Node, original: &str) -> String {
+        if !original.trim().is_empty() || in_error(previous) || in_error(next) {
+            return original.to_string();
+        }
+
+        let line_breaks = self.line_breaks(previous, next, original.matches('\n').count());
+        if line_breaks > 0 {
+            return format!(
+                "{}{}",
+                "\n".repeat(line_breaks),
+                self.indentation(previous, next)
+            );
+        }
+
+        if self.has_space(previous, next, !original.is_empty()) {
+            " ".to_string()
+        } else {
+            String::new()
+        }
+    }
+
+    fn line_breaks(&self, previous: Node, next: Node, original: usize) -> usize {
+        let end_of_line = self.style.brace_style == BraceStyle::EndOfLine;
+
+        if previous.kind() == "line_comment" {
+            return original.clamp(1, 2);
+        }
+        // Trailing comments stay on the line they comment
+        if is_comment(next) && original == 0 {
+            return 0;
+        }
+        if let Some(body) = brace_of(previous, "{").filter(|body| is_body(*body)) {
+            // Empty bodies stay on one line if they are written like that
+            if brace_of(next, "}") == Some(body) {
+                return original.min(1);
+            }
+            return original.clamp(1, 2);
+        }
+        if brace_of(next, "}").is_some_and(is_body) {
+            return 1;
+        }
+        if brace_of(next, "{").is_some_and(|body| is_body(body) && !is_lambda_body(body))
+            && !is_comment(previous)
+        {
+            return if end_of_line { 0 } else { 1 };
+        }
+        if brace_of(previous, "}").is_some() && is_continuation_keyword(next) {
+            return if end_of_line { 0 } else { 1 };
+        }
+        // The package and the imports are followed by a blank line
+        if is_unit_end(previous)
+            && unit(previous).is_some_and(|ended| match ended.kind() {
+                "package_declaration" => true,
+                "import_declaration" => {
+                    unit(next).is_none_or(|next| next.kind() != "import_declaration")
+                }
+                _ => false,
+            })
+        {
+            return 2;
+        }
+
+        let next_unit = unit_start(next);
+        if next_unit && is_unit_end(previous) && unit_parent(next).is_some_and(is_body_like) {
+            return original.clamp(1, 2);
+        }
+        if next_unit || is_comment(next) {
+            original.min(2)
+        } else {
+            original.min(1)
+        }
+    }
+
+    /// Indentation of `next` at the start of a line
+    fn indentation(&self, previous: Node, next: Node) -> String {
+        let mut width = depth(next) * self.style.indent_width;
+        let continues = !unit_start(next)
+            && brace_of(next, "{").is_none_or(is_lambda_body)
+            && brace_of(next, "}").is_none()
+            && !is_continuation_keyword(next)
+            && !ends_annotation(previous);
+        if continues {
+            width += self.style.continuation_indent_width;
+        }
+
+        " ".repeat(width)
+    }
+
+    /// Whether tokens on the same line are separated by a space
+    fn has_space(&self, previous: Node, next: Node, original: bool) -> bool {
+        let (before, after) = (previous.kind(), next.kind());
+
+        if is_operator(previous) || is_operator(next) {
+            return true;
+        }
+        if matches!(after, ";" | "," | ")" | "]" | "." | "::" | "...") {
+            return false;
+        }
+        if matches!(before, "(" | "[" | "." | "::" | "@") {
+            return false;
+        }
+        if matches!(before, "," | ";") {
+            return true;
+        }
+        if after == "(" {
+            return CONTROL_KEYWORDS.contains(&before) && !previous.is_named();
+        }
+        if after == "[" {
+            return false;
+        }
+        if previous
+            .parent()
+            .is_some_and(|parent| parent.kind() == "unary_expression")
+            && !previous.is_named()
+        {
+            return false;
+        }
+        if previous
+            .parent()
+            .is_some_and(|parent| parent.kind() == "update_expression")
+            || next
+                .parent()
+                .is_some_and(|parent| parent.kind() == "update_expression" && !next.is_named())
+        {
+            return false;
+        }
+        if is_type_argument_bracket(next) || (before == "<" && is_type_argument_bracket(previous)) {
+            return false;
+        }
+        // Lists are written like `{1, 2}` and empty bodies like `{}`
+        if brace_of(previous, "{").is_some_and(|body| brace_of(next, "}") == Some(body)) {
+            return false;
+        }
+        if brace_of(previous, "{").is_some_and(|list| !is_body(list))
+            || brace_of(next, "}").is_some_and(|list| !is_body(list))
+        {
+            return false;
+        }
+        if brace_of(next, "{").is_some_and(is_body) {
+            return true;
+        }
+        if matches!(before, "}" | ")") && matches!(after, "{") {
+            return true;
+        }
+        // `} else`, `} catch` and `} finally` on one line
+        if brace_of(previous, "}").is_some() && is_continuation_keyword(next) {
+            return true;
+        }
+
+        original
+    }
+
+    /// Break lines that are longer than the line width after commas, before the dots of
+    /// method chains or before binary operators
+    fn wrap(&self, leaves: &[Node], separators: &mut [(usize, String)]) {
+        let mut column = 0;
+        // Last separator of the current line where the line can be broken
+        let mut breakable: Option<usize> = None;
+        let mut i = 0;
+
+        while i < leaves.len() {
+            let separator = &separators[i].1;
+            match separator.rfind('\n') {
+                Some(line_break) => {
+                    column = separator.len() - line_break - 1;
+                    breakable = None;
+                }
+                None => column += separator.len(),
+            }
+
+            let text = &self.source[leaves[i].byte_range()];
+            match text.rfind('\n') {
+                Some(line_break) => {
+                    column = text[line_break + 1..].chars().count();
+                    breakable = None;
+                }
+                None => column += text.chars().count(),
+            }
+
+            if column > self.style.line_width
+                && let Some(position) = breakable.take()
+            {
+                let indentation = self.indentation(leaves[position - 1], leaves[position]);
+                separators[position].1 = format!("\n{indentation}");
+                i = position;
+                continue;
+            }
+
+            if let Some(next) = leaves.get(i + 1)
+                && is_breakable(leaves[i], *next)
+                && !separators[i + 1].1.contains('\n')
+            {
+                breakable = Some(i + 1);
+            }
+            i += 1;
+        }
+    }
+
+    /// Align the lines of a multi line comment that start with `*` to its first line
+    fn align_comment(&self, comment: Node, indent: &str) -> Vec<Replacement> {
+        let text = &self.source[comment.byte_range()];
+        let mut replacements = Vec::new();
+        let mut offset = 0;
+
+        for line in text.split_inclusive('\n') {
+            let start = comment.start_byte() + offset;
+            offset += line.len();
+            if start == comment.start_byte() {
+                continue;
+            }
+
+            let content = line.trim_start_matches([' ', '\t']);
+            let whitespace = line.len() - content.len();
+            let expected = format!("{indent} ");
+            if content.starts_with('*') && line[..whitespace] != expected {
+                replacements.push(Replacement {
+                    start,
+                    end: start + whitespace,
+                    text: expected,
+                });
+            }
+        }
+
+        replacements
+    }
+}
+
+/// Group and sort the imports, imports with comments between them are not sorted
+fn sort_imports(root: Node, source: &str, order: &ImportOrder) -> Option<Replacement> {
+    let mut cursor = root.walk();
+    let children = root.children(&mut cursor).collect::<Vec<Node>>();
+    let first = children
+        .iter()
+        .position(|child| child.kind() == "import_declaration")?;
+    let last = children
+        .iter()
+        .rposition(|child| child.kind() == "import_declaration")?;
+    if children[first..=last]
+        .iter()
+        .any(|child| child.kind() != "import_declaration" || child.has_error())
+    {
+        return None;
+    }
+
+    let (start, end) = (children[first].start_byte(), children[last].end_byte());
+    let text = import_block(&collect_imports(root, source.as_bytes()), order);
+
+    (source[start..end] != text).then_some(Replacement { start, end, text })
+}
+
+fn container(node: Node) -> Option<Container> {
+    match node.kind() {
+        kind if BODIES.contains(&kind) => Some(Container::Body),
+        "enum_body" => {
+            // Enums with members are bodies, plain lists of constants are lists
+            let mut cursor = node.walk();
+            let has_members = node
+                .named_children(&mut cursor)
+                .any(|child| child.kind() == "enum_body_declarations");
+            Some(if has_members {
+                Container::Body
+            } else {
+                Container::List
+            })
+        }
+        "array_initializer" | "element_value_array_initializer" => Some(Container::List),
+        _ => None,
+    }
+}
+
+fn is_body(node: Node) -> bool {
+    container(node) == Some(Container::Body)
+}
+
+/// Whether the children of `node` are placed on lines of their own
+fn is_body_like(node: Node) -> bool {
+    is_body(node)
+        || matches!(
+            node.kind(),
+            "program" | "enum_body_declarations" | "switch_block_statement_group"
+        )
+}
+
+fn is_lambda_body(node: Node) -> bool {
+    node.parent()
+        .is_some_and(|parent| parent.kind() == "lambda_expression")
+}
+
+/// The container if `leaf` is its opening or closing brace `brace`
+fn brace_of<'tree>(leaf: Node<'tree>, brace: &str) -> Option<Node<'tree>> {
+    if leaf.kind() != brace {
+        return None;
+    }
+
+    leaf.parent().filter(|parent| container(*parent).is_some())
+}
+
+/// Parent of the statement, member or comment that `node` is part of
+fn unit_parent(node: Node) -> Option<Node> {
+    unit(node).and_then(|unit| unit.parent())
+}
+
+/// Statement, member or comment that `node` is part of, i.e. the outermost ancestor below a
+/// body or the compilation unit
+fn unit(node: Node) -> Option<Node> {
+    let mut current = node;
+    while let Some(parent) = current.parent() {
+        if is_body_like(parent) || container(parent).is_some() {
+            // Punctuation like the colon of a case label is not a unit of its own
+            return current.is_named().then_some(current);
+        }
+        current = parent;
+    }
+
+    None
+}
+
+fn unit_start(leaf: Node) -> bool {
+    unit(leaf).is_some_and(|unit| unit.start_byte() == leaf.start_byte())
+}
+
+fn is_unit_end(leaf: Node) -> bool {
+    unit(leaf).is_some_and(|unit| unit.end_byte() == leaf.end_byte())
+}
+
+/// Number of bodies and lists that enclose `leaf`, their own braces are not enclosed
+fn depth(leaf: Node) -> usize {
+    let mut depth = 0;
+    let mut child = leaf;
+
+    while let Some(parent) = child.parent() {
+        let is_own_brace = child == leaf && matches!(leaf.kind(), "{" | "}");
+        if container(parent).is_some() && !is_own_brace {
+            depth += 1;
+        }
+        // Statements of a case are indented below its label
+        if parent.kind() == "switch_block_statement_group"
+            && !matches!(child.kind(), "switch_label" | ":")
+        {
+            depth += 1;
+        }
+        child = parent;
+    }
+
+    depth
+}
+
+/// Keywords that continue a statement after the closing brace of a block
+fn is_continuation_keyword(leaf: Node) -> bool {
+    match leaf.kind() {
+        "else" | "catch" | "finally" => !leaf.is_named(),
+        "while" => leaf
+            .parent()
+            .is_some_and(|parent| parent.kind() == "do_statement"),
+        _ => false,
+    }
+}
+
+/// Whether `leaf` ends an annotation of a declaration, which is usually placed on a line
+/// of its own
+fn ends_annotation(leaf: Node) -> bool {
+    let mut current = leaf;
+    while let Some(parent) = current.parent() {
+        if matches!(parent.kind(), "marker_annotation" | "annotation") {
+            return parent.end_byte() == leaf.end_byte()
+                && parent
+                    .parent()
+                    .is_some_and(|modifiers| modifiers.kind() == "modifiers");
+        }
+        current = parent;
+    }
+
+    false
+}
+
+fn is_operator(leaf: Node) -> bool {
+    !leaf.is_named()
+        && !matches!(leaf.kind(), "(" | ")" | "for" | ";" | ",")
+        && leaf
+            .parent()
+            .is_some_and(|parent| OPERATOR_PARENTS.contains(&parent.kind()))
+}
+
+/// Whether `leaf` is `<` or `>` of type arguments or parameters
+fn is_type_argument_bracket(leaf: Node) -> bool {
+    matches!(leaf.kind(), "<" | ">")
+        && leaf
+            .parent()
+            .is_some_and(|parent| matches!(parent.kind(), "type_arguments" | "type_parameters"))
+}
+
+/// Whether a line can be broken between `previous` and `next`
+fn is_breakable(previous: Node, next: Node) -> bool {
+    if in_error(previous) || in_error(next) || is_comment(previous) {
+        return false;
+    }
+
+    let parent_kind = |node: Node| {
+        node.parent()
+            .map(|parent| parent.kind())
+            .unwrap_or_default()
+    };
+    (previous.kind() == ","
+        && matches!(
+            parent_kind(previous),
+            "argument_list" | "formal_parameters" | "array_initializer" | "type_arguments"
+        ))
+        || (next.kind() == "." && matches!(parent_kind(next), "method_invocation" | "field_access"))
+        || (is_operator(next) && parent_kind(next) == "binary_expression")
+}
+
+fn is_comment(node: Node) -> bool {
+    matches!(node.kind(), "line_comment" | "block_comment")
+}
+
+/// Whether `node` is or is part of a syntax error
+fn in_error(node: Node) -> bool {
+    let mut current = Some(node);
+    while let Some(node) = current {
+        if node.is_error() || node.is_missing() {
+            return true;
+        }
+        current = node.parent();
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::Parser;
+
+    use super::{Style, format};
+    use crate::configuration::BraceStyle;
+
+    const SOURCE: &str = "package demo;
+import java.util.Map;
+import java.util.List;
+public class Demo{
+private int count=0;
+    int add(int a,int b){if(a>b){return a;}else{
+ return b;}}
+  void call() { String result = String.join(\", \", \"first argument\", \"second argument\", \"third argument\"); }
+}";
+
+    /// `source` with the replacements of the formatter applied
+    fn formatted(source: &str, style: &Style) -> String {
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_java::LANGUAGE.into())
+            .unwrap();
+        let tree = parser.parse(source, None).unwrap();
+
+        let mut text = source.to_string();
+        for replacement in format(tree.root_node(), source, style).into_iter().rev() {
+            text.replace_range(replacement.start..replacement.end, &replacement.text);
+        }
+        text
+    }
+
+    fn next_line() -> Style {
+        Style {
+            brace_style: BraceStyle::NextLine,
+            ..Style::aosp()
+        }
+    }
+
+    fn narrow() -> Style {
+        Style {
+            line_width: 60,
+            ..Style::google()
+        }
+    }
+
+    #[test]
+    fn formatting_is_idempotent() {
+        for style in [Style::google(), Style::aosp(), next_line(), narrow()] {
+            let once = formatted(SOURCE, &style);
+            assert_eq!(formatted(&once, &style), once, "{style:?}");
+        }
+    }
+
+    #[test]
+    fn braces_follow_the_brace_style() {
+        let end_of_line = formatted(SOURCE, &Style::google());
+        assert!(
+            end_of_line.contains("public class Demo {\n"),
+            "{end_of_line}"
+        );
+        assert!(
+            end_of_line.contains("  int add(int a, int b) {\n    if (a > b) {\n"),
+            "{end_of_line}"
+        );
+        assert!(
+            end_of_line.contains("    } else {\n      return b;\n    }\n"),
+            "{end_of_line}"
+        );
+
+        let next_line = formatted(SOURCE, &next_line());
+        assert!(next_line.contains("public class Demo\n{\n"), "{next_line}");
+        assert!(
+            next_line.contains("    int add(int a, int b)\n    {\n        if (a > b)\n        {\n"),
+            "{next_line}"
+        );
+        assert!(
+            next_line.contains("        }\n        else\n        {\n            return b;\n"),
+            "{next_line}"
+        );
+    }
+
+    #[test]
+    fn long_lines_are_wrapped_at_line_width() {
+        let call = "String result = String.join(\", \", \"first argument\", \"second argument\", \"third argument\");";
+        assert!(formatted(SOURCE, &Style::google()).contains(call));
+
+        let style = narrow();
+        let wrapped = formatted(SOURCE, &style);
+        assert!(
+            wrapped
+                .lines()
+                .all(|line| line.chars().count() <= style.line_width),
+            "{wrapped}"
+        );
+        // Continuation lines are indented by the continuation width
+        assert!(
+            wrapped.contains(
+                "    String result = String.join(\", \", \"first argument\",\n        \"second argument\", \"third argument\");"
+            ),
+            "{wrapped}"
+        );
+    }
+}
@@ -0,0 +1,581 @@
+use jclassfile::methods::MethodFlags;
+use tree_sitter::Node;
+
+use crate::{
+    classfile::{Classfile, JavaType},
+    hierarchy::InheritedMethod,
+    quick_fix::METHOD_STUB,
+    tree_sitter::has_modifier,
+};
+
+/// Methods of `Object` that are not delegated
+const OBJECT_METHODS: [&str; 9] = [
+    "equals",
+    "hashCode",
+    "toString",
+    "getClass",
+    "notify",
+    "notifyAll",
+    "wait",
+    "clone",
+    "finalize",
+];
+
+/// Field that is declared in the body of a class
+pub struct Field<'tree> {
+    pub name: String,
+    /// Declared type with the dimensions of the name like `int[]` for `int x[]`
+    pub field_type: String,
+    pub is_static: bool,
+    pub is_final: bool,
+    pub has_value: bool,
+    /// Whole field declaration, it can declare further fields
+    pub declaration: Node<'tree>,
+}
+
+/// Method or constructor that is already declared in a class body
+pub struct Declared {
+    pub name: String,
+    /// Types of the parameters as they are written
+    pub parameters: Vec<String>,
+}
+
+/// Method of another type that a field can delegate to
+pub struct Delegate {
+    pub name: String,
+    pub return_type: String,
+    /// Types of the parameters like they are written in the generated method
+    pub parameters: Vec<String>,
+    pub exceptions: Vec<String>,
+}
+
+/// Fields that are declared by the `field_declaration` nodes of `body`
+pub fn fields<'tree>(body: Node<'tree>, source: &str) -> Vec<Field<'tree>> {
+    let mut fields = Vec::new();
+    let mut cursor = body.walk();
+
+    for declaration in declarations(body) {
+        if declaration.kind() != "field_declaration" {
+            continue;
+        }
+        let Some(declared) = declaration.child_by_field_name("type") else {
+            continue;
+        };
+        for declarator in declaration.children_by_field_name("declarator", &mut cursor) {
+            let Some(name) = declarator.child_by_field_name("name") else {
+                continue;
+            };
+            let dimensions = declarator
+                .child_by_field_name("dimensions")
+                .map(|dimensions| &source[dimensions.byte_range()])
+                .unwrap_or_default();
+
+            fields.push(Field {
+                name: source[name.byte_range()].to_string(),
+                field_type: format!("{}{dimensions}", &source[declared.byte_range()]),
+                is_static: has_modifier(declaration, "static"),
+                is_final: has_modifier(declaration, "final"),
+                has_value: declarator.child_by_field_name("value").is_some(),
+                declaration,
+            });
+        }
+    }
+
+    fields
+}
+
+/// Methods and constructors of `body` with the types of their parameters
+pub fn declared(body: Node, source: &str) -> Vec<Declared> {
+    declarations(body)
+        .into_iter()
+        .filter(|declaration| {
+            matches!(
+                declaration.kind(),
+                "method_declaration" | "constructor_declaration"
+            )
+        })
+        .filter_map(|declaration| {
+            let name = declaration.child_by_field_name("name")?;
+            Some(Declared {
+                name: source[name.byte_range()].to_string(),
+                parameters: parameter_types(declaration, source),
+            })
+        })
+        .collect()
+}
+
+/// Whether a method `name` with `arity` parameters is declared
+pub fn is_declared(declared: &[Declared], name: &str, arity: usize) -> bool {
+    declared
+        .iter()
+        .any(|method| method.name == name && method.parameters.len() == arity)
+}
+
+/// Fields whose declarations overlap the selection between `start` and `end`, all fields if
+/// the selection contains none
+pub fn selected<'a, 'tree>(
+    fields: &'a [Field<'tree>],
+    start: usize,
+    end: usize,
+) -> Vec<&'a Field<'tree>> {
+    let selected = fields
+        .iter()
+        .filter(|field| {
+            field.declaration.start_byte() <= end && start <= field.declaration.end_byte()
+        })
+        .collect::<Vec<&Field>>();
+
+    if selected.is_empty() {
+        fields.iter().collect()
+    } else {
+        selected
+    }
+}
+
+/// Getters and setters of `fields` that are not declared yet, final fields only get getters
+pub fn accessors(
+    class: &str,
+    fields: &[&Field],
+    declared: &[Declared],
+    getters: bool,
+    setters: bool,
+    unit: &str,
+) -> Vec<String> {
+    let mut accessors = Vec::new();
+    for field in fields {
+        let Field {
+            name, field_type, ..
+        } = field;
+        let modifiers = if field.is_static {
+            "public static"
+        } else {
+            "public"
+        };
+        let capitalized = capitalize(name);
+
+        let getter = if field_type == "boolean" {
+            format!("is{capitalized}")
+        } else {
+            format!("get{capitalized}")
+        };
+        if getters && !is_declared(declared, &getter, 0) {
+            accessors.push(format!(
+                "{modifiers} {field_type} {getter}() {{\n{unit}return {name};\n}}"
+            ));
+        }
+
+        let setter = format!("set{capitalized}");
+        if setters && !field.is_final && !is_declared(declared, &setter, 1) {
+            let target = if field.is_static { class } else { "this" };
+            accessors.push(format!(
+                "{modifiers} void {setter}({field_type} {name}) {{\n{unit}{target}.{name} = {name};\n}}"
+            ));
+        }
+    }
+
+    accessors
+}
+
+/// Constructor that assigns `fields` from its parameters
+pub fn constructor(class: &str, is_enum: bool, fields: &[&Field], unit: &str) -> String {
+    // Constructors of enums are implicitly private
+    let modifiers = if is_enum { "" } else { "public " };
+    let parameters = fields
+        .iter()
+        .map(|field| format!("{} {}", field.field_type, field.name))
+        .collect::<Vec<String>>()
+        .join(", ");
+    let assignments = fields
+        .iter()
+        .map(|field| format!("\n{unit}this.{name} = {name};", name = field.name))
+        .collect::<String>();
+
+    format!("{modifiers}{class}({parameters}) {{{assignments}\n}}")
+}
+
+/// `equals` and `hashCode` that compare and hash `fields`, and the types they need to import
+pub fn equals_hash_code(
+    class: &str,
+    is_generic: bool,
+    fields: &[&Field],
+    unit: &str,
+) -> (Vec<String>, Vec<&'static str>) {
+    let mut imports = vec!["java.util.Objects"];
+    let other_type = if is_generic {
+        format!("{class}<?>")
+    } else {
+        class.to_string()
+    };
+
+    let comparisons = fields
+        .iter()
+        .map(|field| {
+            let name = &field.name;
+            match field.field_type.as_str() {
+                "float" => format!("Float.compare({name}, other.{name}) == 0"),
+                "double" => format!("Double.compare({name}, other.{name}) == 0"),
+                "boolean" | "byte" | "char" | "short" | "int" | "long" => {
+                    format!("{name} == other.{name}")
+                }
+                array if array.ends_with("[][]") => {
+                    imports.push("java.util.Arrays");
+                    format!("Arrays.deepEquals({name}, other.{name})")
+                }
+                array if array.ends_with("[]") => {
+                    imports.push("java.util.Arrays");
+                    format!("Arrays.equals({name}, other.{name})")
+                }
+                _ => format!("Objects.equals({name}, other.{name})"),
+            }
+        })
+        .collect::<Vec<String>>();
+    let comparison = if comparisons.is_empty() {
+        "true".to_string()
+    } else {
+        comparisons.join(&format!("\n{unit}{unit}&& "))
+    };
+    let equals = format!(
+        "@Override\npublic boolean equals(Object obj) {{\n\
+         {unit}if (this == obj) {{\n{unit}{unit}return true;\n{unit}}}\n\
+         {unit}if (obj == null || getClass() != obj.getClass()) {{\n{unit}{unit}return false;\n{unit}}}\n\
+         {unit}{other_type} other = ({other_type}) obj;\n\
+         {unit}return {comparison};\n}}"
+    );
+
+    let hashed = fields
+        .iter()
+        .map(|field| {
+            if field.field_type.ends_with("[][]") {
+                format!("Arrays.deepHashCode({})", field.name)
+            } else if field.field_type.ends_with("[]") {
+                format!("Arrays.hashCode({})", field.name)
+            } else {
+                field.name.clone()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(", ");
+    let hash_code =
+        format!("@Override\npublic int hashCode() {{\n{unit}return Objects.hash({hashed});\n}}");
+
+    imports.dedup();
+    (vec![equals, hash_code], imports)
+}
+
+/// `toString` in the form `Point [x=1, y=2]`, and the types it needs to import
+pub fn to_string(class: &str, fields: &[&Field], unit: &str) -> (String, Vec<&'static str>) {
+    let mut imports = Vec::new();
+    let mut text = format!("\"{class} [");
+    for (i, field) in fields.iter().enumerate() {
+        let separator = if i == 0 { "" } else { ", " };
+        let name = &field.name;
+        let value = if field.field_type.ends_with("[][]") {
+            imports.push("java.util.Arrays");
+            format!("Arrays.deepToString({name})")
+        } else if field.field_type.ends_with("[]") {
+            imports.push("java.util.Arrays");
+            format!("Arrays.toString({name})")
+        } else {
+            name.clone()
+        };
+        text.push_str(&format!("{separator}{name}=\" + {value} + \""));
+    }
+
+    imports.dedup();
+    (
+        format!("@Override\npublic String toString() {{\n{unit}return {text}]\";\n}}"),
+        imports,
+    )
+}
+
+/// Method that calls the method of the same signature on `field`
+pub fn delegate_method(field: &str, delegate: &Delegate, unit: &str) -> String {
+    let Delegate {
+        name,
+        return_type,
+        parameters,
+        exceptions,
+    } = delegate;
+    let arguments = (0..parameters.len())
+        .map(|i| format!("arg{i}"))
+        .collect::<Vec<String>>();
+    let parameters = parameters
+        .iter()
+        .zip(&arguments)
+        .map(|(parameter, argument)| format!("{parameter} {argument}"))
+        .collect::<Vec<String>>()
+        .join(", ");
+    let throws = if exceptions.is_empty() {
+        String::new()
+    } else {
+        format!(" throws {}", exceptions.join(", "))
+    };
+    let call = format!("{field}.{name}({})", arguments.join(", "));
+    let statement = if return_type == "void" {
+        format!("{call};")
+    } else {
+        format!("return {call};")
+    };
+
+    format!("public {return_type} {name}({parameters}){throws} {{\n{unit}{statement}\n}}")
+}
+
+/// Method that overrides `method`, it calls the inherited implementation if there is one
+pub fn override_method(method: &InheritedMethod, unit: &str) -> String {
+    let modifiers = if method.visibility.is_empty() {
+        String::new()
+    } else {
+        format!("{} ", method.visibility)
+    };
+    let type_parameters = if method.type_parameters.is_empty() {
+        String::new()
+    } else {
+        format!("<{}> ", method.type_parameters.join(", "))
+    };
+    let parameters = method
+        .parameters
+        .iter()
+        .zip(&method.parameter_names)
+        .map(|(parameter, name)| format!("{parameter} {name}"))
+        .collect::<Vec<String>>()
+        .join(", ");
+    let throws = if method.exceptions.is_empty() {
+        String::new()
+    } else {
+        format!(" throws {}", method.exceptions.join(", "))
+    };
+    let body = match &method.receiver {
+        Some(receiver) => {
+            let call = format!(
+                "{receiver}.{}({})",
+                method.name,
+                method.parameter_names.join(", ")
+            );
+            if method.return_type == "void" {
+                format!("{call};")
+            } else {
+                format!("return {call};")
+            }
+        }
+        None => METHOD_STUB.to_string(),
+    };
+
+    format!(
+        "@Override\n{modifiers}{type_parameters}{} {}({parameters}){throws} {{\n{unit}{body}\n}}",
+        method.return_type, method.name
+    )
+}
+
+/// Public instance methods of `class` with its type parameters replaced by the type
+/// `arguments` of the field, and the types that they need to import. Generic methods are
+/// left out.
+pub fn class_delegates(class: &Classfile, arguments: &[&str]) -> (Vec<Delegate>, Vec<String>) {
+    let argument = |variable: &str| {
+        let argument = class
+            .type_parameters
+            .iter()
+            .position(|parameter| parameter == variable)
+            .and_then(|i| arguments.get(i))
+            .copied()
+            .unwrap_or("Object");
+        // Only upper bounds of wildcards can be used for parameters and return types
+        match argument.strip_prefix('?') {
+            Some(bound) => bound
+                .trim()
+                .strip_prefix("extends")
+                .map(str::trim)
+                .unwrap_or("Object"),
+            None => argument,
+        }
+        .to_string()
+    };
+    let mut imports = Vec::new();
+
+    let delegates = class
+        .methods
+        .iter()
+        .filter(|method| {
+            method.flags.contains(MethodFlags::ACC_PUBLIC)
+                && !method.flags.intersects(
+                    MethodFlags::ACC_STATIC | MethodFlags::ACC_BRIDGE | MethodFlags::ACC_SYNTHETIC,
+                )
+                && !method.is_generic
+                && !method.name.starts_with('<')
+        })
+        .map(|method| {
+            let mut parameters = method
+                .parameters
+                .iter()
+                .enumerate()
+                .map(
+                    |(i, parameter)| match method.parameter_type_variables.get(i) {
+                        Some(Some(variable)) => argument(variable),
+                        _ => {
+                            import(parameter, &mut imports);
+                            parameter.to_string()
+                        }
+                    },
+                )
+                .collect::<Vec<String>>();
+            if method.flags.contains(MethodFlags::ACC_VARARGS)
+                && let Some(last) = parameters.last_mut()
+                && let Some(component) = last.strip_suffix("[]")
+            {
+                *last = format!("{component}...");
+            }
+
+            let return_type = match &method.return_type_variable {
+                Some(variable) => argument(variable),
+                None => {
+                    import(&method.return_type, &mut imports);
+                    method.return_type.to_string()
+                }
+            };
+            let exceptions = method
+                .exceptions
+                .iter()
+                .map(|exception| {
+                    import(&JavaType::Class(exception.clone()), &mut imports);
+                    exception
+                        .rsplit('.')
+                        .next()
+                        .unwrap_or(exception)
+                        .to_string()
+                })
+                .collect();
+
+            Delegate {
+                name: method.name.clone(),
+                return_type,
+                parameters,
+                exceptions,
+            }
+        })
+        .collect();
+
+    (delegates, imports)
+}
+
+/// Instance methods of the type body `body` that other types can call, generic methods are
+/// left out
+pub fn source_delegates(body: Node, source: &str) -> Vec<Delegate> {
+    let is_interface = body.kind() == "interface_body";
+    let text = |node: Node| source[node.byte_range()].to_string();
+
+    declarations(body)
+        .into_iter()
+        .filter(|declaration| {
+            declaration.kind() == "method_declaration"
+                && declaration.child_by_field_name("type_parameters").is_none()
+                && !has_modifier(*declaration, "static")
+                && !has_modifier(*declaration, "private")
+                && (is_interface || has_modifier(*declaration, "public"))
+        })
+        .filter_map(|declaration| {
+            let mut cursor = declaration.walk();
+            let exceptions = declaration
+                .children(&mut cursor)
+                .find(|child| child.kind() == "throws")
+                .map(|throws| {
+                    let mut cursor = throws.walk();
+                    throws.named_children(&mut cursor).map(text).collect()
+                })
+                .unwrap_or_default();
+            let dimensions = declaration
+                .child_by_field_name("dimensions")
+                .map(text)
+                .unwrap_or_default();
+
+            Some(Delegate {
+                name: text(declaration.child_by_field_name("name")?),
+                return_type: text(declaration.child_by_field_name("type")?) + &dimensions,
+                parameters: parameter_types(declaration, source),
+                exceptions,
+            })
+        })
+        .collect()
+}
+
+/// Whether the method `name` of `Object` is never delegated
+pub fn is_object_method(name: &str) -> bool {
+    OBJECT_METHODS.contains(&name)
+}
+
+/// Members of a class or enum body, the ones of enums follow the constants
+pub fn declarations(body: Node) -> Vec<Node> {
+    let mut cursor = body.walk();
+    let children = body.named_children(&mut cursor).collect::<Vec<Node>>();
+
+    children
+        .into_iter()
+        .flat_map(|child| {
+            if child.kind() == "enum_body_declarations" {
+                let mut cursor = child.walk();
+                child.named_children(&mut cursor).collect()
+            } else {
+                vec![child]
+            }
+        })
+        .collect()
+}
+
+/// Types of the parameters of a method or constructor as they are written, varargs keep
+/// their ellipsis
+pub fn parameter_types(declaration: Node, source: &str) -> Vec<String> {
+    let Some(parameters) = declaration.child_by_field_name("parameters") else {
+        return Vec::new();
+    };
+    let mut cursor = parameters.walk();
+
+    parameters
+        .named_children(&mut cursor)
+        .filter_map(|parameter| match parameter.kind() {
+            "formal_parameter" => {
+                let declared = parameter.child_by_field_name("type")?;
+                let dimensions = parameter
+                    .child_by_field_name("dimensions")
+                    .map(|dimensions| &source[dimensions.byte_range()])
+                    .unwrap_or_default();
+                Some(format!("{}{dimensions}", &source[declared.byte_range()]))
+            }
+            "spread_parameter" => {
+                let mut cursor = parameter.walk();
+                let declared = parameter.named_children(&mut cursor).find(|child| {
+                    !matches!(
+                        child.kind(),
+                        "modifiers" | "annotation" | "marker_annotation" | "variable_declarator"
+                    )
+                })?;
+                Some(format!("{}...", &source[declared.byte_range()]))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Add the class of `java_type` to `imports` unless it is visible in every compilation unit,
+/// nested classes are imported through their outermost class
+fn import(java_type: &JavaType, imports: &mut Vec<String>) {
+    match java_type {
+        JavaType::Array(component) => import(component, imports),
+        JavaType::Class(fqdn) => {
+            let outer = fqdn.split('$').next().unwrap_or(fqdn);
+            if outer
+                .rsplit_once('.')
+                .is_some_and(|(package, _)| package != "java.lang")
+                && !imports.iter().any(|import| import == outer)
+            {
+                imports.push(outer.to_string());
+            }
+        }
+        _ => {}
+    }
+}
+
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
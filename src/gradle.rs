@@ -1,20 +1,107 @@
-use crate::configuration::{GRADLE_CLASSPATH_TASK_NAME, gradle_init_script_path};
-use std::process::Command;
+use crate::{
+    build_tool::GRADLE,
+    configuration::{
+        Configuration, GRADLE_CLASSPATH_TASK_NAME, GRADLE_SOURCE_SET_PREFIX,
+        gradle_init_script_path,
+    },
+    errors::ProjectError,
+    project::{Module, Project},
+};
+use serde::Deserialize;
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
 
-pub fn generate_claspath() -> String {
-    let mut classpath = String::from("build/classes");
+/// Source set of a gradle project as printed by the init script
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SourceSet {
+    project: String,
+    source_set: String,
+    project_directory: PathBuf,
+    source_directories: Vec<PathBuf>,
+    output_directories: Vec<PathBuf>,
+    compile_classpath: Vec<PathBuf>,
+    annotation_processor_path: Vec<PathBuf>,
+    generated_sources_directory: Option<PathBuf>,
+    java_version: Option<String>,
+}
+
+impl SourceSet {
+    fn into_module(self) -> Option<Module> {
+        let mut output_directories = self.output_directories.into_iter();
+        let output_directory = output_directories.next()?;
+        // Output of other languages (e.g. kotlin) must be visible to java sources
+        let mut classpath = vec![output_directory.clone()];
+        classpath.extend(output_directories);
+        classpath.extend(self.compile_classpath);
+
+        let generated_sources = self.generated_sources_directory.unwrap_or_else(|| {
+            self.project_directory
+                .join("build/generated/sources/annotationProcessor/java")
+                .join(&self.source_set)
+        });
+
+        Some(Module {
+            name: format!("{}:{}", self.project.trim_end_matches(':'), self.source_set),
+            root: self.project_directory,
+            source_roots: self.source_directories,
+            output_directory,
+            classpath,
+            release: self.java_version,
+            processor_path: self.annotation_processor_path,
+            generated_sources,
+        })
+    }
+}
 
-    // TODO: try to search for gradle and gradlew
-    if let Ok(output) = Command::new("gradle")
+/// Find the source sets of all projects of the build that starts at `root`
+pub fn project(root: &Path, configuration: &Configuration) -> Result<Project, ProjectError> {
+    let executable = GRADLE.find(root, configuration.gradle.executable.as_deref())?;
+    let output = match Command::new(executable)
+        .current_dir(root)
         .arg(GRADLE_CLASSPATH_TASK_NAME)
         .arg("--quiet")
         .arg("--init-script")
         .arg(gradle_init_script_path().unwrap_or_default())
         .output()
     {
-        classpath += ":";
-        classpath += str::from_utf8(&output.stdout).unwrap_or_default();
+        Ok(output) => output,
+        Err(error) => {
+            eprintln!("Could not generate classpath with gradle: {error}");
+            return Ok(Project::new(vec![fallback_module(root)]));
+        }
+    };
+
+    let modules = str::from_utf8(&output.stdout)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.strip_prefix(GRADLE_SOURCE_SET_PREFIX))
+        .filter_map(|json| serde_json::from_str::<SourceSet>(json).ok())
+        .filter_map(SourceSet::into_module)
+        .collect::<Vec<Module>>();
+
+    if modules.is_empty() {
+        Ok(Project::new(vec![fallback_module(root)]))
+    } else {
+        Ok(Project::new(modules))
     }
+}
 
-    classpath
+/// Module that is used when gradle could not describe the project
+fn fallback_module(root: &Path) -> Module {
+    Module {
+        name: root
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        root: root.to_path_buf(),
+        source_roots: vec![root.to_path_buf()],
+        output_directory: root.join("build/classes"),
+        classpath: vec![root.join("build/classes")],
+        release: None,
+        processor_path: Vec::new(),
+        generated_sources: root.join("build/generated/sources/annotationProcessor/java/main"),
+    }
 }
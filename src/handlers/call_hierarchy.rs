@@ -0,0 +1,187 @@
+use lsp_types::{
+    CallHierarchyIncomingCall, CallHierarchyIncomingCallsParams, CallHierarchyItem,
+    CallHierarchyOutgoingCall, CallHierarchyOutgoingCallsParams, CallHierarchyPrepareParams,
+    Position, SymbolKind, Url,
+};
+
+use crate::{
+    calls::{self, Call, METHODS, Method},
+    generate::parameter_types,
+    state::State,
+};
+
+pub fn handle_prepare_call_hierarchy(
+    state: &mut State,
+    params: CallHierarchyPrepareParams,
+) -> Option<Vec<CallHierarchyItem>> {
+    let params = params.text_document_position_params;
+    let state = &*state;
+    let document = state.document(params.text_document.uri.as_str())?;
+
+    // The name of a declaration or of an invocation
+    let (_, item) = document.analyze(params.position, |root, source, offset| {
+        let name = root
+            .descendant_for_byte_range(offset, offset)
+            .filter(|node| node.kind() == "identifier")?;
+        let parent = name
+            .parent()
+            .filter(|parent| parent.child_by_field_name("name") == Some(name))?;
+
+        if METHODS.contains(&parent.kind()) {
+            let (method, _) = calls::methods(root, source)
+                .into_iter()
+                .find(|(_, declaration)| *declaration == parent)?;
+            declaration(state, &method.owner, |declared| *declared == method)
+        } else if parent.kind() == "method_invocation" {
+            let invocation = calls::invocation(state, root, source, parent)?;
+            declaration(state, &invocation.owner, |declared| {
+                invocation.invokes(declared)
+            })
+        } else {
+            None
+        }
+    })?;
+
+    Some(vec![item])
+}
+
+pub fn handle_call_hierarchy_incoming_calls(
+    state: &mut State,
+    params: CallHierarchyIncomingCallsParams,
+) -> Option<Vec<CallHierarchyIncomingCall>> {
+    let method = method(&params.item)?;
+    state.index_stale_calls();
+    let state = &*state;
+
+    // Calls are grouped by the method that makes them
+    let mut callers: Vec<(&Url, Vec<&Call>)> = Vec::new();
+    for (uri, call) in state.incoming_calls(&method) {
+        match callers
+            .iter_mut()
+            .find(|(caller_uri, calls)| *caller_uri == uri && calls[0].caller == call.caller)
+        {
+            Some((_, calls)) => calls.push(call),
+            None => callers.push((uri, vec![call])),
+        }
+    }
+    for (_, calls) in &mut callers {
+        calls.sort_by_key(|call| call.invocation.start);
+    }
+    callers.sort_by_key(|(uri, calls)| (uri.as_str(), calls[0].invocation.start));
+
+    Some(
+        callers
+            .into_iter()
+            .filter_map(|(uri, calls)| {
+                let caller = &calls[0].caller;
+                let (_, from) = declaration(state, &caller.owner, |declared| declared == caller)?;
+                let from_ranges = state.with_document(uri, |document| {
+                    Some(
+                        calls
+                            .iter()
+                            .map(|call| document.range(call.invocation.start, call.invocation.end))
+                            .collect(),
+                    )
+                })?;
+
+                Some(CallHierarchyIncomingCall { from, from_ranges })
+            })
+            .collect(),
+    )
+}
+
+pub fn handle_call_hierarchy_outgoing_calls(
+    state: &mut State,
+    params: CallHierarchyOutgoingCallsParams,
+) -> Option<Vec<CallHierarchyOutgoingCall>> {
+    let method = method(&params.item)?;
+    let state = &*state;
+
+    state.with_document(&params.item.uri, |document| {
+        let calls = document.analyze(Position::default(), |root, source, _| {
+            let (caller, declaration) = calls::methods(root, source)
+                .into_iter()
+                .find(|(declared, _)| *declared == method)?;
+            Some(calls::outgoing_calls(
+                state,
+                root,
+                source,
+                caller,
+                declaration,
+            ))
+        })?;
+
+        // Calls are grouped by the method that they invoke
+        let mut callees: Vec<(Method, CallHierarchyOutgoingCall)> = Vec::new();
+        for call in calls {
+            let invocation = call.invocation;
+            let range = document.range(invocation.start, invocation.end);
+            if let Some((_, callee)) = callees
+                .iter_mut()
+                .find(|(callee, _)| invocation.invokes(callee))
+            {
+                callee.from_ranges.push(range);
+            } else if let Some((callee, to)) = declaration(state, &invocation.owner, |declared| {
+                invocation.invokes(declared)
+            }) {
+                callees.push((
+                    callee,
+                    CallHierarchyOutgoingCall {
+                        to,
+                        from_ranges: vec![range],
+                    },
+                ));
+            }
+        }
+
+        Some(callees.into_iter().map(|(_, call)| call).collect())
+    })
+}
+
+/// Item of the method of the type `owner` from the sources that satisfies `predicate`, it
+/// keeps the method as data for the follow-up requests
+fn declaration(
+    state: &State,
+    owner: &str,
+    predicate: impl Fn(&Method) -> bool,
+) -> Option<(Method, CallHierarchyItem)> {
+    let uri = &state.source(owner)?.location.uri;
+
+    state.with_document(uri, |document| {
+        let (method, kind, parameters, range, name) =
+            document.analyze(Position::default(), |root, source, _| {
+                let (method, declaration) = calls::methods(root, source)
+                    .into_iter()
+                    .find(|(method, _)| method.owner == owner && predicate(method))?;
+                let name = declaration.child_by_field_name("name")?;
+                let kind = match declaration.kind() {
+                    "method_declaration" => SymbolKind::METHOD,
+                    _ => SymbolKind::CONSTRUCTOR,
+                };
+                Some((
+                    method,
+                    kind,
+                    parameter_types(declaration, source),
+                    declaration.byte_range(),
+                    name.byte_range(),
+                ))
+            })?;
+
+        let item = CallHierarchyItem {
+            name: format!("{}({})", method.name, parameters.join(", ")),
+            kind,
+            tags: None,
+            detail: Some(owner.to_string()),
+            uri: uri.clone(),
+            range: document.range(range.start, range.end),
+            selection_range: document.range(name.start, name.end),
+            data: serde_json::to_value(&method).ok(),
+        };
+
+        Some((method, item))
+    })
+}
+
+fn method(item: &CallHierarchyItem) -> Option<Method> {
+    serde_json::from_value(item.data.clone()?).ok()
+}
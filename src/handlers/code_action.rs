@@ -0,0 +1,846 @@
+use std::collections::HashMap;
+
+use lsp_types::{
+    AnnotatedTextEdit, ChangeAnnotation, CodeAction, CodeActionKind, CodeActionOrCommand,
+    CodeActionParams, CodeActionResponse, Diagnostic, DocumentChanges, NumberOrString, OneOf,
+    OptionalVersionedTextDocumentIdentifier, Position, Range, TextDocumentEdit, TextEdit, Url,
+    WorkspaceEdit,
+};
+use tree_sitter::Node;
+
+use crate::{
+    configuration::ImportOrder,
+    document::Document,
+    formatter::{Replacement, Style},
+    generate::{self, Delegate, Field},
+    hierarchy,
+    imports::{Resolution, Scope},
+    organize_imports::{add_imports, is_type_name},
+    quick_fix::{self, Symbol},
+    refactor,
+    source_edit::{insert_member, insert_member_at, member_unit},
+    state::State,
+    symbols::TypeIndex,
+};
+
+pub fn handle_code_action(
+    state: &mut State,
+    params: CodeActionParams,
+) -> Option<CodeActionResponse> {
+    let uri = params.text_document.uri;
+    let document = state.document(uri.as_str())?;
+    let only = params.context.only.unwrap_or_default();
+    let mut actions = Vec::new();
+
+    if is_requested(&only, &CodeActionKind::QUICKFIX) {
+        for diagnostic in &params.context.diagnostics {
+            actions.extend(
+                quick_fixes(state, document, diagnostic)
+                    .into_iter()
+                    .map(CodeActionOrCommand::CodeAction),
+            );
+        }
+    }
+
+    actions.extend(
+        refactorings(state, document, params.range, &only)
+            .into_iter()
+            .map(CodeActionOrCommand::CodeAction),
+    );
+
+    actions.extend(
+        generations(state, document, params.range, &only)
+            .into_iter()
+            .map(CodeActionOrCommand::CodeAction),
+    );
+
+    actions.extend(
+        overrides(state, document, params.range, &only)
+            .into_iter()
+            .map(CodeActionOrCommand::CodeAction),
+    );
+
+    if is_explicitly_requested(&only, &CodeActionKind::SOURCE_ORGANIZE_IMPORTS)
+        && let Some(edit) =
+            document.organize_imports(&state.configuration().formatter.import_order, state)
+    {
+        actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+            title: "Organize imports".to_string(),
+            kind: Some(CodeActionKind::SOURCE_ORGANIZE_IMPORTS),
+            edit: Some(WorkspaceEdit {
+                changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }));
+    }
+
+    Some(actions)
+}
+
+/// Whether the client asks for actions of `kind`, kinds are hierarchical like
+/// `source.organizeImports` and an empty filter asks for all actions
+fn is_requested(only: &[CodeActionKind], kind: &CodeActionKind) -> bool {
+    only.is_empty()
+        || only.iter().any(|requested| {
+            kind.as_str() == requested.as_str()
+                || kind
+                    .as_str()
+                    .strip_prefix(requested.as_str())
+                    .is_some_and(|rest| rest.starts_with('.'))
+        })
+}
+
+/// Whether the client asks for actions of `kind` by name, source actions that apply to the
+/// whole file are only computed then
+fn is_explicitly_requested(only: &[CodeActionKind], kind: &CodeActionKind) -> bool {
+    !only.is_empty() && is_requested(only, kind)
+}
+
+/// Fixes for a diagnostic of javac, they are chosen by its code and read the symbol and
+/// location that javac reported from its data. The positions of the diagnostic have to
+/// belong to the current content of the document.
+fn quick_fixes(state: &State, document: &Document, diagnostic: &Diagnostic) -> Vec<CodeAction> {
+    let Some(NumberOrString::String(code)) = &diagnostic.code else {
+        return Vec::new();
+    };
+    // Diagnostics without a version were published while the document was not open or had
+    // unsaved edits, they belong to the content on disk
+    let version = diagnostic
+        .data
+        .as_ref()
+        .and_then(|data| data.get("version"))
+        .and_then(|version| version.as_i64());
+    let is_current = match version {
+        Some(version) => version == i64::from(document.version()),
+        None => document.is_saved(),
+    };
+    if !is_current {
+        return Vec::new();
+    }
+    // The details are appended to the message as further lines
+    let message = diagnostic.message.lines().next().unwrap_or_default();
+    let detail = |key: &str| {
+        diagnostic
+            .data
+            .as_ref()
+            .and_then(|data| data.get(key))
+            .and_then(|value| value.as_str())
+    };
+    let position = diagnostic.range.start;
+    let style = Style::from(&state.configuration().formatter);
+    let unit = " ".repeat(style.indent_width);
+    let order = &style.import_order;
+    let fix = |title: String, edits: Vec<TextEdit>| {
+        quick_fix(
+            title,
+            diagnostic,
+            HashMap::from([(document.uri.clone(), edits)]),
+        )
+    };
+
+    match code.as_str() {
+        "cant.resolve" => {
+            let Some(symbol) = detail("symbol").and_then(Symbol::parse) else {
+                return Vec::new();
+            };
+            match symbol {
+                // Types are also reported as variables when they qualify a member
+                Symbol::Class(name) | Symbol::Variable(name) if is_type_name(name) => state
+                    .find_types(name)
+                    .into_iter()
+                    .filter_map(|fqdn| {
+                        let edit = import(document, &fqdn, order)?;
+                        Some(fix(format!("Import '{fqdn}'"), vec![edit]))
+                    })
+                    .collect(),
+                Symbol::Class(_) => Vec::new(),
+                _ => detail("location")
+                    .and_then(|location| {
+                        create_member(state, document, diagnostic, &symbol, location, &unit)
+                    })
+                    .into_iter()
+                    .collect(),
+            }
+        }
+        "unreported.exception" => {
+            let Some(exception) = message
+                .strip_prefix("unreported exception ")
+                .and_then(|rest| rest.split(';').next())
+            else {
+                return Vec::new();
+            };
+            let import = missing_import(state, document, exception, order);
+            let with_import = |edit: TextEdit| import.iter().cloned().chain([edit]).collect();
+
+            let mut fixes = Vec::new();
+            if let Some(throws) = edit(document, position, |root, _, offset| {
+                quick_fix::add_throws(root, offset, exception)
+            }) {
+                fixes.push(fix(
+                    format!("Add 'throws {exception}'"),
+                    with_import(throws),
+                ));
+            }
+            if let Some(try_catch) = edit(document, position, |root, source, offset| {
+                quick_fix::surround_with_try(root, source, offset, exception, &unit)
+            }) {
+                fixes.push(fix(
+                    "Surround with try/catch".to_string(),
+                    with_import(try_catch),
+                ));
+            }
+
+            fixes
+        }
+        "does.not.override.abstract" => {
+            let Some((signature, owner)) = message
+                .split_once(" does not override abstract method ")
+                .and_then(|(_, rest)| rest.rsplit_once(" in "))
+            else {
+                return Vec::new();
+            };
+            // Type parameters are replaced by the arguments that the class passes
+            let return_type = resolve_type(state, document, position, owner)
+                .and_then(|fqdn| return_type(state, &fqdn, signature))
+                .map(|(declared, parameter)| {
+                    parameter
+                        .and_then(|index| {
+                            document.analyze(position, |root, source, offset| {
+                                quick_fix::supertype_argument(root, source, offset, owner, index)
+                            })
+                        })
+                        .unwrap_or(declared)
+                })
+                .unwrap_or("void".to_string());
+
+            edit(document, position, |root, source, offset| {
+                quick_fix::implement_method(root, source, offset, signature, &return_type, &unit)
+            })
+            .map(|edit| fix(format!("Implement method '{signature}'"), vec![edit]))
+            .into_iter()
+            .collect()
+        }
+        "prob.found.req" => {
+            let Some(target) = quick_fix::required_type(message) else {
+                return Vec::new();
+            };
+
+            edit(document, position, |root, source, offset| {
+                quick_fix::add_cast(root, source, offset, target)
+            })
+            .map(|edit| fix(format!("Cast to '{target}'"), vec![edit]))
+            .into_iter()
+            .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn quick_fix(
+    title: String,
+    diagnostic: &Diagnostic,
+    changes: HashMap<Url, Vec<TextEdit>>,
+) -> CodeAction {
+    CodeAction {
+        title,
+        kind: Some(CodeActionKind::QUICKFIX),
+        diagnostics: Some(vec![diagnostic.clone()]),
+        edit: Some(WorkspaceEdit {
+            changes: Some(changes),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Kind, title and edits of a refactoring of the selection in a syntax tree and its source
+type Refactoring<'a> = (
+    &'a str,
+    &'a str,
+    &'a dyn Fn(Node, &str, usize, usize) -> Option<Vec<Replacement>>,
+);
+
+/// Refactorings of the selection `range`, their edits are bound to the version of the
+/// document and annotated for review if the client supports it
+fn refactorings(
+    state: &State,
+    document: &Document,
+    range: Range,
+    only: &[CodeActionKind],
+) -> Vec<CodeAction> {
+    let unit = " ".repeat(Style::from(&state.configuration().formatter).indent_width);
+    let refactorings: [Refactoring; 4] = [
+        (
+            "refactor.extract.variable",
+            "Extract to local variable",
+            &|root, source, start, end| refactor::extract_variable(root, source, start, end),
+        ),
+        (
+            "refactor.extract.constant",
+            "Extract to constant",
+            &|root, source, start, end| refactor::extract_constant(root, source, start, end, &unit),
+        ),
+        (
+            "refactor.extract.method",
+            "Extract to method",
+            &|root, source, start, end| refactor::extract_method(root, source, start, end, &unit),
+        ),
+        (
+            "refactor.inline",
+            "Inline local variable",
+            &|root, source, start, _| refactor::inline_variable(root, source, start),
+        ),
+    ];
+
+    refactorings
+        .into_iter()
+        .filter(|(kind, _, _)| is_requested(only, &CodeActionKind::from(kind.to_string())))
+        .filter_map(|(kind, title, refactoring)| {
+            let edits = document
+                .analyze_range(range, refactoring)?
+                .into_iter()
+                .map(|replacement| document.text_edit(replacement))
+                .collect::<Vec<TextEdit>>();
+
+            Some(CodeAction {
+                title: title.to_string(),
+                kind: Some(CodeActionKind::from(kind.to_string())),
+                edit: Some(refactoring_edit(state, document, title, edits)),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+fn refactoring_edit(
+    state: &State,
+    document: &Document,
+    title: &str,
+    edits: Vec<TextEdit>,
+) -> WorkspaceEdit {
+    if !state.change_annotations {
+        return WorkspaceEdit {
+            changes: Some(HashMap::from([(document.uri.clone(), edits)])),
+            ..Default::default()
+        };
+    }
+
+    let annotation = title.to_string();
+    WorkspaceEdit {
+        document_changes: Some(DocumentChanges::Edits(vec![TextDocumentEdit {
+            text_document: OptionalVersionedTextDocumentIdentifier {
+                uri: document.uri.clone(),
+                version: Some(document.version()),
+            },
+            edits: edits
+                .into_iter()
+                .map(|text_edit| {
+                    OneOf::Right(AnnotatedTextEdit {
+                        text_edit,
+                        annotation_id: annotation.clone(),
+                    })
+                })
+                .collect(),
+        }])),
+        change_annotations: Some(HashMap::from([(
+            annotation.clone(),
+            ChangeAnnotation {
+                label: annotation,
+                needs_confirmation: Some(true),
+                description: None,
+            },
+        )])),
+        ..Default::default()
+    }
+}
+
+/// Members that can be generated into the class at the selection `range` from its fields,
+/// they are inserted at the cursor if it is between members and at the end otherwise
+fn generations(
+    state: &State,
+    document: &Document,
+    range: Range,
+    only: &[CodeActionKind],
+) -> Vec<CodeAction> {
+    let formatter = &state.configuration().formatter;
+    let style = Style::from(formatter);
+    let order = &formatter.import_order;
+    let requested = |kind: &str| is_requested(only, &CodeActionKind::from(kind.to_string()));
+
+    let generations = document.analyze_range(range, |root, source, start, end| {
+        let body = quick_fix::type_body(root, start)?;
+        let declaration = body.parent()?;
+        let is_enum = declaration.kind() == "enum_declaration";
+        if !is_enum && declaration.kind() != "class_declaration" {
+            return None;
+        }
+        let class = &source[declaration.child_by_field_name("name")?.byte_range()];
+        let is_generic = declaration.child_by_field_name("type_parameters").is_some();
+        let unit = member_unit(body, source).unwrap_or_else(|| " ".repeat(style.indent_width));
+        let fields = generate::fields(body, source);
+        let selected = generate::selected(&fields, start, end);
+        let instance_fields = selected
+            .iter()
+            .copied()
+            .filter(|field| !field.is_static)
+            .collect::<Vec<&Field>>();
+        let declared = generate::declared(body, source);
+
+        let generate = |members: Vec<String>, imports: &[&str]| {
+            if members.is_empty() {
+                return None;
+            }
+            let mut replacements = vec![insert_member_at(
+                body,
+                source,
+                start,
+                &members.join("\n\n"),
+                &unit,
+            )?];
+            replacements.extend(add_imports(root, source, imports, order));
+            Some(replacements)
+        };
+        let mut generations = Vec::new();
+
+        if requested("source.generate.accessors") {
+            // Constants need no accessors
+            let fields = selected
+                .iter()
+                .copied()
+                .filter(|field| !(field.is_static && field.is_final))
+                .collect::<Vec<&Field>>();
+            let getters = generate::accessors(class, &fields, &declared, true, false, &unit);
+            let setters = generate::accessors(class, &fields, &declared, false, true, &unit);
+            if !getters.is_empty() && !setters.is_empty() {
+                let accessors = generate::accessors(class, &fields, &declared, true, true, &unit);
+                generations.push((
+                    "source.generate.accessors",
+                    "Generate getters and setters".to_string(),
+                    generate(accessors, &[]),
+                ));
+            }
+            generations.push((
+                "source.generate.accessors",
+                "Generate getters".to_string(),
+                generate(getters, &[]),
+            ));
+            generations.push((
+                "source.generate.accessors",
+                "Generate setters".to_string(),
+                generate(setters, &[]),
+            ));
+        }
+
+        if requested("source.generate.constructors") {
+            // Final fields with a value can not be assigned again
+            let fields = instance_fields
+                .iter()
+                .copied()
+                .filter(|field| !(field.is_final && field.has_value))
+                .collect::<Vec<&Field>>();
+            let exists = declared.iter().any(|constructor| {
+                constructor.name == class
+                    && constructor
+                        .parameters
+                        .iter()
+                        .eq(fields.iter().map(|field| &field.field_type))
+            });
+            if !fields.is_empty() && !exists {
+                generations.push((
+                    "source.generate.constructors",
+                    "Generate constructor using fields".to_string(),
+                    generate(
+                        vec![generate::constructor(class, is_enum, &fields, &unit)],
+                        &[],
+                    ),
+                ));
+            }
+        }
+
+        // Enums inherit final implementations of equals and hashCode
+        if requested("source.generate.hashCodeEquals")
+            && !is_enum
+            && !generate::is_declared(&declared, "equals", 1)
+            && !generate::is_declared(&declared, "hashCode", 0)
+        {
+            let (members, imports) =
+                generate::equals_hash_code(class, is_generic, &instance_fields, &unit);
+            generations.push((
+                "source.generate.hashCodeEquals",
+                "Generate equals() and hashCode()".to_string(),
+                generate(members, &imports),
+            ));
+        }
+
+        if requested("source.generate.toString") && !generate::is_declared(&declared, "toString", 0)
+        {
+            let (member, imports) = generate::to_string(class, &instance_fields, &unit);
+            generations.push((
+                "source.generate.toString",
+                "Generate toString()".to_string(),
+                generate(vec![member], &imports),
+            ));
+        }
+
+        // Delegates are generated for the field at the cursor only
+        if requested("source.generate.delegateMethods")
+            && let Some(field) = fields.iter().find(|field| {
+                field.declaration.start_byte() <= start && start <= field.declaration.end_byte()
+            })
+            && let Some((delegates, imports)) = delegates(state, root, source, &field.field_type)
+        {
+            let members = delegates
+                .iter()
+                .filter(|delegate| {
+                    !generate::is_object_method(&delegate.name)
+                        && !generate::is_declared(
+                            &declared,
+                            &delegate.name,
+                            delegate.parameters.len(),
+                        )
+                })
+                .map(|delegate| generate::delegate_method(&field.name, delegate, &unit))
+                .collect();
+            let imports = imports.iter().map(String::as_str).collect::<Vec<&str>>();
+            generations.push((
+                "source.generate.delegateMethods",
+                format!("Generate delegate methods for '{}'", field.name),
+                generate(members, &imports),
+            ));
+        }
+
+        Some(generations)
+    });
+
+    generations
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(kind, title, replacements)| {
+            let edits = replacements?
+                .into_iter()
+                .map(|replacement| document.text_edit(replacement))
+                .collect();
+
+            Some(CodeAction {
+                title,
+                kind: Some(CodeActionKind::from(kind.to_string())),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(HashMap::from([(document.uri.clone(), edits)])),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Methods that a field of the type `field_type` like `List<String>` can delegate to, and the
+/// types that they need to import into the compilation unit `root`
+fn delegates(
+    state: &State,
+    root: Node,
+    source: &str,
+    field_type: &str,
+) -> Option<(Vec<Delegate>, Vec<String>)> {
+    let (raw, arguments) = match field_type.split_once('<') {
+        Some((raw, arguments)) => (
+            raw.trim(),
+            quick_fix::split_types(arguments.rsplit_once('>')?.0),
+        ),
+        None => (field_type, Vec::new()),
+    };
+    let scope = Scope::new(root, source.as_bytes());
+    let Resolution::Type(fqdn) = scope.resolve_type(raw, state)? else {
+        return None;
+    };
+
+    let (mut delegates, imports) = match state.class(&fqdn) {
+        Some(class) => {
+            let (delegates, imports) = generate::class_delegates(class, &arguments);
+            (delegates, missing_imports(state, &scope, imports))
+        }
+        None => {
+            let uri = state.source(&fqdn)?.location.uri.clone();
+            let name = fqdn.rsplit('.').next()?;
+            let delegates = state.with_document(&uri, |document| {
+                document.analyze(Position::default(), |root, source, _| {
+                    let body = quick_fix::find_type_body(root, source, name)?;
+                    Some(generate::source_delegates(body, source))
+                })
+            })?;
+            (delegates, Vec::new())
+        }
+    };
+
+    // Overloads are declared once even if a class file repeats them
+    let mut signatures = Vec::new();
+    delegates.retain(|delegate| {
+        let signature = (delegate.name.clone(), delegate.parameters.clone());
+        let is_new = !signatures.contains(&signature);
+        signatures.push(signature);
+        is_new
+    });
+
+    Some((delegates, imports))
+}
+
+/// Methods of the supertypes that the class at the selection `range` can override, one
+/// action per method. Stubs are inserted at the cursor if it is between members. There can
+/// be dozens of them, so they are only offered when the client asks for them by name.
+fn overrides(
+    state: &State,
+    document: &Document,
+    range: Range,
+    only: &[CodeActionKind],
+) -> Vec<CodeAction> {
+    let kind = CodeActionKind::from("source.overrideMethods".to_string());
+    if !is_explicitly_requested(only, &kind) {
+        return Vec::new();
+    }
+    let formatter = &state.configuration().formatter;
+    let style = Style::from(formatter);
+
+    let overrides = document.analyze_range(range, |root, source, start, _| {
+        let body = quick_fix::type_body(root, start)?;
+        let declaration = body.parent()?;
+        if !matches!(
+            declaration.kind(),
+            "class_declaration" | "enum_declaration" | "record_declaration"
+        ) {
+            return None;
+        }
+        let unit = member_unit(body, source).unwrap_or_else(|| " ".repeat(style.indent_width));
+        let declared = generate::declared(body, source);
+        let scope = Scope::new(root, source.as_bytes());
+        let same_type = |a: &str, b: &str| a.split_whitespace().eq(b.split_whitespace());
+
+        let overrides = hierarchy::overridable_methods(state, root, source, declaration)
+            .into_iter()
+            .filter(|method| {
+                !declared.iter().any(|declared| {
+                    declared.name == method.name
+                        && declared.parameters.len() == method.parameters.len()
+                        && declared
+                            .parameters
+                            .iter()
+                            .zip(&method.parameters)
+                            .all(|(declared, inherited)| same_type(declared, inherited))
+                })
+            })
+            .filter_map(|method| {
+                let stub = generate::override_method(&method, &unit);
+                let imports = missing_imports(state, &scope, method.classes.clone());
+                let imports = imports.iter().map(String::as_str).collect::<Vec<&str>>();
+                let mut replacements = vec![insert_member_at(body, source, start, &stub, &unit)?];
+                replacements.extend(add_imports(root, source, &imports, &formatter.import_order));
+
+                let verb = if method.is_abstract {
+                    "Implement"
+                } else {
+                    "Override"
+                };
+                let owner = method.owner.rsplit(['.', '$']).next()?;
+                let title = format!(
+                    "{verb} '{}({})' from '{owner}'",
+                    method.name,
+                    method.parameters.join(", ")
+                );
+                Some((title, replacements))
+            })
+            .collect::<Vec<(String, Vec<Replacement>)>>();
+        Some(overrides)
+    });
+
+    overrides
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(title, replacements)| CodeAction {
+            title,
+            kind: Some(kind.clone()),
+            edit: Some(WorkspaceEdit {
+                changes: Some(HashMap::from([(
+                    document.uri.clone(),
+                    replacements
+                        .into_iter()
+                        .map(|replacement| document.text_edit(replacement))
+                        .collect(),
+                )])),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Classes of `classes` that are neither visible in `scope` nor in `java.lang`
+fn missing_imports(state: &State, scope: &Scope, classes: Vec<String>) -> Vec<String> {
+    classes
+        .into_iter()
+        .filter(|fqdn| {
+            let (package, name) = fqdn.rsplit_once('.').unwrap_or(("", fqdn));
+            !package.is_empty()
+                && package != "java.lang"
+                && scope.resolve_type(name, state) != Some(Resolution::Type(fqdn.clone()))
+        })
+        .collect()
+}
+
+/// Declare the method or field that javac could not find in the type of `location`. Members
+/// of the own class are private, the ones of other classes of the project public.
+fn create_member(
+    state: &State,
+    document: &Document,
+    diagnostic: &Diagnostic,
+    symbol: &Symbol,
+    location: &str,
+    unit: &str,
+) -> Option<CodeAction> {
+    let position = diagnostic.range.start;
+    let fqdn = resolve_type(
+        state,
+        document,
+        position,
+        quick_fix::location_type(location)?,
+    )?;
+    // Only types of the sources can be changed
+    let target = state.source(&fqdn)?.location.uri.clone();
+    let name = fqdn.rsplit('.').next()?;
+    let visibility = if target == document.uri {
+        "private"
+    } else {
+        "public"
+    };
+
+    let stub = document.analyze(position, |root, source, offset| {
+        let is_static = quick_fix::is_static_access(root, offset, location);
+        quick_fix::member_stub(root, source, offset, symbol, is_static, visibility, unit)
+    })?;
+    let edit = state.with_document(&target, |target| {
+        edit(target, Position::default(), |root, source, _| {
+            insert_member(
+                quick_fix::find_type_body(root, source, name)?,
+                source,
+                &stub,
+                unit,
+            )
+        })
+    })?;
+
+    let title = match symbol {
+        Symbol::Method(member, _) => format!("Create method '{member}' in '{name}'"),
+        Symbol::Variable(member) | Symbol::Class(member) => {
+            format!("Create field '{member}' in '{name}'")
+        }
+    };
+    Some(quick_fix(
+        title,
+        diagnostic,
+        HashMap::from([(target, vec![edit])]),
+    ))
+}
+
+/// Declared return type of the method with `signature` like `compare(T,T)` in the type
+/// `fqdn` and the index of the type parameter of `fqdn` that it refers to
+fn return_type(state: &State, fqdn: &str, signature: &str) -> Option<(String, Option<usize>)> {
+    let (name, parameters) = signature.split_once('(')?;
+    let arity = parameters
+        .trim_end_matches(')')
+        .split(',')
+        .filter(|parameter| !parameter.is_empty())
+        .count();
+
+    if let Some(class) = state.class(fqdn) {
+        let method = class
+            .methods
+            .iter()
+            .find(|method| method.name == name && method.parameters.len() == arity)?;
+        let parameter = method.return_type_variable.as_ref().and_then(|variable| {
+            class
+                .type_parameters
+                .iter()
+                .position(|parameter| parameter == variable)
+        });
+        return Some((method.return_type.to_string(), parameter));
+    }
+
+    let source = state.source(fqdn)?;
+    let simple_name = fqdn.rsplit('.').next()?;
+    state.with_document(&source.location.uri, |document| {
+        document.analyze(Position::default(), |root, source, _| {
+            let text = |node: Node| node.utf8_text(source.as_bytes()).ok();
+            let body = quick_fix::find_type_body(root, source, simple_name)?;
+            let mut cursor = body.walk();
+            let method = body.named_children(&mut cursor).find(|member| {
+                member.kind() == "method_declaration"
+                    && member.child_by_field_name("name").and_then(text) == Some(name)
+                    && member
+                        .child_by_field_name("parameters")
+                        .is_some_and(|parameters| parameters.named_child_count() == arity)
+            })?;
+            let declared = method.child_by_field_name("type").and_then(text)?;
+
+            let parameter = body
+                .parent()
+                .and_then(|declaration| declaration.child_by_field_name("type_parameters"))
+                .and_then(|parameters| {
+                    let mut cursor = parameters.walk();
+                    let names = parameters
+                        .named_children(&mut cursor)
+                        .filter_map(|parameter| parameter.named_child(0).and_then(text))
+                        .collect::<Vec<&str>>();
+                    names.iter().position(|parameter| *parameter == declared)
+                });
+            Some((declared.to_string(), parameter))
+        })
+    })
+}
+
+/// Import of the type with the simple name `name` if it is not visible yet and only one type
+/// has this name
+fn missing_import(
+    state: &State,
+    document: &Document,
+    name: &str,
+    order: &ImportOrder,
+) -> Option<TextEdit> {
+    if resolve_type(state, document, Position::default(), name).is_some() {
+        return None;
+    }
+
+    match state.find_types(name).as_slice() {
+        [fqdn] => import(document, fqdn, order),
+        _ => None,
+    }
+}
+
+fn import(document: &Document, fqdn: &str, order: &ImportOrder) -> Option<TextEdit> {
+    edit(document, Position::default(), |root, source, _| {
+        add_imports(root, source, &[fqdn], order).pop()
+    })
+}
+
+/// Fully qualified name of the type `name` as it is visible in `document`
+fn resolve_type(
+    state: &State,
+    document: &Document,
+    position: Position,
+    name: &str,
+) -> Option<String> {
+    document.analyze(position, |root, source, _| {
+        match Scope::new(root, source.as_bytes()).resolve_type(name, state)? {
+            Resolution::Type(fqdn) => Some(fqdn),
+            _ => None,
+        }
+    })
+}
+
+fn edit(
+    document: &Document,
+    position: Position,
+    change: impl FnOnce(Node, &str, usize) -> Option<Replacement>,
+) -> Option<TextEdit> {
+    document
+        .analyze(position, change)
+        .map(|replacement| document.text_edit(replacement))
+}
@@ -1,5 +1,17 @@
+mod call_hierarchy;
+pub use call_hierarchy::*;
+
+mod code_action;
+pub use code_action::*;
+
 mod notification;
 pub use notification::*;
 
 mod request;
 pub use request::*;
+
+mod task;
+pub use task::*;
+
+mod type_hierarchy;
+pub use type_hierarchy::*;
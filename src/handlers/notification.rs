@@ -1,39 +1,42 @@
 use std::collections::HashMap;
 
 use lsp_types::{
-    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
-    DidSaveTextDocumentParams, Url,
+    DidChangeTextDocumentParams, DidChangeWatchedFilesParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, DidSaveTextDocumentParams, Url,
 };
 
 use crate::{compiler::CompileError, diagnostic, state::State};
 
 pub fn handle_did_open_text_document(state: &mut State, params: DidOpenTextDocumentParams) {
     // TODO: Allow excluding files with configuration
-    match state.register_document(params.text_document.uri.clone(), &params.text_document.text) {
-        Ok(_) => compile_and_publish_compile_errors(state),
+    match state.register_document(
+        params.text_document.uri.clone(),
+        params.text_document.version,
+        &params.text_document.text,
+    ) {
+        Ok(_) => compile_and_publish_compile_errors(state, false),
         Err(error) => eprintln!("{error}"),
     };
 }
 
 pub fn handle_did_change_text_document(state: &mut State, params: DidChangeTextDocumentParams) {
-    for change in params.content_changes {
-        match state.update_document(
-            params.text_document.uri.clone(),
-            params.text_document.version,
-            change.range.as_ref(),
-            &change.text,
-        ) {
-            Ok(_) => {}
-            Err(error) => eprintln!("{error}"),
-        };
+    if let Err(error) = state.update_document(
+        params.text_document.uri,
+        params.text_document.version,
+        &params.content_changes,
+    ) {
+        eprintln!("{error}");
     }
 
-    compile_and_publish_compile_errors(state);
+    compile_and_publish_compile_errors(state, false);
 }
 
 pub fn handle_did_save_text_document(state: &mut State, params: DidSaveTextDocumentParams) {
     if state.document(params.text_document.uri.as_str()).is_some() {
-        compile_and_publish_compile_errors(state);
+        if let Err(error) = state.resync_document(&params.text_document.uri) {
+            eprintln!("{error}");
+        }
+        compile_and_publish_compile_errors(state, false);
     }
 }
 
@@ -41,8 +44,15 @@ pub fn handle_did_close_text_document(state: &mut State, params: DidCloseTextDoc
     state.unregister_document(params.text_document.uri);
 }
 
-fn compile_and_publish_compile_errors(state: &mut State) {
-    let errors = state.compiler.compile(false);
+pub fn handle_did_change_watched_files(state: &mut State, params: DidChangeWatchedFilesParams) {
+    if !params.changes.is_empty() {
+        state.reload_project();
+    }
+}
+
+pub(super) fn compile_and_publish_compile_errors(state: &mut State, force_all: bool) {
+    let errors = state.compiler.compile(force_all);
+    state.index_generated_sources();
     let fixed_documents: HashMap<Url, Vec<CompileError>> = state
         .documents_uri()
         .into_iter()
@@ -50,7 +60,8 @@ fn compile_and_publish_compile_errors(state: &mut State) {
         .map(|url| (url.clone(), Vec::new()))
         .collect();
 
-    diagnostic::publish_compile_errors(&state.sender, errors);
+    let versions = state.document_versions();
+    diagnostic::publish_compile_errors(&state.sender, state.encoding, &versions, errors);
     // Clear documents that were fixed
-    diagnostic::publish_compile_errors(&state.sender, fixed_documents);
+    diagnostic::publish_compile_errors(&state.sender, state.encoding, &versions, fixed_documents);
 }
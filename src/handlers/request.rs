@@ -1,15 +1,406 @@
-use lsp_types::GotoDefinitionParams;
+use jclassfile::methods::MethodFlags;
+use lsp_types::{
+    DocumentFormattingParams, DocumentHighlight, DocumentHighlightParams,
+    DocumentOnTypeFormattingParams, DocumentRangeFormattingParams, FoldingRange,
+    FoldingRangeParams, GotoDefinitionParams, GotoDefinitionResponse, InlayHint, InlayHintLabel,
+    InlayHintParams, Location, Position, Range, SelectionRange, SelectionRangeParams,
+    SemanticTokens, SemanticTokensDelta, SemanticTokensDeltaParams, SemanticTokensFullDeltaResult,
+    SemanticTokensParams, SemanticTokensRangeParams, SemanticTokensRangeResult,
+    SemanticTokensResult, TextEdit,
+    request::{
+        GotoImplementationParams, GotoImplementationResponse, GotoTypeDefinitionParams,
+        GotoTypeDefinitionResponse,
+    },
+};
 
-use crate::state::State;
+use crate::{
+    calls,
+    formatter::Style,
+    highlight,
+    imports::{self, Resolution, Scope},
+    inlay_hints,
+    resolver::Resolver,
+    semantic_tokens,
+    state::State,
+};
 
-pub fn handle_go_to_definition(state: &mut State, params: GotoDefinitionParams) {
-    if let Some(document) = state.document(
+pub fn handle_go_to_definition(
+    state: &mut State,
+    params: GotoDefinitionParams,
+) -> Option<GotoDefinitionResponse> {
+    let params = params.text_document_position_params;
+    let resolution = state
+        .document(params.text_document.uri.as_str())?
+        .resolve(params.position, state)?;
+
+    // An ambiguous name leads to all of its candidates
+    response(declarations(state, &resolution))
+}
+
+pub fn handle_go_to_implementation(
+    state: &mut State,
+    params: GotoImplementationParams,
+) -> Option<GotoImplementationResponse> {
+    let params = params.text_document_position_params;
+    let state = &*state;
+
+    // The name of a method declaration or of an invocation
+    let (owner, name, arguments) = state.document(params.text_document.uri.as_str())?.analyze(
+        params.position,
+        |root, source, offset| {
+            let name = root
+                .descendant_for_byte_range(offset, offset)
+                .filter(|node| node.kind() == "identifier")?;
+            let parent = name
+                .parent()
+                .filter(|parent| parent.child_by_field_name("name") == Some(name))?;
+
+            match parent.kind() {
+                "method_declaration" => calls::methods(root, source)
+                    .into_iter()
+                    .find(|(_, declaration)| *declaration == parent)
+                    .map(|(method, _)| (method.owner, method.name, method.parameters)),
+                "method_invocation" => Some((
+                    Resolver::new(state, root, source, parent)?.invoked_type(parent)?,
+                    source[name.byte_range()].to_string(),
+                    parent.child_by_field_name("arguments")?.named_child_count(),
+                )),
+                _ => None,
+            }
+        },
+    )?;
+
+    response(implementations(state, &owner, &name, arguments))
+}
+
+pub fn handle_go_to_type_definition(
+    state: &mut State,
+    params: GotoTypeDefinitionParams,
+) -> Option<GotoTypeDefinitionResponse> {
+    let params = params.text_document_position_params;
+    let state = &*state;
+
+    let fqdn = state.document(params.text_document.uri.as_str())?.analyze(
+        params.position,
+        |root, source, offset| {
+            let node = root.descendant_for_byte_range(offset, offset)?;
+            // Type names are their own type
+            if node.kind() == "type_identifier" {
+                let scope = Scope::new(root, source.as_bytes());
+                return match imports::resolve(node, source.as_bytes(), &scope, state)? {
+                    Resolution::Type(fqdn) => Some(fqdn),
+                    _ => None,
+                };
+            }
+
+            // The name of a field access or an invocation stands for the whole expression
+            let expression = match node.parent() {
+                Some(parent)
+                    if parent.kind() == "string_literal"
+                        || (matches!(parent.kind(), "field_access" | "method_invocation")
+                            && (parent.child_by_field_name("field") == Some(node)
+                                || parent.child_by_field_name("name") == Some(node))) =>
+                {
+                    parent
+                }
+                _ => node,
+            };
+
+            Resolver::new(state, root, source, node)?.expression_type(expression)
+        },
+    )?;
+
+    let location = match state.source(&fqdn) {
+        Some(source) => source.location.clone(),
+        None => state.class_location(&fqdn, None)?,
+    };
+    Some(GotoTypeDefinitionResponse::Scalar(location))
+}
+
+pub fn handle_document_highlight(
+    state: &mut State,
+    params: DocumentHighlightParams,
+) -> Option<Vec<DocumentHighlight>> {
+    let params = params.text_document_position_params;
+    let document = state.document(params.text_document.uri.as_str())?;
+    let highlights = document.analyze(params.position, |root, source, offset| {
+        highlight::highlights(state, root, source, offset)
+    })?;
+
+    Some(
+        highlights
+            .into_iter()
+            .map(|(range, kind)| DocumentHighlight {
+                range: document.range(range.start, range.end),
+                kind: Some(kind),
+            })
+            .collect(),
+    )
+}
+
+pub fn handle_inlay_hint(state: &mut State, params: InlayHintParams) -> Option<Vec<InlayHint>> {
+    let document = state.document(params.text_document.uri.as_str())?;
+    let hints = document.analyze_range(params.range, |root, source, start, end| {
+        Some(inlay_hints::hints(
+            state,
+            root,
+            source,
+            start,
+            end,
+            &state.configuration().inlay_hints,
+        ))
+    })?;
+
+    Some(
+        hints
+            .into_iter()
+            .map(|hint| InlayHint {
+                position: document.range(hint.offset, hint.offset).start,
+                label: InlayHintLabel::String(hint.label),
+                kind: Some(hint.kind),
+                text_edits: None,
+                tooltip: None,
+                padding_left: None,
+                padding_right: Some(hint.is_prefix),
+                data: None,
+            })
+            .collect(),
+    )
+}
+
+pub fn handle_folding_range(
+    state: &mut State,
+    params: FoldingRangeParams,
+) -> Option<Vec<FoldingRange>> {
+    Some(
+        state
+            .document(params.text_document.uri.as_str())?
+            .folding_ranges(),
+    )
+}
+
+pub fn handle_selection_range(
+    state: &mut State,
+    params: SelectionRangeParams,
+) -> Option<Vec<SelectionRange>> {
+    let document = state.document(params.text_document.uri.as_str())?;
+
+    // The result has to contain a range for every position
+    Some(
         params
-            .text_document_position_params
-            .text_document
-            .uri
-            .as_str(),
-    ) {
-        document.symbol_at_position(params.text_document_position_params.position);
+            .positions
+            .into_iter()
+            .map(|position| {
+                document
+                    .selection_range(position)
+                    .unwrap_or(SelectionRange {
+                        range: Range::new(position, position),
+                        parent: None,
+                    })
+            })
+            .collect(),
+    )
+}
+
+// The style is configured on the server so that all clients format alike, the formatting
+// options of the requests are ignored
+pub fn handle_formatting(
+    state: &mut State,
+    params: DocumentFormattingParams,
+) -> Option<Vec<TextEdit>> {
+    let style = Style::from(&state.configuration().formatter);
+
+    Some(
+        state
+            .document(params.text_document.uri.as_str())?
+            .format(None, &style),
+    )
+}
+
+pub fn handle_range_formatting(
+    state: &mut State,
+    params: DocumentRangeFormattingParams,
+) -> Option<Vec<TextEdit>> {
+    let style = Style::from(&state.configuration().formatter);
+
+    Some(
+        state
+            .document(params.text_document.uri.as_str())?
+            .format(Some(params.range), &style),
+    )
+}
+
+pub fn handle_on_type_formatting(
+    state: &mut State,
+    params: DocumentOnTypeFormattingParams,
+) -> Option<Vec<TextEdit>> {
+    let style = Style::from(&state.configuration().formatter);
+    let position = params.text_document_position;
+
+    Some(
+        state
+            .document(position.text_document.uri.as_str())?
+            .format_on_type(position.position, &params.ch, &style),
+    )
+}
+
+pub fn handle_semantic_tokens_full(
+    state: &mut State,
+    params: SemanticTokensParams,
+) -> Option<SemanticTokensResult> {
+    let uri = params.text_document.uri.as_str();
+    let data = state.document(uri)?.semantic_tokens(state);
+    let tokens = state.document_mut(uri)?.store_semantic_tokens(data);
+
+    Some(SemanticTokensResult::Tokens(tokens))
+}
+
+pub fn handle_semantic_tokens_full_delta(
+    state: &mut State,
+    params: SemanticTokensDeltaParams,
+) -> Option<SemanticTokensFullDeltaResult> {
+    let uri = params.text_document.uri.as_str();
+    let document = state.document(uri)?;
+    let data = document.semantic_tokens(state);
+    // Without the previous tokens the client gets all of them
+    let edit = document
+        .previous_semantic_tokens(&params.previous_result_id)
+        .map(|previous| semantic_tokens::delta(previous, &data));
+    let tokens = state.document_mut(uri)?.store_semantic_tokens(data);
+
+    Some(match edit {
+        Some(edit) => SemanticTokensFullDeltaResult::TokensDelta(SemanticTokensDelta {
+            result_id: tokens.result_id,
+            edits: vec![edit],
+        }),
+        None => SemanticTokensFullDeltaResult::Tokens(tokens),
+    })
+}
+
+pub fn handle_semantic_tokens_range(
+    state: &mut State,
+    params: SemanticTokensRangeParams,
+) -> Option<SemanticTokensRangeResult> {
+    let data = state
+        .document(params.text_document.uri.as_str())?
+        .semantic_tokens_in_range(params.range, state);
+
+    Some(SemanticTokensRangeResult::Tokens(SemanticTokens {
+        result_id: None,
+        data,
+    }))
+}
+
+/// Response with a single location or all of them, `None` without locations
+fn response(locations: Vec<Location>) -> Option<GotoDefinitionResponse> {
+    match locations.len() {
+        0 => None,
+        1 => locations
+            .into_iter()
+            .next()
+            .map(GotoDefinitionResponse::Scalar),
+        _ => Some(GotoDefinitionResponse::Array(locations)),
+    }
+}
+
+/// Methods that override the method `name` of `owner` taking `arguments` in all of its
+/// subtypes from the sources and the classpath, abstract ones are left out. Methods of the
+/// classpath are located at the start of their source files unless they are the only ones.
+fn implementations(state: &State, owner: &str, name: &str, arguments: usize) -> Vec<Location> {
+    let mut locations = Vec::new();
+    let mut classes = Vec::new();
+    let mut pending = state.subtypes(owner).to_vec();
+    let mut visited = Vec::new();
+    while let Some(subtype) = pending.pop() {
+        if visited.contains(&subtype) {
+            continue;
+        }
+        pending.extend(state.subtypes(&subtype).iter().cloned());
+
+        if let Some(source) = state.source(&subtype) {
+            let uri = &source.location.uri;
+            locations.extend(state.with_document(uri, |document| {
+                let range = document.analyze(Position::default(), |root, source, _| {
+                    let (_, declaration) = calls::methods(root, source).into_iter().find(
+                        |(method, declaration)| {
+                            method.owner == subtype
+                                && method.name == name
+                                && method.accepts(arguments)
+                                && declaration.kind() == "method_declaration"
+                                && declaration.child_by_field_name("body").is_some()
+                        },
+                    )?;
+                    Some(declaration.child_by_field_name("name")?.byte_range())
+                })?;
+                Some(Location::new(
+                    uri.clone(),
+                    document.range(range.start, range.end),
+                ))
+            }));
+        } else if let Some(class) = state.class(&subtype)
+            && class.methods.iter().any(|method| {
+                method.name == name
+                    && method.parameters.len() == arguments
+                    && !method.flags.intersects(
+                        MethodFlags::ACC_ABSTRACT
+                            | MethodFlags::ACC_BRIDGE
+                            | MethodFlags::ACC_SYNTHETIC,
+                    )
+            })
+        {
+            classes.push(subtype.clone());
+        }
+        visited.push(subtype);
+    }
+
+    match classes.as_slice() {
+        [class] if locations.is_empty() => {
+            locations.extend(state.class_location(class, Some(name)))
+        }
+        _ => {
+            let classes = classes.iter().map(String::as_str).collect::<Vec<&str>>();
+            locations.extend(state.class_item_locations(&classes).into_iter().flatten());
+        }
+    }
+
+    locations.sort_by(|a, b| (a.uri.as_str(), a.range.start).cmp(&(b.uri.as_str(), b.range.start)));
+    locations
+}
+
+/// Locations of the declarations in the sources of the project that `resolution` refers to,
+/// types of the classpath are declared in the files of [`State::class_location`]
+fn declarations(state: &State, resolution: &Resolution) -> Vec<Location> {
+    match resolution {
+        Resolution::Type(fqdn) => state
+            .source(fqdn)
+            .map(|source| source.location.clone())
+            .or_else(|| state.class_location(fqdn, None))
+            .into_iter()
+            .collect(),
+        Resolution::Member(fqdn, member) if state.source(fqdn).is_none() => state
+            .class_location(fqdn, Some(member))
+            .into_iter()
+            .collect(),
+        Resolution::Member(fqdn, member) => state
+            .source(fqdn)
+            .map(|source| {
+                let members = source
+                    .members
+                    .iter()
+                    .filter(|source_member| &source_member.name == member)
+                    .map(|source_member| source_member.location.clone())
+                    .collect::<Vec<Location>>();
+
+                if members.is_empty() {
+                    vec![source.location.clone()]
+                } else {
+                    members
+                }
+            })
+            .unwrap_or_default(),
+        Resolution::Ambiguous(candidates) => candidates
+            .iter()
+            .flat_map(|candidate| declarations(state, candidate))
+            .collect(),
     }
 }
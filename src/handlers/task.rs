@@ -0,0 +1,41 @@
+use std::collections::HashMap;
+
+use lsp_types::{MessageType, Url};
+
+use super::notification::compile_and_publish_compile_errors;
+use crate::{
+    compiler::{CompileError, Compiler},
+    diagnostic,
+    errors::ProjectError,
+    state::State,
+};
+
+pub fn handle_project_reloaded(state: &mut State, compiler: Result<Compiler, ProjectError>) {
+    match compiler {
+        Ok(compiler) => {
+            // Problems of the build files might be fixed, they are published again on compilation
+            let outdated: HashMap<Url, Vec<CompileError>> = state
+                .compiler
+                .build_errors()
+                .keys()
+                .map(|url| (url.clone(), Vec::new()))
+                .collect();
+            diagnostic::publish_compile_errors(
+                &state.sender,
+                state.encoding,
+                &state.document_versions(),
+                outdated,
+            );
+
+            state.compiler = compiler;
+            state.index_classpath();
+            state.index_sources();
+            compile_and_publish_compile_errors(state, true);
+        }
+        Err(error) => {
+            diagnostic::show_message(&state.sender, MessageType::ERROR, error.to_string())
+        }
+    }
+
+    state.finish_reload();
+}
@@ -0,0 +1,115 @@
+use lsp_types::{
+    Location, SymbolKind, SymbolTag, TypeHierarchyItem, TypeHierarchyPrepareParams,
+    TypeHierarchySubtypesParams, TypeHierarchySupertypesParams,
+};
+use serde_json::Value;
+
+use crate::{
+    imports::Resolution,
+    state::State,
+    symbols::{TypeIndex, TypeKind},
+};
+
+pub fn handle_prepare_type_hierarchy(
+    state: &mut State,
+    params: TypeHierarchyPrepareParams,
+) -> Option<Vec<TypeHierarchyItem>> {
+    let params = params.text_document_position_params;
+    let resolution = state
+        .document(params.text_document.uri.as_str())?
+        .resolve(params.position, state)?;
+
+    // An ambiguous name has a hierarchy for each of its candidates
+    let candidates = match resolution {
+        Resolution::Ambiguous(candidates) => candidates,
+        resolution => vec![resolution],
+    };
+    let items = candidates
+        .iter()
+        .filter_map(|candidate| match candidate {
+            Resolution::Type(fqdn) => {
+                let location = match state.source(fqdn) {
+                    Some(source) => source.location.clone(),
+                    None => state.class_location(fqdn, None)?,
+                };
+                item(state, fqdn, location)
+            }
+            _ => None,
+        })
+        .collect::<Vec<TypeHierarchyItem>>();
+
+    (!items.is_empty()).then_some(items)
+}
+
+pub fn handle_type_hierarchy_supertypes(
+    state: &mut State,
+    params: TypeHierarchySupertypesParams,
+) -> Option<Vec<TypeHierarchyItem>> {
+    let fqdn = fqdn(&params.item)?;
+
+    Some(items(state, &state.supertypes(fqdn)))
+}
+
+pub fn handle_type_hierarchy_subtypes(
+    state: &mut State,
+    params: TypeHierarchySubtypesParams,
+) -> Option<Vec<TypeHierarchyItem>> {
+    let fqdn = fqdn(&params.item)?;
+
+    Some(items(state, state.subtypes(fqdn)))
+}
+
+/// Items of the types `fqdns`, types of the classpath are located at the start of their
+/// source files
+fn items<S: AsRef<str>>(state: &State, fqdns: &[S]) -> Vec<TypeHierarchyItem> {
+    let classes = fqdns
+        .iter()
+        .map(AsRef::as_ref)
+        .filter(|fqdn| state.source(fqdn).is_none())
+        .collect::<Vec<&str>>();
+    let mut class_locations = state.class_item_locations(&classes).into_iter();
+
+    fqdns
+        .iter()
+        .map(AsRef::as_ref)
+        .filter_map(|fqdn| {
+            let location = match state.source(fqdn) {
+                Some(source) => source.location.clone(),
+                None => class_locations.next()??,
+            };
+            item(state, fqdn, location)
+        })
+        .collect()
+}
+
+/// Item of a type from the sources or the classpath at `location`, it keeps the fully
+/// qualified name of the type as data for the follow-up requests
+fn item(state: &State, fqdn: &str, location: Location) -> Option<TypeHierarchyItem> {
+    let info = state.type_info(fqdn)?;
+    let fqdn = fqdn.replace('$', ".");
+    let (qualifier, name) = fqdn.rsplit_once('.').unwrap_or(("", &fqdn));
+    let kind = match info.kind {
+        TypeKind::Class => SymbolKind::CLASS,
+        TypeKind::Interface | TypeKind::Annotation => SymbolKind::INTERFACE,
+        TypeKind::Enum => SymbolKind::ENUM,
+        TypeKind::Record => SymbolKind::STRUCT,
+    };
+
+    Some(TypeHierarchyItem {
+        name: name.to_string(),
+        kind,
+        tags: info.deprecated.then_some(SymbolTag::DEPRECATED),
+        detail: Some(qualifier.to_string()).filter(|qualifier| !qualifier.is_empty()),
+        uri: location.uri,
+        range: location.range,
+        selection_range: location.range,
+        data: Some(Value::String(fqdn.clone())),
+    })
+}
+
+fn fqdn(item: &TypeHierarchyItem) -> Option<&str> {
+    match &item.data {
+        Some(Value::String(fqdn)) => Some(fqdn),
+        _ => None,
+    }
+}
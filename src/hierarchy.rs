@@ -0,0 +1,528 @@
+use std::collections::{HashMap, HashSet};
+
+use jclassfile::{class_file::ClassFlags, methods::MethodFlags};
+use lsp_types::Position;
+use tree_sitter::Node;
+
+use crate::{
+    classfile::{Classfile, Supertype},
+    imports::{Resolution, Scope},
+    quick_fix::find_type_body,
+    state::State,
+    symbols::{TypeIndex, TypeKind},
+    tree_sitter::{has_modifier, package_name, supertype_nodes},
+};
+
+/// Method of a supertype that a class can override
+pub struct InheritedMethod {
+    /// Type that declares the method
+    pub owner: String,
+    pub is_abstract: bool,
+    /// Final methods can not be overridden but still implement methods of interfaces
+    pub is_final: bool,
+    /// Expression that calls the inherited implementation like `super` or `Iterable.super`
+    pub receiver: Option<String>,
+    /// `public`, `protected` or empty for package private methods
+    pub visibility: &'static str,
+    /// Type parameters of the method with their bounds
+    pub type_parameters: Vec<String>,
+    pub return_type: String,
+    pub name: String,
+    pub parameters: Vec<String>,
+    pub parameter_names: Vec<String>,
+    pub exceptions: Vec<String>,
+    /// Classes that the types refer to
+    pub classes: Vec<String>,
+}
+
+/// Supertypes that the type `declaration` declares, resolved in the compilation unit `root`.
+/// Classes without superclass extend `Object` and enums `Enum`.
+pub fn declared_supertypes(
+    state: &State,
+    root: Node,
+    source: &str,
+    declaration: Node,
+) -> Vec<Supertype> {
+    let scope = Scope::new(root, source.as_bytes());
+    let mut supertypes = supertype_nodes(declaration)
+        .into_iter()
+        .filter_map(|supertype| declared_type(state, &scope, source, supertype))
+        .collect::<Vec<Supertype>>();
+
+    let has_superclass = declaration.child_by_field_name("superclass").is_some();
+    match declaration.kind() {
+        "class_declaration" | "record_declaration" if !has_superclass => {
+            supertypes.insert(0, object());
+        }
+        "enum_declaration" => {
+            let name = declaration
+                .child_by_field_name("name")
+                .map(|name| source[name.byte_range()].to_string())
+                .unwrap_or_default();
+            supertypes.insert(
+                0,
+                Supertype {
+                    fqdn: "java.lang.Enum".to_string(),
+                    arguments: vec![name],
+                },
+            );
+        }
+        _ => {}
+    }
+
+    supertypes
+}
+
+/// The class or interface type `node` like `Map<String, T>` with its type arguments as
+/// written, resolved in `scope`
+pub fn declared_type(state: &State, scope: &Scope, source: &str, node: Node) -> Option<Supertype> {
+    let (raw, arguments) = match node.kind() {
+        "generic_type" => {
+            let raw = node.named_child(0)?;
+            let arguments = node.named_child(1)?;
+            let mut cursor = arguments.walk();
+            let arguments = arguments
+                .named_children(&mut cursor)
+                .map(|argument| source[argument.byte_range()].to_string())
+                .collect();
+            (raw, arguments)
+        }
+        _ => (node, Vec::new()),
+    };
+
+    Some(Supertype {
+        fqdn: resolve(scope, state, &source[raw.byte_range()])?,
+        arguments,
+    })
+}
+
+/// Methods that the type `declaration` inherits from all of its supertypes and can override,
+/// the ones of superclasses come before the ones of interfaces
+pub fn overridable_methods(
+    state: &State,
+    root: Node,
+    source: &str,
+    declaration: Node,
+) -> Vec<InheritedMethod> {
+    let package = package_name(root, source.as_bytes()).unwrap_or_default();
+    let mut visited = HashSet::new();
+    let mut methods = Vec::new();
+
+    for supertype in declared_supertypes(state, root, source, declaration) {
+        inherit(state, &supertype, true, package, &mut visited, &mut methods);
+    }
+
+    methods.retain(|method| !method.is_final);
+    methods
+}
+
+/// The single abstract method of the functional interface `interface` with the type
+/// arguments of `interface` in its signature, `None` for raw types of generic interfaces
+pub fn functional_method(state: &State, interface: &Supertype) -> Option<InheritedMethod> {
+    if state.type_info(&interface.fqdn)?.kind != TypeKind::Interface {
+        return None;
+    }
+    let (parameters, _, _) = members(state, &interface.fqdn)?;
+    if parameters.len() != interface.arguments.len() {
+        return None;
+    }
+
+    let mut methods = Vec::new();
+    inherit(
+        state,
+        interface,
+        true,
+        "",
+        &mut HashSet::new(),
+        &mut methods,
+    );
+    // Interfaces can redeclare the public methods of `Object`
+    let mut abstract_methods = methods.into_iter().filter(|method| {
+        method.is_abstract
+            && !matches!(
+                (method.name.as_str(), method.parameters.len()),
+                ("equals", 1) | ("hashCode" | "toString", 0)
+            )
+    });
+    let method = abstract_methods.next()?;
+    abstract_methods.next().is_none().then_some(method)
+}
+
+/// Add the methods of `supertype` and its own supertypes that are not overridden on the way
+fn inherit(
+    state: &State,
+    supertype: &Supertype,
+    is_direct: bool,
+    package: &str,
+    visited: &mut HashSet<String>,
+    methods: &mut Vec<InheritedMethod>,
+) {
+    if !visited.insert(supertype.fqdn.clone()) {
+        return;
+    }
+    let Some((parameters, declared, supertypes)) = members(state, &supertype.fqdn) else {
+        return;
+    };
+
+    // Raw types erase the type parameters
+    let variables = parameters
+        .into_iter()
+        .enumerate()
+        .map(|(i, parameter)| {
+            let argument = supertype.arguments.get(i).map(|argument| {
+                argument
+                    .strip_prefix('?')
+                    .map(|bound| {
+                        bound
+                            .trim()
+                            .strip_prefix("extends")
+                            .map(str::trim)
+                            .unwrap_or("Object")
+                    })
+                    .unwrap_or(argument)
+                    .to_string()
+            });
+            (parameter, argument.unwrap_or_else(|| "Object".to_string()))
+        })
+        .collect::<HashMap<String, String>>();
+    let owner_package = supertype
+        .fqdn
+        .rsplit_once('.')
+        .map(|(package, _)| package)
+        .unwrap_or_default();
+    let simple_name = supertype
+        .fqdn
+        .rsplit(['.', '$'])
+        .next()
+        .unwrap_or(&supertype.fqdn);
+
+    for mut method in declared {
+        if method.visibility.is_empty() && owner_package != package {
+            continue;
+        }
+        // Type parameters of the method shadow the ones of the type
+        let mut variables = variables.clone();
+        for parameter in &method.type_parameters {
+            variables.remove(parameter.split_whitespace().next().unwrap_or(parameter));
+        }
+        method.return_type = substitute(&method.return_type, &variables);
+        for parameter in &mut method.parameters {
+            *parameter = substitute(parameter, &variables);
+        }
+        for exception in &mut method.exceptions {
+            *exception = substitute(exception, &variables);
+        }
+
+        let is_overridden = methods.iter().any(|inherited| {
+            inherited.name == method.name && inherited.parameters == method.parameters
+        });
+        if is_overridden {
+            continue;
+        }
+        // Default methods can only be called through direct superinterfaces
+        method.receiver = match method.receiver.as_deref() {
+            Some("super") => Some("super".to_string()),
+            Some(_) if is_direct => Some(format!("{simple_name}.super")),
+            _ => None,
+        };
+        methods.push(method);
+    }
+
+    for mut inherited in supertypes {
+        inherited.arguments = inherited
+            .arguments
+            .iter()
+            .map(|argument| substitute(argument, &variables))
+            .collect();
+        inherit(state, &inherited, false, package, visited, methods);
+    }
+}
+
+/// Type parameters, overridable methods and supertypes of the class or source type `fqdn`.
+/// The receiver of methods that have an implementation is `super` for classes and empty
+/// for interfaces.
+fn members(
+    state: &State,
+    fqdn: &str,
+) -> Option<(Vec<String>, Vec<InheritedMethod>, Vec<Supertype>)> {
+    if let Some(class) = state.class(fqdn) {
+        return Some((
+            class.type_parameters.clone(),
+            class_methods(class),
+            class.supertypes(),
+        ));
+    }
+
+    let uri = state.source(fqdn)?.location.uri.clone();
+    let name = fqdn.rsplit('.').next()?;
+    state.with_document(&uri, |document| {
+        document.analyze(Position::default(), |root, source, _| {
+            let declaration = find_type_body(root, source, name)?.parent()?;
+            Some((
+                source_type_parameters(declaration, source),
+                source_methods(state, root, source, declaration, fqdn),
+                declared_supertypes(state, root, source, declaration),
+            ))
+        })
+    })
+}
+
+fn class_methods(class: &Classfile) -> Vec<InheritedMethod> {
+    let is_interface = class.flags.contains(ClassFlags::ACC_INTERFACE);
+
+    class
+        .methods
+        .iter()
+        .filter(|method| {
+            !method.flags.intersects(
+                MethodFlags::ACC_STATIC
+                    | MethodFlags::ACC_PRIVATE
+                    | MethodFlags::ACC_BRIDGE
+                    | MethodFlags::ACC_SYNTHETIC,
+            ) && !method.name.starts_with('<')
+        })
+        .filter_map(|method| {
+            let signature = method.source_signature()?;
+            let is_abstract = method.flags.contains(MethodFlags::ACC_ABSTRACT);
+            let visibility = if method.flags.contains(MethodFlags::ACC_PUBLIC) {
+                "public"
+            } else if method.flags.contains(MethodFlags::ACC_PROTECTED) {
+                "protected"
+            } else {
+                ""
+            };
+
+            Some(InheritedMethod {
+                owner: class.fqdn.clone(),
+                is_abstract,
+                is_final: method.flags.contains(MethodFlags::ACC_FINAL),
+                receiver: receiver(is_abstract, is_interface),
+                visibility,
+                type_parameters: signature.type_parameters,
+                return_type: signature.return_type,
+                name: method.name.clone(),
+                parameter_names: (0..signature.parameters.len())
+                    .map(|i| format!("arg{i}"))
+                    .collect(),
+                parameters: signature.parameters,
+                exceptions: signature.exceptions,
+                classes: signature.classes,
+            })
+        })
+        .collect()
+}
+
+fn source_methods(
+    state: &State,
+    root: Node,
+    source: &str,
+    declaration: Node,
+    fqdn: &str,
+) -> Vec<InheritedMethod> {
+    let scope = Scope::new(root, source.as_bytes());
+    let is_interface = declaration.kind() == "interface_declaration";
+    let Some(body) = declaration.child_by_field_name("body") else {
+        return Vec::new();
+    };
+    let text = |node: Node| source[node.byte_range()].to_string();
+    let mut cursor = body.walk();
+    let members = body.named_children(&mut cursor).collect::<Vec<Node>>();
+
+    members
+        .into_iter()
+        .filter(|member| {
+            member.kind() == "method_declaration"
+                && !has_modifier(*member, "static")
+                && !has_modifier(*member, "private")
+        })
+        .filter_map(|method| {
+            let is_abstract = if is_interface {
+                method.child_by_field_name("body").is_none()
+            } else {
+                has_modifier(method, "abstract")
+            };
+            let visibility = if is_interface || has_modifier(method, "public") {
+                "public"
+            } else if has_modifier(method, "protected") {
+                "protected"
+            } else {
+                ""
+            };
+
+            let mut parameters = Vec::new();
+            let mut parameter_names = Vec::new();
+            let list = method.child_by_field_name("parameters")?;
+            let mut cursor = list.walk();
+            for parameter in list.named_children(&mut cursor) {
+                match parameter.kind() {
+                    "formal_parameter" => {
+                        let dimensions = parameter
+                            .child_by_field_name("dimensions")
+                            .map(text)
+                            .unwrap_or_default();
+                        parameters.push(text(parameter.child_by_field_name("type")?) + &dimensions);
+                        parameter_names.push(text(parameter.child_by_field_name("name")?));
+                    }
+                    "spread_parameter" => {
+                        let mut cursor = parameter.walk();
+                        let children = parameter.named_children(&mut cursor).collect::<Vec<Node>>();
+                        let declared = children.iter().find(|child| {
+                            !matches!(
+                                child.kind(),
+                                "modifiers"
+                                    | "annotation"
+                                    | "marker_annotation"
+                                    | "variable_declarator"
+                            )
+                        })?;
+                        let declarator = children
+                            .iter()
+                            .find(|child| child.kind() == "variable_declarator")?;
+                        parameters.push(format!("{}...", text(*declared)));
+                        parameter_names.push(text(declarator.child_by_field_name("name")?));
+                    }
+                    _ => {}
+                }
+            }
+
+            let mut cursor = method.walk();
+            let exceptions = method
+                .children(&mut cursor)
+                .find(|child| child.kind() == "throws")
+                .map(|throws| {
+                    let mut cursor = throws.walk();
+                    throws.named_children(&mut cursor).map(text).collect()
+                })
+                .unwrap_or_default();
+            let type_parameters = method
+                .child_by_field_name("type_parameters")
+                .map(|parameters| {
+                    let mut cursor = parameters.walk();
+                    parameters.named_children(&mut cursor).map(text).collect()
+                })
+                .unwrap_or_default();
+            let dimensions = method
+                .child_by_field_name("dimensions")
+                .map(text)
+                .unwrap_or_default();
+            let mut classes = Vec::new();
+            referenced_classes(method, source, &scope, state, &mut classes);
+
+            Some(InheritedMethod {
+                owner: fqdn.to_string(),
+                is_abstract,
+                is_final: has_modifier(method, "final"),
+                receiver: receiver(is_abstract, is_interface),
+                visibility,
+                type_parameters,
+                return_type: text(method.child_by_field_name("type")?) + &dimensions,
+                name: text(method.child_by_field_name("name")?),
+                parameters,
+                parameter_names,
+                exceptions,
+                classes,
+            })
+        })
+        .collect()
+}
+
+/// Types that the signature of `method` refers to, its body and annotations are skipped
+fn referenced_classes(
+    node: Node,
+    source: &str,
+    scope: &Scope,
+    state: &State,
+    classes: &mut Vec<String>,
+) {
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        match child.kind() {
+            "block" | "modifiers" | "identifier" => {}
+            "type_identifier" => {
+                if let Some(Resolution::Type(fqdn)) =
+                    scope.resolve_type(&source[child.byte_range()], state)
+                    && !classes.contains(&fqdn)
+                {
+                    classes.push(fqdn);
+                }
+            }
+            // Qualified types need no import
+            "scoped_type_identifier" => {}
+            _ => referenced_classes(child, source, scope, state, classes),
+        }
+    }
+}
+
+/// Names of the type parameters of a type declaration
+fn source_type_parameters(declaration: Node, source: &str) -> Vec<String> {
+    let Some(parameters) = declaration.child_by_field_name("type_parameters") else {
+        return Vec::new();
+    };
+    let mut cursor = parameters.walk();
+
+    parameters
+        .named_children(&mut cursor)
+        .filter_map(|parameter| {
+            let mut cursor = parameter.walk();
+            parameter
+                .named_children(&mut cursor)
+                .find(|child| child.kind() == "type_identifier")
+                .map(|name| source[name.byte_range()].to_string())
+        })
+        .collect()
+}
+
+/// Receiver before the owner of an interface is known
+fn receiver(is_abstract: bool, is_interface: bool) -> Option<String> {
+    match (is_abstract, is_interface) {
+        (true, _) => None,
+        (false, true) => Some(String::new()),
+        (false, false) => Some("super".to_string()),
+    }
+}
+
+/// Fully qualified name of the type `name` that can be qualified like `Map.Entry`
+fn resolve(scope: &Scope, state: &State, name: &str) -> Option<String> {
+    let (first, rest) = match name.split_once('.') {
+        Some((first, rest)) => (first, Some(rest)),
+        None => (name, None),
+    };
+
+    match (scope.resolve_type(first, state), rest) {
+        (Some(Resolution::Type(fqdn)), Some(rest)) => Some(format!("{fqdn}.{rest}")),
+        (Some(Resolution::Type(fqdn)), None) => Some(fqdn),
+        _ => Some(name.to_string()).filter(|name| state.has_type(name)),
+    }
+}
+
+fn object() -> Supertype {
+    Supertype {
+        fqdn: "java.lang.Object".to_string(),
+        arguments: Vec::new(),
+    }
+}
+
+/// Replace the type variables in the type `text` by their values
+fn substitute(text: &str, variables: &HashMap<String, String>) -> String {
+    let mut result = String::new();
+    let mut identifier = String::new();
+    let mut after_dot = false;
+
+    for c in text.chars().chain(std::iter::once(' ')) {
+        if c.is_alphanumeric() || c == '_' || c == '$' {
+            identifier.push(c);
+            continue;
+        }
+        // Names of nested types are no type variables
+        match variables.get(&identifier) {
+            Some(value) if !after_dot => result.push_str(value),
+            _ => result.push_str(&identifier),
+        }
+        identifier.clear();
+        after_dot = c == '.';
+        result.push(c);
+    }
+    result.pop();
+
+    result
+}
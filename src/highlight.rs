@@ -0,0 +1,198 @@
+use std::ops::Range;
+
+use lsp_types::DocumentHighlightKind;
+use tree_sitter::Node;
+
+use crate::{
+    calls::{self, METHODS, Method},
+    locals::{self, locals},
+    resolver::Resolver,
+    source_edit::ancestor,
+    state::State,
+};
+
+/// Variable or method whose occurrences are highlighted
+enum Symbol<'tree> {
+    /// Local variable or parameter of a member by its index in the locals of the member
+    Local(Node<'tree>, usize),
+    /// Field or enum constant of a type
+    Field {
+        owner: String,
+        name: String,
+    },
+    Method(Method),
+}
+
+/// Byte ranges of the occurrences in the compilation unit `root` of the variable or method
+/// whose name is at `offset`. Declarations and assignments are writes, other uses of
+/// variables are reads and the occurrences of methods are neither.
+pub fn highlights(
+    state: &State,
+    root: Node,
+    source: &str,
+    offset: usize,
+) -> Option<Vec<(Range<usize>, DocumentHighlightKind)>> {
+    let node = root
+        .descendant_for_byte_range(offset, offset)
+        .filter(|node| node.kind() == "identifier")?;
+
+    let mut highlights = Vec::new();
+    match symbol(state, root, source, node)? {
+        // Only the references within the scope of the variable, shadowing fields and
+        // variables of other members are left out
+        Symbol::Local(member, index) => {
+            let locals = locals(member, source);
+            locals::visit_references(
+                member,
+                source,
+                &locals,
+                &mut |node, local, is_assignment| {
+                    if local == index {
+                        let kind = if is_assignment || locals[local].node == node {
+                            DocumentHighlightKind::WRITE
+                        } else {
+                            DocumentHighlightKind::READ
+                        };
+                        highlights.push((node.byte_range(), kind));
+                    }
+                },
+            );
+        }
+        Symbol::Field { owner, name } => visit(root, &mut |node| {
+            if node.kind() == "identifier"
+                && source[node.byte_range()] == name
+                && Resolver::new(state, root, source, node)
+                    .and_then(|resolver| resolver.field_owner(node))
+                    .is_some_and(|declaring| declaring == owner)
+            {
+                let kind = if is_write(node) {
+                    DocumentHighlightKind::WRITE
+                } else {
+                    DocumentHighlightKind::READ
+                };
+                highlights.push((node.byte_range(), kind));
+            }
+        }),
+        Symbol::Method(method) => {
+            highlights.extend(
+                calls::methods(root, source)
+                    .into_iter()
+                    .filter(|(declared, _)| *declared == method)
+                    .filter_map(|(_, declaration)| declaration.child_by_field_name("name"))
+                    .map(|name| (name.byte_range(), DocumentHighlightKind::TEXT)),
+            );
+            visit(root, &mut |node| {
+                if node.kind() == "method_invocation"
+                    && let Some(name) = node.child_by_field_name("name")
+                    && source[name.byte_range()] == method.name
+                    && invoked(state, root, source, node).is_some_and(|(owner, arguments)| {
+                        owner == method.owner && method.accepts(arguments)
+                    })
+                {
+                    highlights.push((name.byte_range(), DocumentHighlightKind::TEXT));
+                }
+            });
+        }
+    }
+
+    highlights.sort_by_key(|(range, _)| range.start);
+    Some(highlights)
+}
+
+/// Symbol that the identifier `node` declares or refers to
+fn symbol<'tree>(
+    state: &State,
+    root: Node<'tree>,
+    source: &str,
+    node: Node<'tree>,
+) -> Option<Symbol<'tree>> {
+    let parent = node.parent()?;
+    if parent.child_by_field_name("name") == Some(node) {
+        match parent.kind() {
+            "method_declaration" => {
+                return calls::methods(root, source)
+                    .into_iter()
+                    .find(|(_, declaration)| *declaration == parent)
+                    .map(|(method, _)| Symbol::Method(method));
+            }
+            "method_invocation" => {
+                let (owner, arguments) = invoked(state, root, source, parent)?;
+                let name = &source[node.byte_range()];
+                // Methods of other compilation units are told apart by their arguments
+                let method = calls::methods(root, source)
+                    .into_iter()
+                    .map(|(method, _)| method)
+                    .find(|method| {
+                        method.owner == owner && method.name == name && method.accepts(arguments)
+                    })
+                    .unwrap_or(Method {
+                        owner,
+                        name: name.to_string(),
+                        parameters: arguments,
+                        is_varargs: false,
+                    });
+                return Some(Symbol::Method(method));
+            }
+            _ => {}
+        }
+    }
+
+    let member = ancestor(node, &METHODS)
+        .or_else(|| ancestor(node, &["field_declaration", "static_initializer"]));
+    if let Some(member) = member
+        && let Some(index) = locals::declaration(&locals(member, source), node, source)
+    {
+        return Some(Symbol::Local(member, index));
+    }
+
+    let owner = Resolver::new(state, root, source, node)?.field_owner(node)?;
+    Some(Symbol::Field {
+        owner,
+        name: source[node.byte_range()].to_string(),
+    })
+}
+
+/// Type that declares the method that `invocation` invokes and the number of its arguments
+fn invoked(state: &State, root: Node, source: &str, invocation: Node) -> Option<(String, usize)> {
+    Some((
+        Resolver::new(state, root, source, invocation)?.invoked_type(invocation)?,
+        invocation
+            .child_by_field_name("arguments")?
+            .named_child_count(),
+    ))
+}
+
+/// Whether the name of a field is assigned, either directly or through a field access
+fn is_write(node: Node) -> bool {
+    let Some(mut parent) = node.parent() else {
+        return false;
+    };
+    let mut target = node;
+    if parent.kind() == "field_access" && parent.child_by_field_name("field") == Some(node) {
+        target = parent;
+        let Some(grandparent) = parent.parent() else {
+            return false;
+        };
+        parent = grandparent;
+    }
+
+    match parent.kind() {
+        "assignment_expression" => parent.child_by_field_name("left") == Some(target),
+        "update_expression" => true,
+        // Declarations of fields and enum constants
+        "variable_declarator" | "enum_constant" => {
+            parent.child_by_field_name("name") == Some(target)
+        }
+        _ => false,
+    }
+}
+
+fn visit<'tree>(node: Node<'tree>, action: &mut impl FnMut(Node<'tree>)) {
+    action(node);
+
+    let mut cursor = node.walk();
+    let children = node.named_children(&mut cursor).collect::<Vec<Node>>();
+    for child in children {
+        visit(child, action);
+    }
+}
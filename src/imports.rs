@@ -0,0 +1,414 @@
+use tree_sitter::Node;
+
+use crate::{
+    symbols::TypeIndex,
+    tree_sitter::{Import, collect_imports, collect_type_declarations, package_name},
+};
+
+/// Packages whose types are visible in every compilation unit
+const IMPLICIT_IMPORTS: [&str; 1] = ["java.lang"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resolution {
+    Type(String),
+    /// Field, method or enum constant of a type
+    Member(String, String),
+    /// The name is imported on demand from more than one package or type
+    Ambiguous(Vec<Resolution>),
+}
+
+/// Names that are visible in a compilation unit through its package, its declarations and
+/// its imports
+pub struct Scope {
+    package: String,
+    imports: Vec<Import>,
+    /// Types that are declared in the compilation unit, including nested ones
+    declared: Vec<String>,
+}
+
+impl Scope {
+    pub fn new(root: Node, source: &[u8]) -> Self {
+        let package = package_name(root, source).unwrap_or_default().to_string();
+        let declared = collect_type_declarations(root, source, &package)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+
+        Scope {
+            imports: collect_imports(root, source),
+            package,
+            declared,
+        }
+    }
+
+    /// Resolve the simple name of a type in the order of the JLS: declarations of the
+    /// compilation unit, single type imports, the own package and at last imports on demand
+    pub fn resolve_type(&self, name: &str, index: &impl TypeIndex) -> Option<Resolution> {
+        if let Some(declared) = self
+            .declared
+            .iter()
+            .find(|declared| simple_name(declared) == name)
+        {
+            return Some(Resolution::Type(declared.clone()));
+        }
+
+        // A single static import can import a nested type as well
+        if let Some(import) = self.imports.iter().find(|import| {
+            !import.on_demand
+                && simple_name(&import.path) == name
+                && (!import.is_static || index.has_type(&import.path))
+        }) {
+            return Some(Resolution::Type(import.path.clone()));
+        }
+
+        let same_package = qualify(&self.package, name);
+        if index.has_type(&same_package) {
+            return Some(Resolution::Type(same_package));
+        }
+
+        // Packages (`a.b.*`) as well as types (`a.B.*` or `static a.B.*`) import nested types
+        let mut candidates = Vec::new();
+        for path in self
+            .imports
+            .iter()
+            .filter(|import| import.on_demand)
+            .map(|import| import.path.as_str())
+            .chain(IMPLICIT_IMPORTS)
+        {
+            let candidate = qualify(path, name);
+            if index.has_type(&candidate) && !candidates.contains(&candidate) {
+                candidates.push(candidate);
+            }
+        }
+
+        unique(candidates.into_iter().map(Resolution::Type).collect())
+    }
+
+    /// Fully qualified names that the type `name` can refer to in the order in which they
+    /// shadow each other, for resolving names before the types are indexed. Qualified names
+    /// like `Map.Entry` are resolved through their first part.
+    pub fn candidates(&self, name: &str) -> Vec<String> {
+        if let Some((first, rest)) = name.split_once('.') {
+            let mut candidates = vec![name.to_string()];
+            candidates.extend(
+                self.candidates(first)
+                    .into_iter()
+                    .map(|outer| qualify(&outer, rest)),
+            );
+            return candidates;
+        }
+
+        if let Some(declared) = self
+            .declared
+            .iter()
+            .find(|declared| simple_name(declared) == name)
+        {
+            return vec![declared.clone()];
+        }
+        if let Some(import) = self
+            .imports
+            .iter()
+            .find(|import| !import.on_demand && simple_name(&import.path) == name)
+        {
+            return vec![import.path.clone()];
+        }
+
+        let mut candidates = vec![qualify(&self.package, name)];
+        candidates.extend(
+            self.imports
+                .iter()
+                .filter(|import| import.on_demand)
+                .map(|import| import.path.as_str())
+                .chain(IMPLICIT_IMPORTS)
+                .map(|path| qualify(path, name)),
+        );
+        candidates
+    }
+
+    /// Resolve the simple name of a static field or method through static imports
+    pub fn resolve_member(&self, name: &str, index: &impl TypeIndex) -> Option<Resolution> {
+        if let Some(import) = self.imports.iter().find(|import| {
+            import.is_static && !import.on_demand && simple_name(&import.path) == name
+        }) {
+            let (owner, _) = import.path.rsplit_once('.')?;
+            return Some(Resolution::Member(owner.to_string(), name.to_string()));
+        }
+
+        let mut candidates = Vec::new();
+        for import in self
+            .imports
+            .iter()
+            .filter(|import| import.is_static && import.on_demand)
+        {
+            let candidate = Resolution::Member(import.path.clone(), name.to_string());
+            if index.has_member(&import.path, name) && !candidates.contains(&candidate) {
+                candidates.push(candidate);
+            }
+        }
+
+        unique(candidates)
+    }
+}
+
+/// Resolve the name at `node` to the type or member it refers to
+pub fn resolve(
+    node: Node,
+    source: &[u8],
+    scope: &Scope,
+    index: &impl TypeIndex,
+) -> Option<Resolution> {
+    if let Some(import) = ancestor(node, "import_declaration") {
+        return resolve_import(node, import, source, index);
+    }
+
+    match node.kind() {
+        "type_identifier" => resolve_type(node, source, scope, index),
+        "identifier" => {
+            let name = node.utf8_text(source).ok()?;
+            let parent = node.parent()?;
+            let is_selected = match parent.kind() {
+                "method_invocation" => parent.child_by_field_name("name") == Some(node),
+                "field_access" => parent.child_by_field_name("field") == Some(node),
+                _ => false,
+            };
+
+            if !is_selected {
+                // Variables in scope shadow static imports, names that are not variables
+                // can still be types
+                if is_declared(node, name, source, false) {
+                    return None;
+                }
+                return scope
+                    .resolve_member(name, index)
+                    .or_else(|| scope.resolve_type(name, index));
+            }
+
+            match parent.child_by_field_name("object") {
+                Some(object) => match resolve_expression(object, source, scope, index)? {
+                    // Nested types are selected like fields, e.g. `Outer.Inner.method()`
+                    Resolution::Type(fqdn) if index.has_type(&qualify(&fqdn, name)) => {
+                        Some(Resolution::Type(qualify(&fqdn, name)))
+                    }
+                    Resolution::Type(fqdn) => Some(Resolution::Member(fqdn, name.to_string())),
+                    _ => None,
+                },
+                None if is_declared(node, name, source, true) => None,
+                None => scope.resolve_member(name, index),
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Resolve a `type_identifier`, which might be qualified by an outer type or a package
+fn resolve_type(
+    node: Node,
+    source: &[u8],
+    scope: &Scope,
+    index: &impl TypeIndex,
+) -> Option<Resolution> {
+    let name = node.utf8_text(source).ok()?;
+    let Some(parent) = node
+        .parent()
+        .filter(|parent| parent.kind() == "scoped_type_identifier")
+        .filter(|parent| parent.named_child(0) != Some(node))
+    else {
+        return scope.resolve_type(name, index);
+    };
+
+    let qualifier = parent.named_child(0)?;
+    if let Some(Resolution::Type(outer)) = resolve_qualifier(qualifier, source, scope, index) {
+        let nested = qualify(&outer, name);
+        if index.has_type(&nested) {
+            return Some(Resolution::Type(nested));
+        }
+    }
+
+    // Fully qualified name like `java.util.List`
+    let qualified = qualify(qualifier.utf8_text(source).ok()?, name);
+    index
+        .has_type(&qualified)
+        .then_some(Resolution::Type(qualified))
+}
+
+fn resolve_qualifier(
+    node: Node,
+    source: &[u8],
+    scope: &Scope,
+    index: &impl TypeIndex,
+) -> Option<Resolution> {
+    match node.kind() {
+        "type_identifier" => resolve_type(node, source, scope, index),
+        "scoped_type_identifier" => {
+            let last = node.named_child(node.named_child_count().checked_sub(1)?)?;
+            resolve_type(last, source, scope, index)
+        }
+        "generic_type" => resolve_qualifier(node.named_child(0)?, source, scope, index),
+        _ => None,
+    }
+}
+
+/// Resolve an expression that might name a type, e.g. the object of `Outer.Inner.method()`
+fn resolve_expression(
+    node: Node,
+    source: &[u8],
+    scope: &Scope,
+    index: &impl TypeIndex,
+) -> Option<Resolution> {
+    match node.kind() {
+        "identifier" => scope.resolve_type(node.utf8_text(source).ok()?, index),
+        "field_access" => {
+            let object = node.child_by_field_name("object")?;
+            let field = node.child_by_field_name("field")?.utf8_text(source).ok()?;
+
+            if let Some(Resolution::Type(outer)) = resolve_expression(object, source, scope, index)
+            {
+                let nested = qualify(&outer, field);
+                if index.has_type(&nested) {
+                    return Some(Resolution::Type(nested));
+                }
+            }
+
+            let qualified = qualify(object.utf8_text(source).ok()?, field);
+            index
+                .has_type(&qualified)
+                .then_some(Resolution::Type(qualified))
+        }
+        _ => None,
+    }
+}
+
+/// Resolve a segment of the name of an import declaration
+fn resolve_import(
+    node: Node,
+    import: Node,
+    source: &[u8],
+    index: &impl TypeIndex,
+) -> Option<Resolution> {
+    let mut cursor = import.walk();
+    let path = import
+        .named_children(&mut cursor)
+        .find(|child| matches!(child.kind(), "identifier" | "scoped_identifier"))?;
+    let prefix = str::from_utf8(source.get(path.start_byte()..node.end_byte())?).ok()?;
+
+    if index.has_type(prefix) {
+        return Some(Resolution::Type(prefix.to_string()));
+    }
+
+    let (owner, member) = prefix.rsplit_once('.')?;
+    index
+        .has_member(owner, member)
+        .then(|| Resolution::Member(owner.to_string(), member.to_string()))
+}
+
+/// Whether a local variable, a parameter or a field (or a method if `is_method`) named
+/// `name` is in scope at `node`, they shadow static imports (JLS §6.4.1). Inherited members
+/// are not known.
+fn is_declared(node: Node, name: &str, source: &[u8], is_method: bool) -> bool {
+    let is_name = |node: Option<Node>| {
+        node.is_some_and(|node| node.utf8_text(source).is_ok_and(|text| text == name))
+    };
+    let declares_variable = |declaration: Node| {
+        let mut cursor = declaration.walk();
+        declaration
+            .children_by_field_name("declarator", &mut cursor)
+            .any(|declarator| is_name(declarator.child_by_field_name("name")))
+    };
+    let declares = |declaration: Node| match declaration.kind() {
+        "method_declaration" => is_method && is_name(declaration.child_by_field_name("name")),
+        _ if is_method => false,
+        "local_variable_declaration" | "field_declaration" | "constant_declaration" => {
+            declares_variable(declaration)
+        }
+        "formal_parameters" | "inferred_parameters" | "resource_specification" => {
+            let mut cursor = declaration.walk();
+            declaration
+                .named_children(&mut cursor)
+                .any(|parameter| match parameter.kind() {
+                    "identifier" => is_name(Some(parameter)),
+                    "spread_parameter" => {
+                        let mut cursor = parameter.walk();
+                        parameter
+                            .named_children(&mut cursor)
+                            .filter(|child| child.kind() == "variable_declarator")
+                            .any(|declarator| is_name(declarator.child_by_field_name("name")))
+                    }
+                    _ => is_name(parameter.child_by_field_name("name")),
+                })
+        }
+        "catch_formal_parameter" | "resource" | "enum_constant" => {
+            is_name(declaration.child_by_field_name("name"))
+        }
+        _ => false,
+    };
+
+    let mut current = node;
+    while let Some(parent) = current.parent() {
+        // Members are visible in the whole body, local variables only after their declaration
+        let is_body = matches!(
+            parent.kind(),
+            "class_body"
+                | "interface_body"
+                | "enum_body"
+                | "enum_body_declarations"
+                | "annotation_type_body"
+        );
+        let mut cursor = parent.walk();
+        let is_declared = parent
+            .named_children(&mut cursor)
+            .take_while(|child| is_body || child.start_byte() < node.start_byte())
+            .any(|child| {
+                declares(child)
+                    || child.kind() == "enum_body_declarations"
+                        && child.named_children(&mut child.walk()).any(&declares)
+            });
+        if is_declared {
+            return true;
+        }
+
+        // Single parameters of lambdas and variables of enhanced for loops are fields
+        let variable = match parent.kind() {
+            "lambda_expression" => parent.child_by_field_name("parameters"),
+            "enhanced_for_statement" => parent.child_by_field_name("name"),
+            _ => None,
+        };
+        if !is_method && is_name(variable.filter(|variable| variable.kind() == "identifier")) {
+            return true;
+        }
+        current = parent;
+    }
+
+    false
+}
+
+fn ancestor<'tree>(node: Node<'tree>, kind: &str) -> Option<Node<'tree>> {
+    let mut current = node.parent();
+    while let Some(parent) = current {
+        if parent.kind() == kind {
+            return Some(parent);
+        }
+        current = parent.parent();
+    }
+
+    None
+}
+
+fn unique(mut candidates: Vec<Resolution>) -> Option<Resolution> {
+    match candidates.len() {
+        0 => None,
+        1 => candidates.pop(),
+        _ => Some(Resolution::Ambiguous(candidates)),
+    }
+}
+
+fn simple_name(name: &str) -> &str {
+    name.rsplit('.').next().unwrap_or(name)
+}
+
+fn qualify(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{prefix}.{name}")
+    }
+}
@@ -0,0 +1,475 @@
+use jclassfile::methods::MethodFlags;
+use lsp_types::{InlayHintKind, Position};
+use tree_sitter::Node;
+
+use crate::{
+    calls,
+    classfile::{JavaType, Supertype},
+    configuration::InlayHintsConfiguration,
+    hierarchy::{declared_type, functional_method},
+    imports::Scope,
+    resolver::Resolver,
+    state::State,
+    tree_sitter::{collect_type_declarations, package_name},
+};
+
+/// Arguments whose meaning is not obvious without the name of their parameter
+const LITERALS: [&str; 11] = [
+    "binary_integer_literal",
+    "character_literal",
+    "decimal_floating_point_literal",
+    "decimal_integer_literal",
+    "false",
+    "hex_floating_point_literal",
+    "hex_integer_literal",
+    "null_literal",
+    "octal_integer_literal",
+    "string_literal",
+    "true",
+];
+
+/// Initializers that already name the type of a `var` declaration, like `new ArrayList<>()`,
+/// `new int[3]`, `(List<String>) value` or `"text"`. Their variables get no hint as it would
+/// only repeat the type, others like method calls get one with the type they resolve to.
+const TYPED_INITIALIZERS: [&str; 4] = [
+    "object_creation_expression",
+    "array_creation_expression",
+    "cast_expression",
+    "string_literal",
+];
+
+/// Label that is shown at a byte offset of the source
+pub struct Hint {
+    pub offset: usize,
+    pub label: String,
+    pub kind: InlayHintKind,
+    /// Parameter names and types of lambda parameters precede the text that they describe
+    pub is_prefix: bool,
+}
+
+/// Parameters of a method or constructor that a call can invoke
+struct Overload {
+    /// Empty if the class was compiled without the names
+    names: Vec<String>,
+    /// Declared class and interface types with their type arguments
+    types: Vec<Option<Supertype>>,
+}
+
+/// Hints between the byte offsets `start` and `end` of the compilation unit `root`, the
+/// kinds are enabled by `configuration`
+pub fn hints(
+    state: &State,
+    root: Node,
+    source: &str,
+    start: usize,
+    end: usize,
+    configuration: &InlayHintsConfiguration,
+) -> Vec<Hint> {
+    fn visit(node: Node, start: usize, end: usize, hint: &mut impl FnMut(Node)) {
+        if node.end_byte() < start || node.start_byte() > end {
+            return;
+        }
+        hint(node);
+
+        let mut cursor = node.walk();
+        let children = node.named_children(&mut cursor).collect::<Vec<Node>>();
+        for child in children {
+            visit(child, start, end, hint);
+        }
+    }
+
+    let mut hints = Vec::new();
+    visit(root, start, end, &mut |node| match node.kind() {
+        "method_invocation" | "object_creation_expression" if configuration.parameter_names => {
+            hints.extend(parameter_names(state, root, source, node));
+        }
+        "local_variable_declaration" if configuration.variable_types => {
+            hints.extend(variable_types(state, root, source, node));
+        }
+        "lambda_expression" if configuration.lambda_parameter_types => {
+            hints.extend(lambda_parameter_types(state, root, source, node));
+        }
+        _ => {}
+    });
+
+    hints.retain(|hint| start <= hint.offset && hint.offset <= end);
+    hints
+}
+
+/// Names of the parameters before the literal arguments of `call`, as long as all overloads
+/// that can be invoked agree on them
+fn parameter_names(state: &State, root: Node, source: &str, call: Node) -> Vec<Hint> {
+    let Some(arguments) = call.child_by_field_name("arguments") else {
+        return Vec::new();
+    };
+    let mut cursor = arguments.walk();
+    let arguments = arguments.named_children(&mut cursor).collect::<Vec<Node>>();
+    let is_literal = |argument: &Node| {
+        LITERALS.contains(&argument.kind())
+            || (argument.kind() == "unary_expression"
+                && argument
+                    .child_by_field_name("operand")
+                    .is_some_and(|operand| LITERALS.contains(&operand.kind())))
+    };
+    if !arguments.iter().any(is_literal) {
+        return Vec::new();
+    }
+
+    let overloads = overloads(state, root, source, call, arguments.len());
+    arguments
+        .iter()
+        .enumerate()
+        .filter(|(_, argument)| is_literal(argument))
+        .filter_map(|(i, argument)| {
+            let name = agreed(&overloads, |overload| overload.names.get(i))?;
+            Some(Hint {
+                offset: argument.start_byte(),
+                label: format!("{name}:"),
+                kind: InlayHintKind::PARAMETER,
+                is_prefix: true,
+            })
+        })
+        .collect()
+}
+
+/// Types of the variables of a `var` declaration whose initializer does not name the type
+fn variable_types(state: &State, root: Node, source: &str, declaration: Node) -> Vec<Hint> {
+    if declaration
+        .child_by_field_name("type")
+        .is_none_or(|declared| &source[declared.byte_range()] != "var")
+    {
+        return Vec::new();
+    }
+    let Some(resolver) = Resolver::new(state, root, source, declaration) else {
+        return Vec::new();
+    };
+
+    declarator_types(declaration, |value| resolver.expression_type(value))
+}
+
+/// Hints with the types of the declarators of `declaration` whose initializer does not name
+/// the type, `expression_type` gives the fully qualified type of an initializer
+fn declarator_types(
+    declaration: Node,
+    expression_type: impl Fn(Node) -> Option<String>,
+) -> Vec<Hint> {
+    let mut cursor = declaration.walk();
+    let declarators = declaration
+        .children_by_field_name("declarator", &mut cursor)
+        .collect::<Vec<Node>>();
+    declarators
+        .into_iter()
+        .filter_map(|declarator| {
+            let name = declarator.child_by_field_name("name")?;
+            let value = declarator
+                .child_by_field_name("value")
+                .filter(|value| !TYPED_INITIALIZERS.contains(&value.kind()))?;
+            let fqdn = expression_type(value)?;
+            Some(Hint {
+                offset: name.end_byte(),
+                label: format!(": {}", fqdn.rsplit('.').next().unwrap_or(&fqdn)),
+                kind: InlayHintKind::TYPE,
+                is_prefix: false,
+            })
+        })
+        .collect()
+}
+
+/// Types of the parameters of `lambda` without declared types, from the functional
+/// interface of the variable or the parameter that the lambda is assigned to
+fn lambda_parameter_types(state: &State, root: Node, source: &str, lambda: Node) -> Vec<Hint> {
+    let Some(parameters) = lambda.child_by_field_name("parameters") else {
+        return Vec::new();
+    };
+    let names = match parameters.kind() {
+        "identifier" => vec![parameters],
+        "inferred_parameters" => {
+            let mut cursor = parameters.walk();
+            parameters.named_children(&mut cursor).collect()
+        }
+        _ => return Vec::new(),
+    };
+    let Some(method) = target_type(state, root, source, lambda)
+        .and_then(wildcards_captured)
+        .and_then(|interface| functional_method(state, &interface))
+        .filter(|method| method.parameters.len() == names.len())
+    else {
+        return Vec::new();
+    };
+
+    names
+        .into_iter()
+        .zip(method.parameters)
+        .map(|(name, parameter_type)| Hint {
+            offset: name.start_byte(),
+            label: parameter_type,
+            kind: InlayHintKind::TYPE,
+            is_prefix: true,
+        })
+        .collect()
+}
+
+/// Functional interface that `lambda` implements, the declared type of the variable that it
+/// initializes or of the parameter that it is passed to
+fn target_type(state: &State, root: Node, source: &str, lambda: Node) -> Option<Supertype> {
+    let parent = lambda.parent()?;
+    match parent.kind() {
+        "variable_declarator" => {
+            let declared = parent.parent()?.child_by_field_name("type")?;
+            declared_type(
+                state,
+                &Scope::new(root, source.as_bytes()),
+                source,
+                declared,
+            )
+        }
+        "argument_list" => {
+            let call = parent.parent()?;
+            let mut cursor = parent.walk();
+            let arguments = parent.named_children(&mut cursor).collect::<Vec<Node>>();
+            let i = arguments.iter().position(|argument| *argument == lambda)?;
+
+            let overloads = overloads(state, root, source, call, arguments.len());
+            agreed(&overloads, |overload| overload.types.get(i)?.as_ref()).cloned()
+        }
+        _ => None,
+    }
+}
+
+/// `interface` with the bounds of its wildcards as type arguments, `None` for unbounded
+/// wildcards
+fn wildcards_captured(interface: Supertype) -> Option<Supertype> {
+    let arguments = interface
+        .arguments
+        .iter()
+        .map(|argument| match argument.strip_prefix('?') {
+            Some(bound) => {
+                let bound = bound.trim();
+                bound
+                    .strip_prefix("extends")
+                    .or_else(|| bound.strip_prefix("super"))
+                    .map(|bound| bound.trim().to_string())
+            }
+            None => Some(argument.clone()),
+        })
+        .collect::<Option<Vec<String>>>()?;
+
+    Some(Supertype {
+        fqdn: interface.fqdn,
+        arguments,
+    })
+}
+
+/// The value that all overloads have, `None` if there are none or they differ
+fn agreed<'a, T: PartialEq>(
+    overloads: &'a [Overload],
+    value: impl Fn(&'a Overload) -> Option<T>,
+) -> Option<T> {
+    let mut values = overloads.iter().map(value);
+    let first = values.next()??;
+    values
+        .all(|other| other.as_ref() == Some(&first))
+        .then_some(first)
+}
+
+/// Methods or constructors of the sources or the classpath that `call` with `arguments`
+/// arguments can invoke
+fn overloads(
+    state: &State,
+    root: Node,
+    source: &str,
+    call: Node,
+    arguments: usize,
+) -> Vec<Overload> {
+    let Some(resolver) = Resolver::new(state, root, source, call) else {
+        return Vec::new();
+    };
+    // Constructors are named like their type in sources and `<init>` in class files
+    let (owner, name) = match call.kind() {
+        "method_invocation" => (
+            resolver.invoked_type(call),
+            call.child_by_field_name("name")
+                .map(|name| source[name.byte_range()].to_string()),
+        ),
+        _ => (resolver.expression_type(call), None),
+    };
+    let Some(owner) = owner else {
+        return Vec::new();
+    };
+
+    let package = package_name(root, source.as_bytes()).unwrap_or_default();
+    if collect_type_declarations(root, source.as_bytes(), package)
+        .iter()
+        .any(|(fqdn, _)| *fqdn == owner)
+    {
+        return source_overloads(state, root, source, &owner, name.as_deref(), arguments);
+    }
+    if let Some(declaration) = state.source(&owner) {
+        return state
+            .with_document(&declaration.location.uri, |document| {
+                document.analyze(Position::default(), |root, source, _| {
+                    Some(source_overloads(
+                        state,
+                        root,
+                        source,
+                        &owner,
+                        name.as_deref(),
+                        arguments,
+                    ))
+                })
+            })
+            .unwrap_or_default();
+    }
+
+    let Some(class) = state.class(&owner) else {
+        return Vec::new();
+    };
+    let name = name.as_deref().unwrap_or("<init>");
+    class
+        .methods
+        .iter()
+        .filter(|method| {
+            let parameters = method.parameters.len();
+            method.name == name
+                && !method
+                    .flags
+                    .intersects(MethodFlags::ACC_BRIDGE | MethodFlags::ACC_SYNTHETIC)
+                && (parameters == arguments
+                    || (method.flags.contains(MethodFlags::ACC_VARARGS)
+                        && arguments + 1 >= parameters))
+        })
+        .map(|method| Overload {
+            names: method.parameter_names.clone(),
+            types: method
+                .parameters
+                .iter()
+                .map(|parameter| match parameter {
+                    JavaType::Class(fqdn) => Some(Supertype {
+                        fqdn: fqdn.replace('$', "."),
+                        arguments: Vec::new(),
+                    }),
+                    _ => None,
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// Overloads of the method `name`, or of the constructors for `None`, of the type `owner`
+/// that is declared in the compilation unit `root`
+fn source_overloads(
+    state: &State,
+    root: Node,
+    source: &str,
+    owner: &str,
+    name: Option<&str>,
+    arguments: usize,
+) -> Vec<Overload> {
+    let scope = Scope::new(root, source.as_bytes());
+
+    calls::methods(root, source)
+        .into_iter()
+        .filter(|(method, declaration)| {
+            method.owner == owner
+                && method.accepts(arguments)
+                && match name {
+                    Some(name) => declaration.kind() == "method_declaration" && method.name == name,
+                    None => declaration.kind() == "constructor_declaration",
+                }
+        })
+        .filter_map(|(_, declaration)| {
+            let parameters = declaration.child_by_field_name("parameters")?;
+            let mut cursor = parameters.walk();
+            let mut names = Vec::new();
+            let mut types = Vec::new();
+            for parameter in parameters.named_children(&mut cursor) {
+                match parameter.kind() {
+                    "formal_parameter" => {
+                        names.push(
+                            source[parameter.child_by_field_name("name")?.byte_range()].to_string(),
+                        );
+                        types.push(
+                            parameter.child_by_field_name("type").and_then(|declared| {
+                                declared_type(state, &scope, source, declared)
+                            }),
+                        );
+                    }
+                    "spread_parameter" => {
+                        let mut cursor = parameter.walk();
+                        let declarator = parameter
+                            .named_children(&mut cursor)
+                            .find(|child| child.kind() == "variable_declarator")?;
+                        names.push(
+                            source[declarator.child_by_field_name("name")?.byte_range()]
+                                .to_string(),
+                        );
+                        types.push(None);
+                    }
+                    _ => {}
+                }
+            }
+
+            Some(Overload { names, types })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::{Node, Parser};
+
+    use super::declarator_types;
+
+    /// Labels of the hints of the `var` declaration in `body`, every initializer has the
+    /// type `java.util.List`
+    fn labels(body: &str) -> Vec<String> {
+        fn find<'tree>(node: Node<'tree>, kind: &str) -> Option<Node<'tree>> {
+            if node.kind() == kind {
+                return Some(node);
+            }
+            let mut cursor = node.walk();
+            let children = node.named_children(&mut cursor).collect::<Vec<Node>>();
+            children.into_iter().find_map(|child| find(child, kind))
+        }
+
+        let source = format!("class Demo {{\n    void run() {{\n        {body}\n    }}\n}}\n");
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_java::LANGUAGE.into())
+            .unwrap();
+        let tree = parser.parse(&source, None).unwrap();
+        let declaration = find(tree.root_node(), "local_variable_declaration").unwrap();
+
+        declarator_types(declaration, |_| Some("java.util.List".to_string()))
+            .into_iter()
+            .map(|hint| {
+                format!(
+                    "{}{}",
+                    &source[..hint.offset].rsplit(' ').next().unwrap(),
+                    hint.label
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn initializers_that_name_the_type_get_no_hint() {
+        for body in [
+            "var names = new ArrayList<String>();",
+            "var names = new String[3];",
+            "var names = (List<String>) value;",
+            "var names = \"text\";",
+        ] {
+            assert_eq!(labels(body), Vec::<String>::new(), "{body}");
+        }
+    }
+
+    #[test]
+    fn method_calls_get_the_type_they_resolve_to() {
+        assert_eq!(labels("var names = names();"), ["names: List"]);
+        assert_eq!(
+            labels("var names = names(), copy = new ArrayList<String>();"),
+            ["names: List"]
+        );
+    }
+}
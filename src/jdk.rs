@@ -0,0 +1,60 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{build_tool::search_path, jimage::Image};
+
+/// Home directory of the JDK, the one of the `javac` that compiles the project is preferred
+pub fn home() -> Option<PathBuf> {
+    search_path(Path::new("javac"))
+        .and_then(|javac| fs::canonicalize(javac).ok())
+        .and_then(|javac| Some(javac.parent()?.parent()?.to_path_buf()))
+        .or_else(|| env::var_os("JAVA_HOME").map(PathBuf::from))
+}
+
+/// Modules of the JDK that contain the public API. These are the module files (jmods) or,
+/// for JDKs without them, the modules of the runtime image like `lib/modules/java.base`.
+pub fn modules() -> Vec<PathBuf> {
+    let Some(home) = home() else {
+        return Vec::new();
+    };
+
+    let jmods = fs::read_dir(home.join("jmods"))
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .map(|name| name.starts_with("java.") && name.ends_with(".jmod"))
+                .unwrap_or(false)
+        })
+        .collect::<Vec<PathBuf>>();
+    if !jmods.is_empty() {
+        return jmods;
+    }
+
+    let path = home.join("lib").join("modules");
+    let Some(image) = Image::open(&path) else {
+        eprintln!(
+            "Could not find the modules of the JDK at {}, neither jmods nor a runtime image",
+            home.display()
+        );
+        return Vec::new();
+    };
+    image
+        .modules()
+        .into_iter()
+        .filter(|module| module.starts_with("java."))
+        .map(|module| path.join(module))
+        .collect()
+}
+
+/// Runtime image and name of a module from [`modules`] that is part of the image
+pub fn image_module(path: &Path) -> Option<(&Path, &str)> {
+    let image = path.parent()?;
+    (image.file_name()? == "modules" && image.is_file())
+        .then_some((image, path.file_name()?.to_str()?))
+}
@@ -0,0 +1,355 @@
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+/// Magic number of the runtime image `lib/modules` of the JDK
+const MAGIC: u32 = 0xCAFE_DADA;
+const HEADER_SIZE: usize = 7 * 4;
+
+/// Kinds of the attributes of a location, they follow the order of `jdk.internal.jimage`
+const ATTRIBUTE_END: u8 = 0;
+const ATTRIBUTE_MODULE: u8 = 1;
+const ATTRIBUTE_PARENT: u8 = 2;
+const ATTRIBUTE_BASE: u8 = 3;
+const ATTRIBUTE_EXTENSION: u8 = 4;
+const ATTRIBUTE_OFFSET: u8 = 5;
+const ATTRIBUTE_COMPRESSED: u8 = 6;
+const ATTRIBUTE_UNCOMPRESSED: u8 = 7;
+
+/// Runtime image of the JDK, it contains the classes of all modules
+pub struct Image {
+    file: File,
+    resources: Vec<Resource>,
+}
+
+/// File of a module in the image
+pub struct Resource {
+    pub module: String,
+    /// Path within the module like `java/lang/Object.class`
+    pub path: String,
+    offset: u64,
+    size: usize,
+}
+
+impl Image {
+    /// Read the index of the image at `path`, compressed resources are left out
+    pub fn open(path: &Path) -> Option<Image> {
+        let mut file = File::open(path).ok()?;
+        let mut header = [0; HEADER_SIZE];
+        file.read_exact(&mut header).ok()?;
+
+        // The image is written in the byte order of the platform
+        let is_little_endian = u32::from_le_bytes(header[0..4].try_into().ok()?) == MAGIC;
+        if !is_little_endian && u32::from_be_bytes(header[0..4].try_into().ok()?) != MAGIC {
+            return None;
+        }
+        let u32_at = |bytes: &[u8], index: usize| -> Option<u32> {
+            let bytes = bytes.get(index..index + 4)?.try_into().ok()?;
+            Some(if is_little_endian {
+                u32::from_le_bytes(bytes)
+            } else {
+                u32::from_be_bytes(bytes)
+            })
+        };
+        let table_length = u32_at(&header, 16)? as usize;
+        let locations_size = u32_at(&header, 20)? as usize;
+        let strings_size = u32_at(&header, 24)? as usize;
+
+        // Redirects and offsets of the perfect hash table precede the locations and strings
+        let mut index = vec![0; table_length * 8 + locations_size + strings_size];
+        file.read_exact(&mut index).ok()?;
+        let offsets = &index[table_length * 4..table_length * 8];
+        let locations = &index[table_length * 8..table_length * 8 + locations_size];
+        let strings = &index[table_length * 8 + locations_size..];
+        let content_start = (HEADER_SIZE + index.len()) as u64;
+
+        let resources = (0..table_length)
+            .filter_map(|i| {
+                let attributes = attributes(locations, u32_at(offsets, i * 4)? as usize)?;
+                let string = |kind: u8| string(strings, attributes[kind as usize] as usize);
+                if attributes[ATTRIBUTE_COMPRESSED as usize] != 0 {
+                    return None;
+                }
+
+                let parent = string(ATTRIBUTE_PARENT)?;
+                let base = string(ATTRIBUTE_BASE)?;
+                let extension = string(ATTRIBUTE_EXTENSION)?;
+                let mut path = String::new();
+                if !parent.is_empty() {
+                    path.push_str(parent);
+                    path.push('/');
+                }
+                path.push_str(base);
+                if !extension.is_empty() {
+                    path.push('.');
+                    path.push_str(extension);
+                }
+
+                Some(Resource {
+                    module: string(ATTRIBUTE_MODULE)?.to_string(),
+                    path,
+                    offset: content_start + attributes[ATTRIBUTE_OFFSET as usize],
+                    size: attributes[ATTRIBUTE_UNCOMPRESSED as usize] as usize,
+                })
+            })
+            .collect();
+
+        Some(Image { file, resources })
+    }
+
+    pub fn resources(&self) -> &[Resource] {
+        &self.resources
+    }
+
+    /// Names of the modules with resources in the image
+    pub fn modules(&self) -> Vec<&str> {
+        let mut modules = self
+            .resources
+            .iter()
+            .map(|resource| resource.module.as_str())
+            .filter(|module| !module.is_empty())
+            .collect::<Vec<&str>>();
+        modules.sort();
+        modules.dedup();
+
+        modules
+    }
+
+    pub fn read(&self, resource: &Resource) -> Option<Vec<u8>> {
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(resource.offset)).ok()?;
+        let mut content = vec![0; resource.size];
+        file.read_exact(&mut content).ok()?;
+
+        Some(content)
+    }
+}
+
+/// Attributes of the location at `offset` by their kind, each one is a byte with the kind
+/// and the length that is followed by the big endian value
+fn attributes(locations: &[u8], offset: usize) -> Option<[u64; 8]> {
+    let mut attributes = [0; 8];
+    let mut index = offset;
+    loop {
+        let byte = *locations.get(index)?;
+        let kind = byte >> 3;
+        if kind == ATTRIBUTE_END {
+            return Some(attributes);
+        }
+
+        let length = (byte & 0x7) as usize + 1;
+        let value = locations
+            .get(index + 1..index + 1 + length)?
+            .iter()
+            .fold(0, |value, byte| (value << 8) | u64::from(*byte));
+        *attributes.get_mut(kind as usize)? = value;
+        index += 1 + length;
+    }
+}
+
+/// Zero terminated string at `offset` of the strings of the image
+fn string(strings: &[u8], offset: usize) -> Option<&str> {
+    let bytes = strings.get(offset..)?;
+    let end = bytes.iter().position(|byte| *byte == 0)?;
+
+    std::str::from_utf8(&bytes[..end]).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf, process};
+
+    use super::{
+        ATTRIBUTE_BASE, ATTRIBUTE_COMPRESSED, ATTRIBUTE_END, ATTRIBUTE_EXTENSION, ATTRIBUTE_MODULE,
+        ATTRIBUTE_OFFSET, ATTRIBUTE_PARENT, ATTRIBUTE_UNCOMPRESSED, Image, MAGIC,
+    };
+
+    /// Resource of a fixture image
+    struct Entry {
+        module: &'static str,
+        parent: &'static str,
+        base: &'static str,
+        extension: &'static str,
+        content: &'static [u8],
+        compressed: bool,
+    }
+
+    const ENTRIES: [Entry; 4] = [
+        Entry {
+            module: "java.base",
+            parent: "java/lang",
+            base: "Object",
+            extension: "class",
+            content: b"\xCA\xFE\xBA\xBEobject",
+            compressed: false,
+        },
+        Entry {
+            module: "java.sql",
+            parent: "java/sql",
+            base: "Date",
+            extension: "class",
+            content: b"\xCA\xFE\xBA\xBEdate",
+            compressed: false,
+        },
+        Entry {
+            module: "java.base",
+            parent: "",
+            base: "module-info",
+            extension: "class",
+            content: b"\xCA\xFE\xBA\xBEmodule",
+            compressed: false,
+        },
+        Entry {
+            module: "java.desktop",
+            parent: "java/awt",
+            base: "Color",
+            extension: "class",
+            content: b"compressed",
+            compressed: true,
+        },
+    ];
+
+    /// Write an image with `entries` like jlink does, the hash table is left out as the
+    /// index is read sequentially
+    fn write_image(name: &str, entries: &[Entry], is_little_endian: bool) -> PathBuf {
+        let u32_bytes = |value: u32| {
+            if is_little_endian {
+                value.to_le_bytes()
+            } else {
+                value.to_be_bytes()
+            }
+        };
+
+        let mut strings = vec![0];
+        let mut string = |value: &str| {
+            let offset = strings.len() as u64;
+            strings.extend_from_slice(value.as_bytes());
+            strings.push(0);
+            offset
+        };
+
+        let mut offsets = Vec::new();
+        let mut locations = Vec::new();
+        let mut content = Vec::new();
+        for entry in entries {
+            offsets.extend_from_slice(&u32_bytes(locations.len() as u32));
+            let attributes = [
+                (ATTRIBUTE_MODULE, string(entry.module)),
+                (ATTRIBUTE_PARENT, string(entry.parent)),
+                (ATTRIBUTE_BASE, string(entry.base)),
+                (ATTRIBUTE_EXTENSION, string(entry.extension)),
+                (ATTRIBUTE_OFFSET, content.len() as u64),
+                (
+                    ATTRIBUTE_COMPRESSED,
+                    if entry.compressed {
+                        entry.content.len() as u64
+                    } else {
+                        0
+                    },
+                ),
+                (ATTRIBUTE_UNCOMPRESSED, entry.content.len() as u64),
+            ];
+            for (kind, value) in attributes {
+                // Values are big endian with as few bytes as needed
+                let bytes = value.to_be_bytes();
+                let start = bytes.iter().position(|byte| *byte != 0).unwrap_or(7);
+                locations.push(kind << 3 | (7 - start) as u8);
+                locations.extend_from_slice(&bytes[start..]);
+            }
+            locations.push(ATTRIBUTE_END);
+            content.extend_from_slice(entry.content);
+        }
+
+        let mut image = Vec::new();
+        for value in [
+            MAGIC,
+            1 << 16,
+            0,
+            entries.len() as u32,
+            entries.len() as u32,
+            locations.len() as u32,
+            strings.len() as u32,
+        ] {
+            image.extend_from_slice(&u32_bytes(value));
+        }
+        image.extend(entries.iter().flat_map(|_| u32_bytes(0)));
+        image.extend(offsets);
+        image.extend(locations);
+        image.extend(strings);
+        image.extend(content);
+
+        let path = env::temp_dir().join(format!("java-lsp-{}-{name}", process::id()));
+        fs::write(&path, image).unwrap();
+        path
+    }
+
+    fn read_all(image: &Image) -> Vec<(String, String, Vec<u8>)> {
+        image
+            .resources()
+            .iter()
+            .map(|resource| {
+                (
+                    resource.module.clone(),
+                    resource.path.clone(),
+                    image.read(resource).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn resources_are_read_in_both_byte_orders() {
+        for is_little_endian in [true, false] {
+            let path = write_image(&format!("{is_little_endian}"), &ENTRIES, is_little_endian);
+            let image = Image::open(&path).unwrap();
+
+            // Compressed resources are left out
+            assert_eq!(
+                read_all(&image),
+                [
+                    (
+                        "java.base",
+                        "java/lang/Object.class",
+                        b"\xCA\xFE\xBA\xBEobject".to_vec()
+                    ),
+                    (
+                        "java.sql",
+                        "java/sql/Date.class",
+                        b"\xCA\xFE\xBA\xBEdate".to_vec()
+                    ),
+                    (
+                        "java.base",
+                        "module-info.class",
+                        b"\xCA\xFE\xBA\xBEmodule".to_vec()
+                    ),
+                ]
+                .map(|(module, path, content)| (
+                    module.to_string(),
+                    path.to_string(),
+                    content
+                ))
+            );
+            assert_eq!(image.modules(), ["java.base", "java.sql"]);
+
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn other_files_are_not_images() {
+        let path = write_image("magic", &ENTRIES, true);
+        let mut content = fs::read(&path).unwrap();
+        content[0] ^= 0xFF;
+        fs::write(&path, &content).unwrap();
+        assert!(Image::open(&path).is_none());
+
+        // The index must be complete
+        content[0] ^= 0xFF;
+        fs::write(&path, &content[..40]).unwrap();
+        assert!(Image::open(&path).is_none());
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,223 @@
+use tree_sitter::Node;
+
+use crate::source_edit::statement;
+
+/// Local variable or parameter of a method
+pub struct Local<'tree> {
+    pub name: String,
+    /// Declared type, `None` for `var` and lambda parameters without types
+    pub declared_type: Option<String>,
+    /// Name of the declaration
+    pub node: Node<'tree>,
+    /// Node that the variable is visible in
+    pub scope: Node<'tree>,
+}
+
+/// Local variables and parameters that are declared in `method`, outside of local and
+/// anonymous classes, in the order of their declarations
+pub fn locals<'tree>(method: Node<'tree>, source: &str) -> Vec<Local<'tree>> {
+    fn visit<'tree>(node: Node<'tree>, source: &str, locals: &mut Vec<Local<'tree>>) {
+        let field = |field: &str| node.child_by_field_name(field);
+        let mut declare = |name: Option<Node<'tree>>, declared_type: Option<String>, scope| {
+            if let Some(name) = name.filter(|name| name.kind() == "identifier") {
+                locals.push(Local {
+                    name: source[name.byte_range()].to_string(),
+                    declared_type,
+                    node: name,
+                    scope,
+                });
+            }
+        };
+
+        match node.kind() {
+            "class_body" | "interface_body" | "enum_body" => return,
+            "formal_parameter" => declare(
+                field("name"),
+                declared_type(field("type"), field("dimensions"), source),
+                node.parent()
+                    .and_then(|parameters| parameters.parent())
+                    .unwrap_or(node),
+            ),
+            "spread_parameter" => {
+                let mut cursor = node.walk();
+                let children = node.named_children(&mut cursor).collect::<Vec<Node>>();
+                let declared = children
+                    .iter()
+                    .find(|child| !matches!(child.kind(), "modifiers" | "variable_declarator"))
+                    .map(|declared| format!("{}[]", &source[declared.byte_range()]));
+                let name = children
+                    .iter()
+                    .find(|child| child.kind() == "variable_declarator")
+                    .and_then(|declarator| declarator.child_by_field_name("name"));
+                declare(
+                    name,
+                    declared,
+                    node.parent()
+                        .and_then(|parameters| parameters.parent())
+                        .unwrap_or(node),
+                );
+            }
+            "catch_formal_parameter" => {
+                let mut cursor = node.walk();
+                let declared = node
+                    .named_children(&mut cursor)
+                    .find(|child| child.kind() == "catch_type")
+                    .map(|declared| source[declared.byte_range()].to_string());
+                declare(field("name"), declared, node.parent().unwrap_or(node));
+            }
+            "enhanced_for_statement" => declare(
+                field("name"),
+                declared_type(field("type"), field("dimensions"), source),
+                node,
+            ),
+            "resource" => declare(
+                field("name"),
+                declared_type(field("type"), field("dimensions"), source),
+                node.parent()
+                    .and_then(|resources| resources.parent())
+                    .unwrap_or(node),
+            ),
+            "local_variable_declaration" => {
+                let mut cursor = node.walk();
+                let declarators = node
+                    .children_by_field_name("declarator", &mut cursor)
+                    .collect::<Vec<Node>>();
+                for declarator in declarators {
+                    declare(
+                        declarator.child_by_field_name("name"),
+                        declared_type(
+                            field("type"),
+                            declarator.child_by_field_name("dimensions"),
+                            source,
+                        ),
+                        node.parent().unwrap_or(node),
+                    );
+                }
+            }
+            "inferred_parameters" => {
+                let mut cursor = node.walk();
+                let names = node.named_children(&mut cursor).collect::<Vec<Node>>();
+                for name in names {
+                    declare(Some(name), None, node.parent().unwrap_or(node));
+                }
+            }
+            "lambda_expression" => declare(field("parameters"), None, node),
+            // Pattern variables are approximated to be visible in the whole block
+            "instanceof_expression" => declare(
+                field("name"),
+                field("right").map(|declared| source[declared.byte_range()].to_string()),
+                pattern_scope(node),
+            ),
+            "type_pattern" => {
+                let declared = node.named_child(0);
+                declare(
+                    node.named_child(1),
+                    declared.map(|declared| source[declared.byte_range()].to_string()),
+                    pattern_scope(node),
+                );
+            }
+            _ => {}
+        }
+
+        let mut cursor = node.walk();
+        let children = node.named_children(&mut cursor).collect::<Vec<Node>>();
+        for child in children {
+            visit(child, source, locals);
+        }
+    }
+
+    let mut locals = Vec::new();
+    visit(method, source, &mut locals);
+
+    locals
+}
+
+fn pattern_scope(node: Node) -> Node {
+    statement(node)
+        .and_then(|statement| statement.parent())
+        .unwrap_or(node)
+}
+
+/// Declared type with the dimensions of the name like `int x[]`, `None` for `var`
+pub fn declared_type(
+    declared: Option<Node>,
+    dimensions: Option<Node>,
+    source: &str,
+) -> Option<String> {
+    let declared = &source[declared?.byte_range()];
+    if declared == "var" {
+        return None;
+    }
+
+    Some(match dimensions {
+        Some(dimensions) => format!("{declared}{}", &source[dimensions.byte_range()]),
+        None => declared.to_string(),
+    })
+}
+
+/// Index of the local variable that the identifier `node` refers to or declares
+pub fn declaration(locals: &[Local], node: Node, source: &str) -> Option<usize> {
+    if node.kind() != "identifier" {
+        return None;
+    }
+    if let Some(local) = locals.iter().position(|local| local.node == node) {
+        return Some(local);
+    }
+    if !is_reference(node) {
+        return None;
+    }
+
+    let name = &source[node.byte_range()];
+    locals.iter().rposition(|local| {
+        local.name == name
+            && local.node.start_byte() < node.start_byte()
+            && local.scope.start_byte() <= node.start_byte()
+            && node.end_byte() <= local.scope.end_byte()
+    })
+}
+
+pub fn local_type(locals: &[Local], node: Node, source: &str) -> Option<String> {
+    declaration(locals, node, source).and_then(|local| locals[local].declared_type.clone())
+}
+
+/// Whether the identifier `node` can refer to a variable, names of members and labels can not
+pub fn is_reference(node: Node) -> bool {
+    let Some(parent) = node.parent() else {
+        return false;
+    };
+
+    match parent.kind() {
+        "field_access" => parent.child_by_field_name("field") != Some(node),
+        "method_invocation" => parent.child_by_field_name("name") != Some(node),
+        "labeled_statement" | "break_statement" | "continue_statement" | "method_reference" => {
+            false
+        }
+        _ => true,
+    }
+}
+
+/// Call `visit` with every identifier below `node` that refers to a local variable, the index
+/// of the variable and whether the variable is assigned there
+pub fn visit_references<'tree>(
+    node: Node<'tree>,
+    source: &str,
+    locals: &[Local<'tree>],
+    visit: &mut impl FnMut(Node<'tree>, usize, bool),
+) {
+    if node.kind() == "identifier"
+        && let Some(local) = declaration(locals, node, source)
+    {
+        let is_assignment = node.parent().is_some_and(|parent| {
+            (parent.kind() == "assignment_expression"
+                && parent.child_by_field_name("left") == Some(node))
+                || parent.kind() == "update_expression"
+        });
+        visit(node, local, is_assignment);
+    }
+
+    let mut cursor = node.walk();
+    let children = node.named_children(&mut cursor).collect::<Vec<Node>>();
+    for child in children {
+        visit_references(child, source, locals, visit);
+    }
+}
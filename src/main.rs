@@ -1,3 +1,6 @@
+mod build_tool;
+mod calls;
+mod class_source;
 mod classfile;
 mod classpath_indexer;
 mod compiler;
@@ -5,16 +8,45 @@ mod configuration;
 mod diagnostic;
 mod document;
 mod errors;
+mod folding;
+mod formatter;
+mod generate;
 mod gradle;
 mod handlers;
+mod hierarchy;
+mod highlight;
+mod imports;
+mod inlay_hints;
+mod jdk;
+mod jimage;
+mod locals;
 mod main_loop;
 mod maven;
+mod organize_imports;
+mod position;
+mod project;
+mod quick_fix;
+mod refactor;
+mod resolver;
+mod semantic_tokens;
+mod source_edit;
+mod source_index;
 mod state;
+mod symbols;
 mod tree_sitter;
 
 use compiler::Compiler;
+use configuration::Configuration;
 use lsp_server::Connection;
-use lsp_types::{ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind};
+use lsp_types::{
+    CallHierarchyServerCapability, CodeActionKind, CodeActionOptions, CodeActionProviderCapability,
+    DocumentOnTypeFormattingOptions, FoldingRangeProviderCapability,
+    ImplementationProviderCapability, InitializeParams, InitializeResult, MessageType, OneOf,
+    SelectionRangeProviderCapability, SemanticTokensFullOptions, SemanticTokensOptions,
+    SemanticTokensServerCapabilities, ServerCapabilities, TextDocumentSyncCapability,
+    TextDocumentSyncKind, TypeDefinitionProviderCapability,
+};
+use position::PositionEncoding;
 use state::State;
 use std::error::Error;
 
@@ -23,19 +55,9 @@ fn main() -> Result<(), Box<dyn Error + Sync + Send>> {
     configuration::initialize_data_directory();
 
     let (connection, io_threads) = Connection::stdio();
-    let mut state = State::new(connection.sender.clone(), Compiler::new());
 
     // Run the server and wait for the two threads to end (typically by trigger LSP Exit event).
-    let server_capabilities = serde_json::to_value(&ServerCapabilities {
-        text_document_sync: Some(TextDocumentSyncCapability::Kind(
-            TextDocumentSyncKind::INCREMENTAL,
-        )),
-        definition_provider: Some(lsp_types::OneOf::Left(true)),
-        ..Default::default()
-    })
-    .unwrap();
-
-    let initialization_params = match connection.initialize(server_capabilities) {
+    let (initialize_id, initialization_params) = match connection.initialize_start() {
         Ok(it) => it,
         Err(e) => {
             if e.channel_is_disconnected() {
@@ -44,6 +66,87 @@ fn main() -> Result<(), Box<dyn Error + Sync + Send>> {
             return Err(e.into());
         }
     };
+    let params = serde_json::from_value::<InitializeParams>(initialization_params.clone()).ok();
+    let encoding = params
+        .as_ref()
+        .map(|params| PositionEncoding::negotiate(&params.capabilities))
+        .unwrap_or_default();
+    let change_annotations = params
+        .as_ref()
+        .and_then(|params| params.capabilities.workspace.as_ref())
+        .and_then(|workspace| workspace.workspace_edit.as_ref())
+        .is_some_and(|workspace_edit| {
+            workspace_edit.document_changes == Some(true)
+                && workspace_edit.change_annotation_support.is_some()
+        });
+
+    let mut initialize_result = serde_json::to_value(InitializeResult {
+        capabilities: ServerCapabilities {
+            position_encoding: Some(encoding.kind()),
+            text_document_sync: Some(TextDocumentSyncCapability::Kind(
+                TextDocumentSyncKind::INCREMENTAL,
+            )),
+            definition_provider: Some(OneOf::Left(true)),
+            type_definition_provider: Some(TypeDefinitionProviderCapability::Simple(true)),
+            implementation_provider: Some(ImplementationProviderCapability::Simple(true)),
+            document_highlight_provider: Some(OneOf::Left(true)),
+            inlay_hint_provider: Some(OneOf::Left(true)),
+            code_action_provider: Some(CodeActionProviderCapability::Options(CodeActionOptions {
+                code_action_kinds: Some(vec![
+                    CodeActionKind::QUICKFIX,
+                    CodeActionKind::REFACTOR_EXTRACT,
+                    CodeActionKind::REFACTOR_INLINE,
+                    CodeActionKind::SOURCE_ORGANIZE_IMPORTS,
+                    CodeActionKind::from("source.generate".to_string()),
+                    CodeActionKind::from("source.overrideMethods".to_string()),
+                ]),
+                ..Default::default()
+            })),
+            document_formatting_provider: Some(OneOf::Left(true)),
+            document_range_formatting_provider: Some(OneOf::Left(true)),
+            document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+                first_trigger_character: "}".to_string(),
+                more_trigger_character: Some(vec![";".to_string()]),
+            }),
+            folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+            selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
+            call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
+            semantic_tokens_provider: Some(
+                SemanticTokensServerCapabilities::SemanticTokensOptions(SemanticTokensOptions {
+                    legend: semantic_tokens::legend(),
+                    range: Some(true),
+                    full: Some(SemanticTokensFullOptions::Delta { delta: Some(true) }),
+                    ..Default::default()
+                }),
+            ),
+            ..Default::default()
+        },
+        server_info: None,
+    })
+    .unwrap();
+    // lsp-types has no server capability for the type hierarchy yet
+    initialize_result["capabilities"]["typeHierarchyProvider"] = true.into();
+    if let Err(e) = connection.initialize_finish(initialize_id, initialize_result) {
+        if e.channel_is_disconnected() {
+            io_threads.join()?;
+        }
+        return Err(e.into());
+    }
+
+    let configuration = Configuration::from_initialization_options(
+        params.and_then(|params| params.initialization_options),
+    );
+    let compiler = Compiler::new(&configuration).unwrap_or_else(|error| {
+        diagnostic::show_message(&connection.sender, MessageType::ERROR, error.to_string());
+        Compiler::without_build_tool()
+    });
+    let mut state = State::new(
+        connection.sender.clone(),
+        compiler,
+        configuration,
+        encoding,
+        change_annotations,
+    );
 
     main_loop::start(connection, initialization_params, &mut state)?;
     io_threads.join()?;
@@ -1,87 +1,290 @@
 use std::error::Error;
 
-use lsp_server::{Connection, Message};
+use crossbeam_channel::select;
+use lsp_server::{Connection, ErrorCode, Message, RequestId, Response};
 use lsp_types::{
-    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
-    DidSaveTextDocumentParams,
+    DidChangeTextDocumentParams, DidChangeWatchedFilesParams,
+    DidChangeWatchedFilesRegistrationOptions, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, DidSaveTextDocumentParams, FileSystemWatcher, GlobPattern,
+    InitializeParams, Registration, RegistrationParams,
     notification::{
-        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, DidSaveTextDocument,
-        Notification,
+        DidChangeTextDocument, DidChangeWatchedFiles, DidCloseTextDocument, DidOpenTextDocument,
+        DidSaveTextDocument, Notification,
+    },
+    request::{
+        CallHierarchyIncomingCalls, CallHierarchyOutgoingCalls, CallHierarchyPrepare,
+        CodeActionRequest, DocumentHighlightRequest, FoldingRangeRequest, Formatting,
+        GotoDefinition, GotoImplementation, GotoTypeDefinition, InlayHintRequest, OnTypeFormatting,
+        RangeFormatting, RegisterCapability, Request, SelectionRangeRequest,
+        SemanticTokensFullDeltaRequest, SemanticTokensFullRequest, SemanticTokensRangeRequest,
+        TypeHierarchyPrepare, TypeHierarchySubtypes, TypeHierarchySupertypes,
     },
-    request::{GotoDefinition, Request},
 };
 use serde::de::DeserializeOwned;
 
-use crate::{handlers, state::State};
+use crate::{
+    compiler::Compiler, configuration::BUILD_FILE_PATTERNS, errors::ProjectError, handlers,
+    state::State,
+};
+
+/// Work that was done in the background and has to be applied to the state
+pub enum Task {
+    /// Result of [`State::reload_project`]
+    ProjectReloaded(Result<Compiler, ProjectError>),
+}
 
 pub fn start(
     connection: Connection,
-    _params: serde_json::Value,
+    params: serde_json::Value,
     state: &mut State,
 ) -> Result<(), Box<dyn Error + Sync + Send>> {
-    for msg in &connection.receiver {
-        match msg {
-            Message::Request(request) => {
-                if connection.handle_shutdown(&request)? {
-                    return Ok(());
-                }
+    register_build_file_watchers(&connection, params);
+    let tasks = state.task_receiver();
 
-                match request.method.as_str() {
-                    GotoDefinition::METHOD => {
-                        handle_request(state, request, handlers::handle_go_to_definition)
-                    }
-                    _ => {}
+    loop {
+        select! {
+            recv(connection.receiver) -> msg => {
+                let Ok(msg) = msg else {
+                    break;
                 };
+
+                if handle_message(&connection, state, msg)? {
+                    return Ok(());
+                }
             }
-            Message::Response(_reponse) => {}
-            Message::Notification(notification) => {
-                match notification.method.as_str() {
-                    DidOpenTextDocument::METHOD => {
-                        handle_notification::<DidOpenTextDocumentParams>(
-                            state,
-                            notification,
-                            handlers::handle_did_open_text_document,
-                        )
-                    }
-                    DidChangeTextDocument::METHOD => {
-                        handle_notification::<DidChangeTextDocumentParams>(
-                            state,
-                            notification,
-                            handlers::handle_did_change_text_document,
-                        )
-                    }
-                    DidSaveTextDocument::METHOD => {
-                        handle_notification::<DidSaveTextDocumentParams>(
-                            state,
-                            notification,
-                            handlers::handle_did_save_text_document,
-                        )
-                    }
-                    DidCloseTextDocument::METHOD => {
-                        handle_notification::<DidCloseTextDocumentParams>(
-                            state,
-                            notification,
-                            handlers::handle_did_close_text_document,
-                        )
-                    }
-                    _ => {}
-                };
+            recv(tasks) -> task => {
+                if let Ok(task) = task {
+                    handle_task(state, task);
+                }
             }
         }
     }
+
     Ok(())
 }
 
-fn handle_request<P>(
+/// Handle a message of the client, returns true if the server should shut down
+fn handle_message(
+    connection: &Connection,
+    state: &mut State,
+    msg: Message,
+) -> Result<bool, Box<dyn Error + Sync + Send>> {
+    match msg {
+        Message::Request(request) => {
+            if connection.handle_shutdown(&request)? {
+                return Ok(true);
+            }
+
+            match request.method.as_str() {
+                GotoDefinition::METHOD => handle_request::<GotoDefinition>(
+                    state,
+                    request,
+                    handlers::handle_go_to_definition,
+                ),
+                GotoImplementation::METHOD => handle_request::<GotoImplementation>(
+                    state,
+                    request,
+                    handlers::handle_go_to_implementation,
+                ),
+                GotoTypeDefinition::METHOD => handle_request::<GotoTypeDefinition>(
+                    state,
+                    request,
+                    handlers::handle_go_to_type_definition,
+                ),
+                DocumentHighlightRequest::METHOD => handle_request::<DocumentHighlightRequest>(
+                    state,
+                    request,
+                    handlers::handle_document_highlight,
+                ),
+                InlayHintRequest::METHOD => {
+                    handle_request::<InlayHintRequest>(state, request, handlers::handle_inlay_hint)
+                }
+                FoldingRangeRequest::METHOD => handle_request::<FoldingRangeRequest>(
+                    state,
+                    request,
+                    handlers::handle_folding_range,
+                ),
+                CodeActionRequest::METHOD => handle_request::<CodeActionRequest>(
+                    state,
+                    request,
+                    handlers::handle_code_action,
+                ),
+                Formatting::METHOD => {
+                    handle_request::<Formatting>(state, request, handlers::handle_formatting)
+                }
+                RangeFormatting::METHOD => handle_request::<RangeFormatting>(
+                    state,
+                    request,
+                    handlers::handle_range_formatting,
+                ),
+                OnTypeFormatting::METHOD => handle_request::<OnTypeFormatting>(
+                    state,
+                    request,
+                    handlers::handle_on_type_formatting,
+                ),
+                SelectionRangeRequest::METHOD => handle_request::<SelectionRangeRequest>(
+                    state,
+                    request,
+                    handlers::handle_selection_range,
+                ),
+                SemanticTokensFullRequest::METHOD => handle_request::<SemanticTokensFullRequest>(
+                    state,
+                    request,
+                    handlers::handle_semantic_tokens_full,
+                ),
+                SemanticTokensFullDeltaRequest::METHOD => {
+                    handle_request::<SemanticTokensFullDeltaRequest>(
+                        state,
+                        request,
+                        handlers::handle_semantic_tokens_full_delta,
+                    )
+                }
+                SemanticTokensRangeRequest::METHOD => handle_request::<SemanticTokensRangeRequest>(
+                    state,
+                    request,
+                    handlers::handle_semantic_tokens_range,
+                ),
+                TypeHierarchyPrepare::METHOD => handle_request::<TypeHierarchyPrepare>(
+                    state,
+                    request,
+                    handlers::handle_prepare_type_hierarchy,
+                ),
+                TypeHierarchySupertypes::METHOD => handle_request::<TypeHierarchySupertypes>(
+                    state,
+                    request,
+                    handlers::handle_type_hierarchy_supertypes,
+                ),
+                TypeHierarchySubtypes::METHOD => handle_request::<TypeHierarchySubtypes>(
+                    state,
+                    request,
+                    handlers::handle_type_hierarchy_subtypes,
+                ),
+                CallHierarchyPrepare::METHOD => handle_request::<CallHierarchyPrepare>(
+                    state,
+                    request,
+                    handlers::handle_prepare_call_hierarchy,
+                ),
+                CallHierarchyIncomingCalls::METHOD => handle_request::<CallHierarchyIncomingCalls>(
+                    state,
+                    request,
+                    handlers::handle_call_hierarchy_incoming_calls,
+                ),
+                CallHierarchyOutgoingCalls::METHOD => handle_request::<CallHierarchyOutgoingCalls>(
+                    state,
+                    request,
+                    handlers::handle_call_hierarchy_outgoing_calls,
+                ),
+                _ => {
+                    let _ = state.sender.send(Message::Response(Response::new_err(
+                        request.id,
+                        ErrorCode::MethodNotFound as i32,
+                        format!("Unsupported request {}", request.method),
+                    )));
+                }
+            }
+        }
+        Message::Response(_reponse) => {}
+        Message::Notification(notification) => {
+            match notification.method.as_str() {
+                DidOpenTextDocument::METHOD => handle_notification::<DidOpenTextDocumentParams>(
+                    state,
+                    notification,
+                    handlers::handle_did_open_text_document,
+                ),
+                DidChangeTextDocument::METHOD => {
+                    handle_notification::<DidChangeTextDocumentParams>(
+                        state,
+                        notification,
+                        handlers::handle_did_change_text_document,
+                    )
+                }
+                DidSaveTextDocument::METHOD => handle_notification::<DidSaveTextDocumentParams>(
+                    state,
+                    notification,
+                    handlers::handle_did_save_text_document,
+                ),
+                DidCloseTextDocument::METHOD => handle_notification::<DidCloseTextDocumentParams>(
+                    state,
+                    notification,
+                    handlers::handle_did_close_text_document,
+                ),
+                DidChangeWatchedFiles::METHOD => {
+                    handle_notification::<DidChangeWatchedFilesParams>(
+                        state,
+                        notification,
+                        handlers::handle_did_change_watched_files,
+                    )
+                }
+                _ => {}
+            };
+        }
+    }
+
+    Ok(false)
+}
+
+fn handle_task(state: &mut State, task: Task) {
+    match task {
+        Task::ProjectReloaded(compiler) => handlers::handle_project_reloaded(state, compiler),
+    }
+}
+
+/// Ask the client to notify the server about changes of build files, if it supports that
+fn register_build_file_watchers(connection: &Connection, params: serde_json::Value) {
+    let supported = serde_json::from_value::<InitializeParams>(params)
+        .ok()
+        .and_then(|params| params.capabilities.workspace)
+        .and_then(|workspace| workspace.did_change_watched_files)
+        .and_then(|capability| capability.dynamic_registration)
+        .unwrap_or(false);
+
+    if !supported {
+        return;
+    }
+
+    let options = DidChangeWatchedFilesRegistrationOptions {
+        watchers: BUILD_FILE_PATTERNS
+            .iter()
+            .map(|pattern| FileSystemWatcher {
+                glob_pattern: GlobPattern::String(pattern.to_string()),
+                kind: None,
+            })
+            .collect(),
+    };
+    let params = RegistrationParams {
+        registrations: vec![Registration {
+            id: DidChangeWatchedFiles::METHOD.to_string(),
+            method: DidChangeWatchedFiles::METHOD.to_string(),
+            register_options: serde_json::to_value(options).ok(),
+        }],
+    };
+
+    let _ = connection
+        .sender
+        .send(Message::Request(lsp_server::Request::new(
+            RequestId::from(String::from("register-build-file-watchers")),
+            RegisterCapability::METHOD.to_string(),
+            params,
+        )));
+}
+
+fn handle_request<R>(
     state: &mut State,
     request: lsp_server::Request,
-    handler: fn(&mut State, P) -> (),
+    handler: fn(&mut State, R::Params) -> R::Result,
 ) where
-    P: DeserializeOwned,
+    R: Request,
 {
-    if let Ok(params) = serde_json::from_value::<P>(request.params) {
-        handler(state, params);
-    }
+    let response = match serde_json::from_value::<R::Params>(request.params) {
+        Ok(params) => Response::new_ok(request.id, handler(state, params)),
+        Err(error) => Response::new_err(
+            request.id,
+            ErrorCode::InvalidParams as i32,
+            error.to_string(),
+        ),
+    };
+
+    let _ = state.sender.send(Message::Response(response));
 }
 
 fn handle_notification<P>(
@@ -0,0 +1,190 @@
+mod pom;
+mod resolver;
+mod version;
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use lsp_types::Url;
+use pom::Pom;
+use resolver::{MissingArtifact, Resolver};
+
+use crate::{
+    build_tool::MAVEN,
+    compiler::CompileError,
+    configuration::{Configuration, MavenResolver},
+    errors::ProjectError,
+    project::{Module, Project, split_paths},
+};
+
+/// File (relative to every module) that maven writes the classpath of the module to
+const CLASSPATH_FILE: &str = "target/java-lsp.classpath";
+
+/// Find all modules of the reactor that starts at `root`
+pub fn project(root: &Path, configuration: &Configuration) -> Result<Project, ProjectError> {
+    let mut resolver = Resolver::new();
+    let mut poms = Vec::new();
+    collect_poms(&mut resolver, root, &mut poms);
+    resolver.set_reactor(poms.clone());
+
+    let poms = poms
+        .into_iter()
+        .filter(|pom| {
+            pom.source_directories()
+                .iter()
+                .any(|directory| directory.exists())
+        })
+        .collect::<Vec<Pom>>();
+
+    let mut errors: HashMap<Url, Vec<CompileError>> = HashMap::new();
+    let classpaths = match configuration.maven.resolver {
+        MavenResolver::Native => poms
+            .iter()
+            .map(|pom| {
+                let resolution = resolver.resolve(pom);
+                report_missing_artifacts(&mut errors, pom, resolution.missing);
+
+                let mut classpath = vec![pom.output_directory()];
+                classpath.extend(resolution.classpath);
+                classpath
+            })
+            .collect::<Vec<Vec<PathBuf>>>(),
+        MavenResolver::Maven => {
+            let executable = MAVEN.find(root, configuration.maven.executable.as_deref())?;
+            generate_classpath_files(root, &executable);
+
+            poms.iter().map(|pom| classpath(pom, &poms)).collect()
+        }
+    };
+
+    let modules = poms
+        .iter()
+        .zip(classpaths)
+        .map(|(pom, classpath)| {
+            // Annotation processors are always resolved from the local repository
+            let processors = resolver.resolve_annotation_processors(pom);
+            report_missing_artifacts(&mut errors, pom, processors.missing);
+
+            Module {
+                name: pom.artifact_id.clone(),
+                root: pom.directory().to_path_buf(),
+                source_roots: pom.source_directories(),
+                output_directory: pom.output_directory(),
+                classpath,
+                release: None,
+                processor_path: processors.classpath,
+                generated_sources: pom.generated_sources_directory(),
+            }
+        })
+        .collect();
+
+    Ok(Project { modules, errors })
+}
+
+fn collect_poms(resolver: &mut Resolver, root: &Path, poms: &mut Vec<Pom>) {
+    if poms.iter().any(|pom| pom.directory() == root) {
+        return;
+    }
+
+    if let Some(pom) = resolver.effective_pom(&root.join("pom.xml")) {
+        let modules = pom.module_directories();
+        poms.push(pom);
+
+        for module in modules {
+            collect_poms(resolver, &module, poms);
+        }
+    }
+}
+
+/// Report artifacts that are missing in the local repository on the dependency that requires them
+fn report_missing_artifacts(
+    errors: &mut HashMap<Url, Vec<CompileError>>,
+    pom: &Pom,
+    missing: Vec<MissingArtifact>,
+) {
+    for missing in missing {
+        let location = missing
+            .required_by
+            .location
+            .as_ref()
+            .map(|location| (location.path.clone(), location.row, location.column))
+            .unwrap_or((pom.path.clone(), 1, 1));
+
+        if let Ok(url) = Url::from_file_path(&location.0) {
+            errors.entry(url).or_default().push(CompileError {
+                row: location.1,
+                column: location.2.saturating_sub(1),
+                error_message: format!(
+                    "Could not find artifact {} in the local repository",
+                    missing.coordinates
+                ),
+                ..Default::default()
+            });
+        }
+    }
+}
+
+fn generate_classpath_files(root: &Path, executable: &Path) {
+    if let Err(error) = Command::new(executable)
+        .current_dir(root)
+        .arg("--quiet")
+        .arg("--fail-at-end")
+        .arg("dependency:build-classpath")
+        // Relative paths are resolved against the directory of each module
+        .arg(format!("-Dmdep.outputFile={CLASSPATH_FILE}"))
+        .output()
+    {
+        eprintln!("Could not generate classpath with maven: {error}");
+    }
+}
+
+/// Build the classpath of a module, dependencies on other modules of the reactor are
+/// resolved to their output directory.
+fn classpath(pom: &Pom, poms: &[Pom]) -> Vec<PathBuf> {
+    let siblings = sibling_dependencies(pom, poms);
+    let mut classpath = vec![pom.output_directory()];
+    classpath.extend(siblings.iter().map(|sibling| sibling.output_directory()));
+
+    if let Ok(content) = fs::read_to_string(pom.directory().join(CLASSPATH_FILE)) {
+        classpath.extend(
+            split_paths(&content)
+                .into_iter()
+                .filter(|path| !siblings.iter().any(|sibling| is_artifact(sibling, path))),
+        );
+    }
+
+    classpath
+}
+
+/// Check whether a classpath entry generated by maven points to the module of `pom`
+fn is_artifact(pom: &Pom, path: &Path) -> bool {
+    path.starts_with(pom.directory())
+        || path.to_string_lossy().contains(&format!(
+            "/{}/{}/",
+            pom.group_id.replace('.', "/"),
+            pom.artifact_id
+        ))
+}
+
+/// Collect all modules of the reactor that `pom` depends on, directly or transitively
+fn sibling_dependencies<'a>(pom: &Pom, poms: &'a [Pom]) -> Vec<&'a Pom> {
+    let mut siblings: Vec<&Pom> = Vec::new();
+    let mut queue = pom.dependencies.iter().collect::<Vec<_>>();
+
+    while let Some(dependency) = queue.pop() {
+        if let Some(sibling) = poms.iter().find(|sibling| {
+            sibling.group_id == dependency.group_id && sibling.artifact_id == dependency.artifact_id
+        }) && sibling.path != pom.path
+            && !siblings.iter().any(|known| known.path == sibling.path)
+        {
+            siblings.push(sibling);
+            queue.extend(sibling.dependencies.iter());
+        }
+    }
+
+    siblings
+}
@@ -0,0 +1,507 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use roxmltree::{Document, Node};
+
+/// Position of an element inside a pom file
+#[derive(Debug, Clone)]
+pub struct Location {
+    pub path: PathBuf,
+    pub row: u32,
+    pub column: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Parent {
+    pub group_id: String,
+    pub artifact_id: String,
+    pub version: String,
+    pub relative_path: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Dependency {
+    pub group_id: String,
+    pub artifact_id: String,
+    pub version: Option<String>,
+    pub scope: Option<String>,
+    pub kind: Option<String>,
+    pub classifier: Option<String>,
+    pub optional: bool,
+    pub system_path: Option<String>,
+    pub exclusions: Vec<(String, String)>,
+    pub location: Option<Location>,
+}
+
+impl Dependency {
+    /// Identifier of the dependency that is used to match dependencies with each other, the
+    /// type and classifier tell apart artifacts of the same project like `foo` and `foo:tests`
+    pub fn key(&self) -> (String, String, String, String) {
+        (
+            self.group_id.clone(),
+            self.artifact_id.clone(),
+            self.kind().to_string(),
+            self.classifier.clone().unwrap_or_default(),
+        )
+    }
+
+    pub fn scope(&self) -> &str {
+        self.scope.as_deref().unwrap_or("compile")
+    }
+
+    pub fn kind(&self) -> &str {
+        self.kind.as_deref().unwrap_or("jar")
+    }
+
+    pub fn is_excluded_by(&self, exclusions: &[(String, String)]) -> bool {
+        exclusions.iter().any(|(group_id, artifact_id)| {
+            (group_id == "*" || *group_id == self.group_id)
+                && (artifact_id == "*" || *artifact_id == self.artifact_id)
+        })
+    }
+
+    /// Fill in everything that the dependency does not define itself from `managed`
+    fn apply_management(&mut self, managed: &Dependency) {
+        if self.version.is_none() {
+            self.version = managed.version.clone();
+        }
+        if self.scope.is_none() {
+            self.scope = managed.scope.clone();
+        }
+        if self.system_path.is_none() {
+            self.system_path = managed.system_path.clone();
+        }
+        for exclusion in &managed.exclusions {
+            if !self.exclusions.contains(exclusion) {
+                self.exclusions.push(exclusion.clone());
+            }
+        }
+    }
+
+    fn interpolate(&mut self, properties: &HashMap<String, String>) {
+        self.group_id = interpolate(&self.group_id, properties);
+        self.artifact_id = interpolate(&self.artifact_id, properties);
+        for value in [
+            &mut self.version,
+            &mut self.scope,
+            &mut self.kind,
+            &mut self.classifier,
+            &mut self.system_path,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *value = interpolate(value, properties);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Build {
+    pub source_directory: Option<String>,
+    pub test_source_directory: Option<String>,
+    pub output_directory: Option<String>,
+    /// Artifacts from the `annotationProcessorPaths` of the compiler plugin
+    pub annotation_processor_paths: Vec<Dependency>,
+}
+
+/// Project object model as it is written in a pom file.
+///
+/// Use [`Pom::inherit`] and [`Pom::interpolate`] to get the effective model.
+#[derive(Debug, Clone)]
+pub struct Pom {
+    pub path: PathBuf,
+    pub group_id: String,
+    pub artifact_id: String,
+    pub version: String,
+    pub parent: Option<Parent>,
+    pub properties: HashMap<String, String>,
+    pub dependency_management: Vec<Dependency>,
+    pub dependencies: Vec<Dependency>,
+    pub modules: Vec<String>,
+    pub build: Build,
+}
+
+impl Pom {
+    pub fn parse(path: &Path) -> Option<Self> {
+        let content = fs::read_to_string(path).ok()?;
+        let document = Document::parse(&content).ok()?;
+        let project = document.root_element();
+        let parent = child(project, "parent").map(|parent| Parent {
+            group_id: child_text(parent, "groupId")
+                .unwrap_or_default()
+                .to_string(),
+            artifact_id: child_text(parent, "artifactId")
+                .unwrap_or_default()
+                .to_string(),
+            version: child_text(parent, "version")
+                .unwrap_or_default()
+                .to_string(),
+            relative_path: child_text(parent, "relativePath")
+                .unwrap_or("../pom.xml")
+                .to_string(),
+        });
+        let build = child(project, "build");
+        let build_directory = |name: &str| {
+            build
+                .and_then(|build| child_text(build, name))
+                .map(str::to_string)
+        };
+
+        Some(Pom {
+            path: path.to_path_buf(),
+            group_id: child_text(project, "groupId")
+                .map(str::to_string)
+                .or_else(|| parent.as_ref().map(|parent| parent.group_id.clone()))
+                .unwrap_or_default(),
+            artifact_id: child_text(project, "artifactId")?.to_string(),
+            version: child_text(project, "version")
+                .map(str::to_string)
+                .or_else(|| parent.as_ref().map(|parent| parent.version.clone()))
+                .unwrap_or_default(),
+            properties: child(project, "properties")
+                .map(|properties| {
+                    properties
+                        .children()
+                        .filter(Node::is_element)
+                        .map(|property| {
+                            (
+                                property.tag_name().name().to_string(),
+                                property.text().unwrap_or_default().trim().to_string(),
+                            )
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            dependency_management: child(project, "dependencyManagement")
+                .and_then(|management| child(management, "dependencies"))
+                .map(|dependencies| parse_dependencies(&document, dependencies, path))
+                .unwrap_or_default(),
+            dependencies: child(project, "dependencies")
+                .map(|dependencies| parse_dependencies(&document, dependencies, path))
+                .unwrap_or_default(),
+            modules: child(project, "modules")
+                .map(|modules| {
+                    children(modules, "module")
+                        .filter_map(|module| module.text())
+                        .map(|module| module.trim().to_string())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            build: Build {
+                source_directory: build_directory("sourceDirectory"),
+                test_source_directory: build_directory("testSourceDirectory"),
+                output_directory: build_directory("outputDirectory"),
+                annotation_processor_paths: build
+                    .map(|build| parse_annotation_processor_paths(&document, build, path))
+                    .unwrap_or_default(),
+            },
+            parent,
+        })
+    }
+
+    pub fn directory(&self) -> &Path {
+        self.path.parent().unwrap_or(Path::new("."))
+    }
+
+    /// Inherit properties, dependencies and dependency management from the effective model
+    /// of the parent
+    pub fn inherit(&mut self, parent: &Pom) {
+        for (key, value) in &parent.properties {
+            self.properties
+                .entry(key.clone())
+                .or_insert_with(|| value.clone());
+        }
+
+        for managed in &parent.dependency_management {
+            if !self
+                .dependency_management
+                .iter()
+                .any(|dependency| dependency.key() == managed.key())
+            {
+                self.dependency_management.push(managed.clone());
+            }
+        }
+
+        for inherited in &parent.dependencies {
+            if !self
+                .dependencies
+                .iter()
+                .any(|dependency| dependency.key() == inherited.key())
+            {
+                self.dependencies.push(inherited.clone());
+            }
+        }
+
+        if self.build.annotation_processor_paths.is_empty() {
+            self.build.annotation_processor_paths = parent.build.annotation_processor_paths.clone();
+        }
+
+        self.properties.insert(
+            String::from("project.parent.groupId"),
+            parent.group_id.clone(),
+        );
+        self.properties.insert(
+            String::from("project.parent.version"),
+            parent.version.clone(),
+        );
+    }
+
+    /// Replace all `${...}` expressions with the properties of the model
+    pub fn interpolate(&mut self) {
+        let basedir = self.directory().display().to_string();
+        for (key, value) in [
+            ("project.groupId", self.group_id.clone()),
+            ("project.artifactId", self.artifact_id.clone()),
+            ("project.version", self.version.clone()),
+            ("project.basedir", basedir.clone()),
+            ("project.build.directory", format!("{basedir}/target")),
+            ("pom.groupId", self.group_id.clone()),
+            ("pom.version", self.version.clone()),
+            ("groupId", self.group_id.clone()),
+            ("version", self.version.clone()),
+            ("basedir", basedir.clone()),
+        ] {
+            self.properties.insert(key.to_string(), value);
+        }
+
+        let properties = self.properties.clone();
+        self.group_id = interpolate(&self.group_id, &properties);
+        self.version = interpolate(&self.version, &properties);
+        for dependency in self
+            .dependencies
+            .iter_mut()
+            .chain(self.dependency_management.iter_mut())
+            .chain(self.build.annotation_processor_paths.iter_mut())
+        {
+            dependency.interpolate(&properties);
+        }
+        for directory in [
+            &mut self.build.source_directory,
+            &mut self.build.test_source_directory,
+            &mut self.build.output_directory,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *directory = interpolate(directory, &properties);
+        }
+    }
+
+    /// Add the dependency management of an imported bill of materials
+    pub fn import(&mut self, bom: &Pom) {
+        for managed in &bom.dependency_management {
+            if !self
+                .dependency_management
+                .iter()
+                .any(|dependency| dependency.key() == managed.key())
+            {
+                self.dependency_management.push(managed.clone());
+            }
+        }
+    }
+
+    /// Dependency management entries that import a bill of materials
+    pub fn imports(&self) -> Vec<Dependency> {
+        self.dependency_management
+            .iter()
+            .filter(|dependency| dependency.scope() == "import" && dependency.kind() == "pom")
+            .cloned()
+            .collect()
+    }
+
+    /// Apply the dependency management to the direct dependencies and annotation processors
+    pub fn manage_dependencies(&mut self) {
+        let management = self.dependency_management.clone();
+        for dependency in self
+            .dependencies
+            .iter_mut()
+            .chain(self.build.annotation_processor_paths.iter_mut())
+        {
+            if let Some(managed) = management
+                .iter()
+                .find(|managed| managed.key() == dependency.key())
+            {
+                dependency.apply_management(managed);
+            }
+        }
+    }
+
+    pub fn managed(&self, dependency: &Dependency) -> Option<&Dependency> {
+        self.dependency_management
+            .iter()
+            .find(|managed| managed.key() == dependency.key() && managed.scope() != "import")
+    }
+
+    pub fn source_directories(&self) -> Vec<PathBuf> {
+        vec![
+            self.directory().join(
+                self.build
+                    .source_directory
+                    .as_deref()
+                    .unwrap_or("src/main/java"),
+            ),
+            self.directory().join(
+                self.build
+                    .test_source_directory
+                    .as_deref()
+                    .unwrap_or("src/test/java"),
+            ),
+        ]
+    }
+
+    pub fn output_directory(&self) -> PathBuf {
+        self.directory().join(
+            self.build
+                .output_directory
+                .as_deref()
+                .unwrap_or("target/classes"),
+        )
+    }
+
+    /// Directory that annotation processors generate sources into
+    pub fn generated_sources_directory(&self) -> PathBuf {
+        self.directory()
+            .join("target/generated-sources/annotations")
+    }
+
+    /// Directories of all modules that are listed in the pom
+    pub fn module_directories(&self) -> Vec<PathBuf> {
+        self.modules
+            .iter()
+            .map(|module| {
+                let path = self.directory().join(module);
+                // A module can either point to a directory or directly to a pom file
+                if path.is_file() {
+                    path.parent().map(Path::to_path_buf).unwrap_or(path)
+                } else {
+                    path
+                }
+            })
+            .collect()
+    }
+}
+
+/// Parse all `dependency` (or `path`) elements of `dependencies`
+fn parse_dependencies(document: &Document, dependencies: Node, path: &Path) -> Vec<Dependency> {
+    dependencies
+        .children()
+        .filter(Node::is_element)
+        .filter_map(|dependency| {
+            let position = document.text_pos_at(dependency.range().start);
+
+            Some(Dependency {
+                group_id: child_text(dependency, "groupId")?.to_string(),
+                artifact_id: child_text(dependency, "artifactId")?.to_string(),
+                version: child_text(dependency, "version").map(str::to_string),
+                scope: child_text(dependency, "scope").map(str::to_string),
+                kind: child_text(dependency, "type").map(str::to_string),
+                classifier: child_text(dependency, "classifier").map(str::to_string),
+                optional: child_text(dependency, "optional") == Some("true"),
+                system_path: child_text(dependency, "systemPath").map(str::to_string),
+                exclusions: child(dependency, "exclusions")
+                    .map(|exclusions| {
+                        children(exclusions, "exclusion")
+                            .filter_map(|exclusion| {
+                                Some((
+                                    child_text(exclusion, "groupId")?.to_string(),
+                                    child_text(exclusion, "artifactId")?.to_string(),
+                                ))
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                location: Some(Location {
+                    path: path.to_path_buf(),
+                    row: position.row,
+                    column: position.col,
+                }),
+            })
+        })
+        .collect()
+}
+
+/// Parse the `annotationProcessorPaths` of the compiler plugin from the plugins and the
+/// plugin management of `build`
+fn parse_annotation_processor_paths(
+    document: &Document,
+    build: Node,
+    path: &Path,
+) -> Vec<Dependency> {
+    let mut paths: Vec<Dependency> = Vec::new();
+    let plugins = [Some(build), child(build, "pluginManagement")]
+        .into_iter()
+        .flatten()
+        .filter_map(|node| child(node, "plugins"))
+        .flat_map(|plugins| children(plugins, "plugin"))
+        .filter(|plugin| child_text(*plugin, "artifactId") == Some("maven-compiler-plugin"));
+
+    for plugin in plugins {
+        if let Some(processor_paths) = child(plugin, "configuration")
+            .and_then(|configuration| child(configuration, "annotationProcessorPaths"))
+        {
+            for dependency in parse_dependencies(document, processor_paths, path) {
+                if !paths.iter().any(|known| known.key() == dependency.key()) {
+                    paths.push(dependency);
+                }
+            }
+        }
+    }
+
+    paths
+}
+
+/// Replace all `${...}` expressions in `value`, unknown properties are kept as they are
+fn interpolate(value: &str, properties: &HashMap<String, String>) -> String {
+    let mut result = value.to_string();
+
+    // Properties can reference other properties, limit the depth to not loop forever
+    for _ in 0..10 {
+        let mut interpolated = String::with_capacity(result.len());
+        let mut rest = result.as_str();
+        let mut changed = false;
+
+        while let Some(start) = rest.find("${")
+            && let Some(length) = rest[start..].find('}')
+        {
+            let end = start + length;
+            interpolated.push_str(&rest[..start]);
+            if let Some(value) = properties.get(&rest[start + 2..end]) {
+                interpolated.push_str(value);
+                changed = true;
+            } else {
+                interpolated.push_str(&rest[start..=end]);
+            }
+            rest = &rest[end + 1..];
+        }
+        interpolated.push_str(rest);
+        result = interpolated;
+
+        if !changed {
+            break;
+        }
+    }
+
+    result
+}
+
+pub fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    children(node, name).next()
+}
+
+pub fn children<'a, 'input>(
+    node: Node<'a, 'input>,
+    name: &str,
+) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children()
+        .filter(move |child| child.is_element() && child.tag_name().name() == name)
+}
+
+pub fn child_text<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    child(node, name)
+        .and_then(|child| child.text())
+        .map(str::trim)
+}
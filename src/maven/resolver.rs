@@ -0,0 +1,415 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use roxmltree::Document;
+
+use super::{
+    pom::{Dependency, Pom, child_text},
+    version,
+};
+
+/// Scopes of transitive dependencies that are not part of the classpath of the dependent
+const NON_TRANSITIVE_SCOPES: [&str; 4] = ["test", "provided", "system", "import"];
+/// Maximum number of parents a pom can have, protects against cycles
+const MAXIMUM_PARENT_DEPTH: usize = 32;
+
+/// Artifact that could not be found in the local repository
+pub struct MissingArtifact {
+    pub coordinates: String,
+    /// Dependency of the project that (transitively) requires the artifact
+    pub required_by: Dependency,
+}
+
+#[derive(Default)]
+pub struct Resolution {
+    pub classpath: Vec<PathBuf>,
+    pub missing: Vec<MissingArtifact>,
+}
+
+/// Dependency that is waiting to be resolved
+struct Pending {
+    dependency: Dependency,
+    exclusions: Vec<(String, String)>,
+    required_by: Dependency,
+}
+
+/// Resolves the dependencies of poms against the local maven repository without running maven
+pub struct Resolver {
+    repository: PathBuf,
+    reactor: Vec<Pom>,
+    cache: HashMap<(String, String, String), Option<Pom>>,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Resolver {
+            repository: local_repository(),
+            reactor: Vec::new(),
+            cache: HashMap::new(),
+        }
+    }
+
+    /// Make the effective models of the reactor known to the resolver, dependencies on them
+    /// are resolved to their output directory
+    pub fn set_reactor(&mut self, reactor: Vec<Pom>) {
+        self.reactor = reactor;
+    }
+
+    /// Build the effective model of the pom file at `path`
+    pub fn effective_pom(&mut self, path: &Path) -> Option<Pom> {
+        self.complete(Pom::parse(path)?, 0)
+    }
+
+    /// Resolve the dependencies of `pom` transitively
+    pub fn resolve(&mut self, pom: &Pom) -> Resolution {
+        self.resolve_all(pom, &pom.dependencies, false)
+    }
+
+    /// Resolve the annotation processors of `pom` transitively, they run with their runtime
+    /// dependencies
+    pub fn resolve_annotation_processors(&mut self, pom: &Pom) -> Resolution {
+        self.resolve_all(pom, &pom.build.annotation_processor_paths, true)
+    }
+
+    /// Resolve `dependencies` of `pom` transitively, runtime dependencies are only included
+    /// with `is_runtime`
+    fn resolve_all(
+        &mut self,
+        pom: &Pom,
+        dependencies: &[Dependency],
+        is_runtime: bool,
+    ) -> Resolution {
+        let mut resolution = Resolution::default();
+        let mut seen = HashSet::from([(
+            pom.group_id.clone(),
+            pom.artifact_id.clone(),
+            String::from("jar"),
+            String::new(),
+        )]);
+        let mut queue = dependencies
+            .iter()
+            .filter(|dependency| dependency.scope() != "import")
+            .filter(|dependency| is_runtime || dependency.scope() != "runtime")
+            .map(|dependency| Pending {
+                dependency: dependency.clone(),
+                exclusions: dependency.exclusions.clone(),
+                required_by: dependency.clone(),
+            })
+            .collect::<VecDeque<Pending>>();
+
+        // Breadth first, so that the nearest definition of a dependency wins
+        while let Some(pending) = queue.pop_front() {
+            let dependency = &pending.dependency;
+            if !seen.insert(dependency.key()) {
+                continue;
+            }
+
+            let transitive = if let Some(sibling) = self.reactor.iter().find(|sibling| {
+                sibling.group_id == dependency.group_id
+                    && sibling.artifact_id == dependency.artifact_id
+            }) {
+                resolution.classpath.push(sibling.output_directory());
+                sibling.dependencies.clone()
+            } else {
+                let Some(version) = dependency
+                    .version
+                    .as_deref()
+                    .and_then(|version| self.resolve_version(dependency, version))
+                else {
+                    resolution.missing.push(MissingArtifact {
+                        coordinates: format!(
+                            "{}:{}:{}",
+                            dependency.group_id,
+                            dependency.artifact_id,
+                            dependency.version.as_deref().unwrap_or("?")
+                        ),
+                        required_by: pending.required_by.clone(),
+                    });
+                    continue;
+                };
+
+                if let Some(artifact) = self.artifact(dependency, &version) {
+                    if artifact.exists() {
+                        resolution.classpath.push(artifact);
+                    } else {
+                        resolution.missing.push(MissingArtifact {
+                            coordinates: format!(
+                                "{}:{}:{version}",
+                                dependency.group_id, dependency.artifact_id
+                            ),
+                            required_by: pending.required_by.clone(),
+                        });
+                    }
+                }
+
+                self.repository_pom(&dependency.group_id, &dependency.artifact_id, &version)
+                    .map(|pom| pom.dependencies)
+                    .unwrap_or_default()
+            };
+
+            for mut transitive in transitive {
+                if transitive.optional
+                    || NON_TRANSITIVE_SCOPES.contains(&transitive.scope())
+                    || transitive.is_excluded_by(&pending.exclusions)
+                {
+                    continue;
+                }
+
+                // The dependency management of the project wins over the one of the dependencies
+                if let Some(managed) = pom.managed(&transitive) {
+                    if managed.version.is_some() {
+                        transitive.version = managed.version.clone();
+                    }
+                    if managed.scope.is_some() {
+                        transitive.scope = managed.scope.clone();
+                    }
+                }
+
+                // Runtime dependencies are not needed to compile, dependencies that are only
+                // reachable through them are runtime dependencies as well
+                let scope = transitive_scope(dependency.scope(), transitive.scope());
+                if scope == "runtime" && !is_runtime {
+                    continue;
+                }
+                transitive.scope = Some(scope.to_string());
+
+                let mut exclusions = pending.exclusions.clone();
+                exclusions.extend(transitive.exclusions.iter().cloned());
+                queue.push_back(Pending {
+                    dependency: transitive,
+                    exclusions,
+                    required_by: pending.required_by.clone(),
+                });
+            }
+        }
+
+        resolution
+    }
+
+    /// Apply parent, interpolation and imports to a pom as it is written on disk
+    fn complete(&mut self, mut pom: Pom, depth: usize) -> Option<Pom> {
+        if depth > MAXIMUM_PARENT_DEPTH {
+            return None;
+        }
+
+        if let Some(parent) = pom.parent.clone() {
+            let relative = pom.directory().join(&parent.relative_path);
+            let relative = if relative.is_dir() {
+                relative.join("pom.xml")
+            } else {
+                relative
+            };
+
+            let parent_pom = match Pom::parse(&relative) {
+                Some(candidate) if candidate.artifact_id == parent.artifact_id => {
+                    self.complete(candidate, depth + 1)
+                }
+                _ => self.repository_pom(&parent.group_id, &parent.artifact_id, &parent.version),
+            };
+
+            if let Some(parent_pom) = parent_pom {
+                pom.inherit(&parent_pom);
+            }
+        }
+
+        pom.interpolate();
+
+        for import in pom.imports() {
+            if let Some(version) = &import.version
+                && let Some(bom) =
+                    self.repository_pom(&import.group_id, &import.artifact_id, version)
+            {
+                pom.import(&bom);
+            }
+        }
+
+        pom.manage_dependencies();
+
+        Some(pom)
+    }
+
+    /// Effective model of a pom from the reactor or the local repository
+    fn repository_pom(&mut self, group_id: &str, artifact_id: &str, version: &str) -> Option<Pom> {
+        if let Some(pom) = self
+            .reactor
+            .iter()
+            .find(|pom| pom.group_id == group_id && pom.artifact_id == artifact_id)
+        {
+            return Some(pom.clone());
+        }
+
+        let key = (
+            group_id.to_string(),
+            artifact_id.to_string(),
+            version.to_string(),
+        );
+        if let Some(pom) = self.cache.get(&key) {
+            return pom.clone();
+        }
+
+        // Insert a placeholder first so that cyclic parents / imports terminate
+        self.cache.insert(key.clone(), None);
+        let path = self
+            .artifact_directory(group_id, artifact_id)
+            .join(version)
+            .join(format!("{artifact_id}-{version}.pom"));
+        let pom = Pom::parse(&path).and_then(|pom| self.complete(pom, 0));
+        self.cache.insert(key, pom.clone());
+
+        pom
+    }
+
+    /// Path of the file that has to be added to the classpath for `dependency`
+    fn artifact(&self, dependency: &Dependency, version: &str) -> Option<PathBuf> {
+        if dependency.scope() == "system" {
+            return dependency.system_path.as_ref().map(PathBuf::from);
+        }
+
+        let classifier = match (dependency.kind(), &dependency.classifier) {
+            (_, Some(classifier)) => format!("-{classifier}"),
+            ("test-jar", None) => String::from("-tests"),
+            ("pom", None) => return None,
+            _ => String::new(),
+        };
+
+        Some(
+            self.artifact_directory(&dependency.group_id, &dependency.artifact_id)
+                .join(version)
+                .join(format!(
+                    "{}-{version}{classifier}.jar",
+                    dependency.artifact_id
+                )),
+        )
+    }
+
+    fn artifact_directory(&self, group_id: &str, artifact_id: &str) -> PathBuf {
+        self.repository
+            .join(group_id.replace('.', "/"))
+            .join(artifact_id)
+    }
+
+    /// Resolve version ranges to the highest version within the range that is available locally
+    fn resolve_version(&self, dependency: &Dependency, version: &str) -> Option<String> {
+        if version.contains("${") {
+            return None;
+        }
+
+        if !version.starts_with(['[', '(']) {
+            return Some(version.to_string());
+        }
+
+        fs::read_dir(self.artifact_directory(&dependency.group_id, &dependency.artifact_id))
+            .ok()?
+            .filter_map(Result::ok)
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .filter(|candidate| version::in_range(candidate, version))
+            .max_by(|a, b| version::compare(a, b))
+    }
+}
+
+/// Scope of a transitive dependency with `scope` of a dependency with the scope `parent`,
+/// dependencies of compile dependencies keep their scope and the others take the one of the
+/// parent
+fn transitive_scope<'a>(parent: &'a str, scope: &'a str) -> &'a str {
+    if parent == "compile" { scope } else { parent }
+}
+
+/// Location of the local repository, either from the user settings or the default location
+fn local_repository() -> PathBuf {
+    let maven_directory = PathBuf::from(env::var("HOME").unwrap_or_default()).join(".m2");
+
+    fs::read_to_string(maven_directory.join("settings.xml"))
+        .ok()
+        .and_then(|content| {
+            let document = Document::parse(&content).ok()?;
+            child_text(document.root_element(), "localRepository").map(PathBuf::from)
+        })
+        .unwrap_or(maven_directory.join("repository"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, env, fs, path::Path, process};
+
+    use super::Resolver;
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    fn dependency(artifact_id: &str, extra: &str) -> String {
+        format!(
+            "<dependency><groupId>demo</groupId><artifactId>{artifact_id}</artifactId>\
+             <version>1</version>{extra}</dependency>"
+        )
+    }
+
+    #[test]
+    fn compile_classpath_follows_the_scopes_of_maven() {
+        let root = env::temp_dir().join(format!("java-lsp-{}-resolver", process::id()));
+        let repository = root.join("repository");
+        let artifact = |artifact_id: &str, file: &str| {
+            repository.join(format!("demo/{artifact_id}/1/{artifact_id}-1{file}"))
+        };
+
+        // `a` depends on `b` at runtime, and on `c` and `d` to compile
+        write(
+            &artifact("a", ".pom"),
+            &format!(
+                "<project><groupId>demo</groupId><artifactId>a</artifactId><version>1</version>\
+                 <dependencies>{}{}{}</dependencies></project>",
+                dependency("b", "<scope>runtime</scope>"),
+                dependency("c", ""),
+                dependency("d", ""),
+            ),
+        );
+        for artifact_id in ["a", "b", "c", "d", "e"] {
+            write(&artifact(artifact_id, ".jar"), "");
+        }
+        write(&artifact("e", "-tests.jar"), "");
+
+        // The project manages `d` to be a runtime dependency and uses `e` and its tests
+        write(
+            &root.join("project/pom.xml"),
+            &format!(
+                "<project><groupId>demo</groupId><artifactId>project</artifactId>\
+                 <version>1</version>\
+                 <dependencyManagement><dependencies>{}</dependencies></dependencyManagement>\
+                 <dependencies>{}{}{}{}</dependencies></project>",
+                dependency("d", "<scope>runtime</scope>"),
+                dependency("a", ""),
+                dependency("e", ""),
+                dependency("e", "<classifier>tests</classifier><scope>test</scope>"),
+                dependency("f", "<scope>runtime</scope>"),
+            ),
+        );
+
+        let mut resolver = Resolver {
+            repository: repository.clone(),
+            reactor: Vec::new(),
+            cache: HashMap::new(),
+        };
+        let pom = resolver
+            .effective_pom(&root.join("project/pom.xml"))
+            .unwrap();
+        let resolution = resolver.resolve(&pom);
+
+        assert_eq!(
+            resolution.classpath,
+            [
+                artifact("a", ".jar"),
+                artifact("e", ".jar"),
+                artifact("e", "-tests.jar"),
+                artifact("c", ".jar"),
+            ]
+        );
+        assert!(resolution.missing.is_empty());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
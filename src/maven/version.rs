@@ -0,0 +1,255 @@
+use std::cmp::Ordering;
+
+/// Qualifiers in the order of Maven, the empty qualifier is a release
+const QUALIFIERS: [&str; 7] = ["alpha", "beta", "milestone", "rc", "snapshot", "", "sp"];
+
+/// Part of a version, a `-` or a change between digits and letters starts a nested list
+enum Item {
+    Number(u64),
+    Qualifier(String),
+    List(Vec<Item>),
+}
+
+/// Compare versions like Maven does, numbers are compared numerically and qualifiers by
+/// their meaning, e.g. `1.0-alpha` < `1.0-rc1` < `1.0-SNAPSHOT` < `1.0` < `1.0-sp`
+pub fn compare(a: &str, b: &str) -> Ordering {
+    compare_lists(&parse(a), &parse(b))
+}
+
+/// Whether `version` is within `range`, e.g. `[1.0,2.0)` or `(,1.0],[1.2,)`
+pub fn in_range(version: &str, range: &str) -> bool {
+    let mut rest = range.trim();
+    while let Some(end) = rest.find([']', ')']) {
+        let (bounds, remainder) = rest.split_at(end + 1);
+        rest = remainder.trim_start_matches([',', ' ']);
+
+        let is_lower_inclusive = bounds.starts_with('[');
+        let is_upper_inclusive = bounds.ends_with(']');
+        let bounds = &bounds[1..bounds.len() - 1];
+        let matches = match bounds.split_once(',') {
+            Some((lower, upper)) => {
+                let lower = lower.trim();
+                let upper = upper.trim();
+                (lower.is_empty()
+                    || match compare(version, lower) {
+                        Ordering::Greater => true,
+                        Ordering::Equal => is_lower_inclusive,
+                        Ordering::Less => false,
+                    })
+                    && (upper.is_empty()
+                        || match compare(version, upper) {
+                            Ordering::Less => true,
+                            Ordering::Equal => is_upper_inclusive,
+                            Ordering::Greater => false,
+                        })
+            }
+            None => compare(version, bounds.trim()) == Ordering::Equal,
+        };
+        if matches {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Items of `version` like Maven parses them
+fn parse(version: &str) -> Vec<Item> {
+    /// Add `item` to the innermost list, `lists` go from the outermost to the innermost one
+    fn push(lists: &mut [Vec<Item>], item: Item) {
+        if let Some(list) = lists.last_mut() {
+            list.push(item);
+        }
+    }
+
+    fn item(segment: &str, is_followed_by_digit: bool) -> Item {
+        match segment.parse::<u64>() {
+            Ok(number) => Item::Number(number),
+            Err(_) => Item::Qualifier(qualifier(segment, is_followed_by_digit)),
+        }
+    }
+
+    let version = version.to_lowercase();
+    let mut lists = vec![Vec::new()];
+    let mut start = 0;
+    let mut was_digit = false;
+    for (i, c) in version.char_indices() {
+        match c {
+            '.' | '-' => {
+                if start == i {
+                    push(&mut lists, Item::Number(0));
+                } else {
+                    push(&mut lists, item(&version[start..i], false));
+                }
+                start = i + 1;
+                if c == '-' {
+                    lists.push(Vec::new());
+                }
+            }
+            _ if c.is_ascii_digit() => {
+                if !was_digit && i > start {
+                    push(&mut lists, item(&version[start..i], true));
+                    start = i;
+                    lists.push(Vec::new());
+                }
+                was_digit = true;
+                continue;
+            }
+            _ => {
+                if was_digit && i > start {
+                    push(&mut lists, item(&version[start..i], false));
+                    start = i;
+                    lists.push(Vec::new());
+                }
+            }
+        }
+        was_digit = false;
+    }
+    if start < version.len() {
+        push(&mut lists, item(&version[start..], false));
+    }
+
+    // Nest the lists into each other, zeros and release qualifiers at the end of a list or
+    // before its nested list are irrelevant
+    let mut list = Vec::new();
+    while let Some(mut outer) = lists.pop() {
+        if !list.is_empty() {
+            outer.push(Item::List(list));
+        }
+        let mut i = outer.len();
+        while i > 0 {
+            i -= 1;
+            if is_null(&outer[i]) {
+                outer.remove(i);
+            } else if !matches!(outer[i], Item::List(_)) {
+                break;
+            }
+        }
+        list = outer;
+    }
+    list
+}
+
+/// Qualifier with its aliases replaced, `a1` is `alpha-1` but `a` alone is unknown
+fn qualifier(segment: &str, is_followed_by_digit: bool) -> String {
+    match segment {
+        "a" if is_followed_by_digit => "alpha",
+        "b" if is_followed_by_digit => "beta",
+        "m" if is_followed_by_digit => "milestone",
+        "cr" => "rc",
+        "ga" | "final" | "release" => "",
+        segment => segment,
+    }
+    .to_string()
+}
+
+fn is_null(item: &Item) -> bool {
+    match item {
+        Item::Number(number) => *number == 0,
+        Item::Qualifier(qualifier) => qualifier.is_empty(),
+        Item::List(items) => items.is_empty(),
+    }
+}
+
+/// Known qualifiers by their order, unknown ones come after them in lexical order
+fn qualifier_rank(qualifier: &str) -> (usize, &str) {
+    match QUALIFIERS.iter().position(|known| *known == qualifier) {
+        Some(index) => (index, ""),
+        None => (QUALIFIERS.len(), qualifier),
+    }
+}
+
+/// Compare `item` with `other`, a missing item is a zero or a release
+fn compare_items(item: &Item, other: Option<&Item>) -> Ordering {
+    match (item, other) {
+        (Item::Number(number), None) => number.cmp(&0),
+        (Item::Number(number), Some(Item::Number(other))) => number.cmp(other),
+        (Item::Number(_), Some(_)) => Ordering::Greater,
+        (Item::Qualifier(qualifier), None) => qualifier_rank(qualifier).cmp(&qualifier_rank("")),
+        (Item::Qualifier(qualifier), Some(Item::Qualifier(other))) => {
+            qualifier_rank(qualifier).cmp(&qualifier_rank(other))
+        }
+        (Item::Qualifier(_), Some(Item::Number(_) | Item::List(_))) => Ordering::Less,
+        (Item::List(items), None) => items
+            .first()
+            .map_or(Ordering::Equal, |first| compare_items(first, None)),
+        (Item::List(items), Some(Item::List(other))) => compare_lists(items, other),
+        (Item::List(_), Some(Item::Number(_))) => Ordering::Less,
+        (Item::List(_), Some(Item::Qualifier(_))) => Ordering::Greater,
+    }
+}
+
+fn compare_lists(items: &[Item], other: &[Item]) -> Ordering {
+    (0..items.len().max(other.len()))
+        .map(|i| match (items.get(i), other.get(i)) {
+            (Some(item), other) => compare_items(item, other),
+            (None, Some(other)) => compare_items(other, None).reverse(),
+            (None, None) => Ordering::Equal,
+        })
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use super::{compare, in_range};
+
+    #[test]
+    fn versions_are_ordered_like_maven() {
+        let ascending = [
+            "1-alpha",
+            "1-alpha-2",
+            "1-beta",
+            "1-milestone",
+            "1-rc",
+            "1-snapshot",
+            "1",
+            "1-sp",
+            "1.0.1",
+            "1.1",
+            "1.10",
+        ];
+        for (i, a) in ascending.iter().enumerate() {
+            for (j, b) in ascending.iter().enumerate() {
+                assert_eq!(compare(a, b), i.cmp(&j), "{a} and {b}");
+            }
+        }
+
+        let equal = [
+            ("1", "1.0.0"),
+            ("1-ga", "1"),
+            ("1-final", "1.0"),
+            ("1-RC", "1-cr"),
+            ("1-a1", "1-alpha-1"),
+            ("1-SNAPSHOT", "1-snapshot"),
+        ];
+        for (a, b) in equal {
+            assert_eq!(compare(a, b), Ordering::Equal, "{a} and {b}");
+        }
+    }
+
+    #[test]
+    fn ranges_include_their_bounds_like_maven() {
+        let cases = [
+            ("[1.0,2.0)", "0.9", false),
+            ("[1.0,2.0)", "1.0", true),
+            ("[1.0,2.0)", "1", true),
+            ("[1.0,2.0)", "1.5", true),
+            ("[1.0,2.0)", "2.0-rc", true),
+            ("[1.0,2.0)", "2.0", false),
+            ("(1.0,2.0]", "1.0", false),
+            ("(1.0,2.0]", "2.0", true),
+            ("[1.0,)", "99", true),
+            ("(,1.0]", "1.0", true),
+            ("(,1.0],[1.2,)", "1.1", false),
+            ("(,1.0],[1.2,)", "1.2", true),
+            ("[1.5]", "1.5", true),
+            ("[1.5]", "1.5.1", false),
+        ];
+        for (range, version, expected) in cases {
+            assert_eq!(in_range(version, range), expected, "{version} in {range}");
+        }
+    }
+}
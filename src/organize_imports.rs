@@ -0,0 +1,388 @@
+use std::collections::{BTreeMap, HashSet};
+
+use tree_sitter::Node;
+
+use crate::{
+    configuration::{ImportOrder, StaticImports},
+    formatter::Replacement,
+    imports::Scope,
+    symbols::TypeIndex,
+    tree_sitter::{Import, collect_imports, import},
+};
+
+/// Javadoc tags that reference types
+const JAVADOC_REFERENCES: [&str; 5] = ["@link", "@linkplain", "@see", "@throws", "@exception"];
+
+/// Declarations of `imports`, grouped and sorted as configured by `order`. The groups are
+/// separated by blank lines.
+pub fn import_block(imports: &[Import], order: &ImportOrder) -> String {
+    // The groups of the configuration, imports without a group and static imports
+    let mut groups = vec![Vec::new(); order.groups.len() + 2];
+    for import in imports {
+        let group = if import.is_static {
+            groups.len() - 1
+        } else {
+            group(&import.path, &order.groups)
+        };
+        groups[group].push(import);
+    }
+    if let Some(statics) = groups.pop()
+        && !statics.is_empty()
+    {
+        match order.static_imports {
+            StaticImports::First => groups.insert(0, statics),
+            StaticImports::Last => groups.push(statics),
+        }
+    }
+
+    groups
+        .into_iter()
+        .filter(|group| !group.is_empty())
+        .map(|mut group| {
+            group.sort_by(|a, b| a.path.cmp(&b.path).then(a.on_demand.cmp(&b.on_demand)));
+            let mut declarations = group.into_iter().map(declaration).collect::<Vec<String>>();
+            declarations.dedup();
+            declarations.join("\n")
+        })
+        .collect::<Vec<String>>()
+        .join("\n\n")
+}
+
+/// Index of the group with the longest prefix that matches `path`
+fn group(path: &str, groups: &[String]) -> usize {
+    groups
+        .iter()
+        .enumerate()
+        .filter(|(_, prefix)| {
+            path == prefix.as_str()
+                || path
+                    .strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| rest.starts_with('.'))
+        })
+        .max_by_key(|(_, prefix)| prefix.len())
+        .map(|(i, _)| i)
+        .or_else(|| groups.iter().position(|prefix| prefix == "*"))
+        .unwrap_or(groups.len())
+}
+
+fn declaration(import: &Import) -> String {
+    format!(
+        "import {}{}{};",
+        if import.is_static { "static " } else { "" },
+        import.path,
+        if import.on_demand { ".*" } else { "" }
+    )
+}
+
+/// Replace the imports of the compilation unit below `root` by the ones that are used. Types
+/// that are not imported are added if exactly one type of the index has their name.
+pub fn organize(
+    root: Node,
+    source: &str,
+    order: &ImportOrder,
+    index: &impl TypeIndex,
+) -> Option<Replacement> {
+    let mut references = References::default();
+    references.collect(root, source.as_bytes());
+
+    let mut imports = collect_imports(root, source.as_bytes())
+        .into_iter()
+        .filter(|import| is_used(import, &references.names, index))
+        .collect::<Vec<Import>>();
+
+    let scope = Scope::new(root, source.as_bytes());
+    let mut unresolved = references
+        .types
+        .iter()
+        .filter(|name| !references.type_parameters.contains(*name))
+        .filter(|name| scope.resolve_type(name, index).is_none())
+        .collect::<Vec<&String>>();
+    unresolved.sort();
+    unresolved.dedup();
+    for name in unresolved {
+        if let [fqdn] = index.find_types(name).as_slice() {
+            imports.push(Import {
+                path: fqdn.clone(),
+                is_static: false,
+                on_demand: false,
+            });
+        }
+    }
+
+    replace_imports(root, source, &imports, order)
+}
+
+/// Declarations of the types `fqdns` that are not imported yet, each one is inserted at its
+/// sorted position among the imports of the compilation unit below `root`
+pub fn add_imports(
+    root: Node,
+    source: &str,
+    fqdns: &[&str],
+    order: &ImportOrder,
+) -> Vec<Replacement> {
+    let mut cursor = root.walk();
+    let imports = root
+        .children(&mut cursor)
+        .filter(|node| node.kind() == "import_declaration")
+        .filter_map(|node| Some((node, import(node, source.as_bytes())?)))
+        .collect::<Vec<(Node, Import)>>();
+    let missing = fqdns
+        .iter()
+        .filter(|fqdn| {
+            !imports
+                .iter()
+                .any(|(_, import)| !import.is_static && !import.on_demand && import.path == **fqdn)
+        })
+        .map(|fqdn| Import {
+            path: fqdn.to_string(),
+            is_static: false,
+            on_demand: false,
+        })
+        .collect::<Vec<Import>>();
+    if imports.is_empty() {
+        return replace_imports(root, source, &missing, order)
+            .into_iter()
+            .collect();
+    }
+
+    // Imports go before the first import of their group that is sorted after them, or after
+    // the last one. Without imports of their group the neighbouring groups are used.
+    let mut insertions = BTreeMap::<(usize, bool), Vec<Import>>::new();
+    for import in missing {
+        let key = sort_key(&import, order);
+        let mut candidates = (0..imports.len())
+            .filter(|&i| sort_key(&imports[i].1, order).0 == key.0)
+            .collect::<Vec<usize>>();
+        if candidates.is_empty() {
+            candidates = (0..imports.len()).collect();
+        }
+        let next = candidates
+            .iter()
+            .copied()
+            .filter(|&i| sort_key(&imports[i].1, order) > key)
+            .min_by_key(|&i| sort_key(&imports[i].1, order));
+        let anchor = match next {
+            Some(next) => (next, false),
+            None => match candidates
+                .into_iter()
+                .max_by_key(|&i| sort_key(&imports[i].1, order))
+            {
+                Some(last) => (last, true),
+                None => continue,
+            },
+        };
+        insertions.entry(anchor).or_default().push(import);
+    }
+
+    // The block of the anchor with the new imports has the separators of their groups
+    insertions
+        .into_iter()
+        .filter_map(|((i, is_after), mut block)| {
+            let (node, anchor) = &imports[i];
+            let anchor = Import {
+                path: anchor.path.clone(),
+                is_static: anchor.is_static,
+                on_demand: anchor.on_demand,
+            };
+            let anchor_declaration = declaration(&anchor);
+            block.push(anchor);
+            let block = import_block(&block, order);
+
+            Some(if is_after {
+                Replacement {
+                    start: node.end_byte(),
+                    end: node.end_byte(),
+                    text: block.strip_prefix(&anchor_declaration)?.to_string(),
+                }
+            } else {
+                Replacement {
+                    start: node.start_byte(),
+                    end: node.start_byte(),
+                    text: block.strip_suffix(&anchor_declaration)?.to_string(),
+                }
+            })
+        })
+        .collect()
+}
+
+/// Order of `import` in [`import_block`], the group comes before the path
+fn sort_key<'a>(import: &'a Import, order: &ImportOrder) -> (usize, &'a str, bool) {
+    let group = match (import.is_static, &order.static_imports) {
+        (true, StaticImports::First) => 0,
+        (true, StaticImports::Last) => order.groups.len() + 1,
+        (false, StaticImports::First) => group(&import.path, &order.groups) + 1,
+        (false, StaticImports::Last) => group(&import.path, &order.groups),
+    };
+
+    (group, &import.path, import.on_demand)
+}
+
+/// Replace the import declarations by `imports`. `None` if they do not change or have
+/// comments between them.
+fn replace_imports(
+    root: Node,
+    source: &str,
+    imports: &[Import],
+    order: &ImportOrder,
+) -> Option<Replacement> {
+    let mut cursor = root.walk();
+    let children = root.children(&mut cursor).collect::<Vec<Node>>();
+    let first = children
+        .iter()
+        .position(|child| child.kind() == "import_declaration");
+    let last = children
+        .iter()
+        .rposition(|child| child.kind() == "import_declaration");
+    if let (Some(first), Some(last)) = (first, last)
+        && children[first..=last]
+            .iter()
+            .any(|child| child.kind() != "import_declaration" || child.has_error())
+    {
+        return None;
+    }
+
+    let block = import_block(imports, order);
+    let replacement = match (first, last) {
+        (Some(first), Some(last)) if block.is_empty() => {
+            // The blank lines after the imports are removed together with them
+            let end = children
+                .get(last + 1)
+                .map(|next| next.start_byte())
+                .unwrap_or(source.len());
+            Replacement {
+                start: children[first].start_byte(),
+                end,
+                text: String::new(),
+            }
+        }
+        (Some(first), Some(last)) => Replacement {
+            start: children[first].start_byte(),
+            end: children[last].end_byte(),
+            text: block,
+        },
+        _ if block.is_empty() => return None,
+        _ => match children
+            .iter()
+            .find(|child| child.kind() == "package_declaration")
+        {
+            Some(package) => Replacement {
+                start: package.end_byte(),
+                end: package.end_byte(),
+                text: format!("\n\n{block}"),
+            },
+            None => {
+                // Imports are placed after leading comments like license headers
+                let start = children
+                    .iter()
+                    .find(|child| !matches!(child.kind(), "line_comment" | "block_comment"))
+                    .map(|child| child.start_byte())
+                    .unwrap_or(source.len());
+                Replacement {
+                    start,
+                    end: start,
+                    text: format!("{block}\n\n"),
+                }
+            }
+        },
+    };
+
+    (source[replacement.start..replacement.end] != replacement.text).then_some(replacement)
+}
+
+/// Whether a name that is referenced in the compilation unit is imported by `import`
+fn is_used(import: &Import, names: &HashSet<String>, index: &impl TypeIndex) -> bool {
+    if !import.on_demand {
+        let name = import.path.rsplit('.').next().unwrap_or_default();
+        return names.contains(name);
+    }
+
+    names.iter().any(|name| {
+        let qualified = format!("{}.{name}", import.path);
+        index.has_type(&qualified) || (import.is_static && index.has_member(&import.path, name))
+    })
+}
+
+/// Names that are referenced outside of the package and import declarations
+#[derive(Default)]
+struct References {
+    /// All identifiers, including those in Javadoc references
+    names: HashSet<String>,
+    /// Simple names that are used as types and might have to be imported
+    types: Vec<String>,
+    /// Type parameters of all classes and methods, they are never imported
+    type_parameters: HashSet<String>,
+}
+
+impl References {
+    fn collect(&mut self, node: Node, source: &[u8]) {
+        let text = || node.utf8_text(source).unwrap_or_default().to_string();
+
+        match node.kind() {
+            "package_declaration" | "import_declaration" => return,
+            "identifier" => {
+                // Objects of method invocations like `Collections.emptyList()` are usually types
+                let is_object = node.parent().is_some_and(|parent| {
+                    matches!(parent.kind(), "method_invocation" | "field_access")
+                        && parent.child_by_field_name("object") == Some(node)
+                });
+                let name = text();
+                if is_object && is_type_name(&name) {
+                    self.types.push(name.clone());
+                }
+                self.names.insert(name);
+            }
+            "type_identifier" => {
+                // Only the first segment of a qualified name like `Outer.Inner` is imported
+                let is_qualified = node.parent().is_some_and(|parent| {
+                    parent.kind() == "scoped_type_identifier" && parent.named_child(0) != Some(node)
+                });
+                let name = text();
+                if !is_qualified && name != "var" {
+                    self.types.push(name.clone());
+                }
+                if node
+                    .parent()
+                    .is_some_and(|parent| parent.kind() == "type_parameter")
+                {
+                    self.type_parameters.insert(name.clone());
+                }
+                self.names.insert(name);
+            }
+            "block_comment" => {
+                self.names.extend(javadoc_references(&text()));
+                return;
+            }
+            _ => {}
+        }
+
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            self.collect(child, source);
+        }
+    }
+}
+
+/// First segments of the names that Javadoc tags like `{@link List#size()}` reference
+fn javadoc_references(comment: &str) -> Vec<String> {
+    let words = comment
+        .split(|c: char| c.is_whitespace() || c == '{' || c == '}')
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<&str>>();
+
+    words
+        .windows(2)
+        .filter(|pair| JAVADOC_REFERENCES.contains(&pair[0]))
+        .filter_map(|pair| {
+            pair[1]
+                .split(['.', '#', '(', '<'])
+                .next()
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+        })
+        .collect()
+}
+
+/// Whether `name` follows the convention for names of types, constants like `MAX` do not
+pub fn is_type_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_uppercase()) && name.chars().any(|c| c.is_lowercase())
+}
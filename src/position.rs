@@ -0,0 +1,111 @@
+use lsp_types::{ClientCapabilities, Position, PositionEncodingKind};
+use ropey::Rope;
+
+use crate::errors::DocumentError;
+
+/// Unit in which the `character` of a [`Position`] is counted, negotiated with the client
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum PositionEncoding {
+    Utf8,
+    #[default]
+    Utf16,
+    Utf32,
+}
+
+impl PositionEncoding {
+    /// Pick the first encoding the client prefers that is supported, UTF-16 is the default
+    /// of the protocol and is used if the client does not announce any encodings
+    pub fn negotiate(capabilities: &ClientCapabilities) -> Self {
+        capabilities
+            .general
+            .as_ref()
+            .and_then(|general| general.position_encodings.as_ref())
+            .and_then(|encodings| {
+                encodings
+                    .iter()
+                    .find_map(|encoding| match encoding.as_str() {
+                        "utf-8" => Some(PositionEncoding::Utf8),
+                        "utf-16" => Some(PositionEncoding::Utf16),
+                        "utf-32" => Some(PositionEncoding::Utf32),
+                        _ => None,
+                    })
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn kind(self) -> PositionEncodingKind {
+        match self {
+            PositionEncoding::Utf8 => PositionEncodingKind::UTF8,
+            PositionEncoding::Utf16 => PositionEncodingKind::UTF16,
+            PositionEncoding::Utf32 => PositionEncodingKind::UTF32,
+        }
+    }
+
+    /// Number of code units of `c` in this encoding
+    fn units(self, c: char) -> usize {
+        match self {
+            PositionEncoding::Utf8 => c.len_utf8(),
+            PositionEncoding::Utf16 => c.len_utf16(),
+            PositionEncoding::Utf32 => 1,
+        }
+    }
+
+    /// Number of code units of `text` in this encoding
+    pub fn len(self, text: &str) -> usize {
+        text.chars().map(|c| self.units(c)).sum()
+    }
+
+    /// Byte offset of the `character`th code unit of `line`, `None` if `line` is shorter or
+    /// the offset falls into the middle of a character
+    pub fn byte_offset(self, line: &str, character: u32) -> Option<usize> {
+        let character = character as usize;
+        let mut units = 0;
+
+        for (offset, c) in line.char_indices() {
+            if units == character {
+                return Some(offset);
+            }
+            if units > character {
+                return None;
+            }
+            units += self.units(c);
+        }
+
+        (units == character).then_some(line.len())
+    }
+}
+
+/// Byte index of `position` in `content`
+pub fn byte_index(
+    content: &Rope,
+    position: Position,
+    encoding: PositionEncoding,
+) -> Result<usize, DocumentError> {
+    let invalid = || DocumentError::InvalidPosition(position.line, position.character);
+    let line = position.line as usize;
+    if line >= content.len_lines() {
+        return Err(invalid());
+    }
+
+    let text = content.line(line).to_string();
+    let offset = encoding
+        .byte_offset(line_content(&text), position.character)
+        .ok_or_else(invalid)?;
+
+    Ok(content.line_to_byte(line) + offset)
+}
+
+/// [`Position`] of the byte at `index` in `content`
+pub fn position(content: &Rope, index: usize, encoding: PositionEncoding) -> Position {
+    let index = index.min(content.len_bytes());
+    let line = content.byte_to_line(index);
+    let start = content.line_to_byte(line);
+    let prefix = content.byte_slice(start..index).to_string();
+
+    Position::new(line as u32, encoding.len(&prefix) as u32)
+}
+
+/// Text of a line without its line break
+pub fn line_content(line: &str) -> &str {
+    line.trim_end_matches(['\n', '\r'])
+}
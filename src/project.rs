@@ -0,0 +1,82 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use lsp_types::Url;
+
+use crate::compiler::CompileError;
+
+pub struct Project {
+    pub modules: Vec<Module>,
+    /// Problems of the build files, e.g. dependencies that could not be resolved
+    pub errors: HashMap<Url, Vec<CompileError>>,
+}
+
+impl Project {
+    pub fn new(modules: Vec<Module>) -> Self {
+        Project {
+            modules,
+            errors: HashMap::new(),
+        }
+    }
+}
+
+/// A unit of compilation, e.g. a maven module or a gradle source set.
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub name: String,
+    pub root: PathBuf,
+    pub source_roots: Vec<PathBuf>,
+    pub output_directory: PathBuf,
+    pub classpath: Vec<PathBuf>,
+    /// Java version that the sources are compiled for
+    pub release: Option<String>,
+    /// Classpath that annotation processors are loaded from
+    pub processor_path: Vec<PathBuf>,
+    /// Directory that annotation processors generate sources into
+    pub generated_sources: PathBuf,
+}
+
+impl Module {
+    /// Check whether this module needs the output of `other` to compile
+    pub fn depends_on(&self, other: &Module) -> bool {
+        self.name != other.name && self.classpath.contains(&other.output_directory)
+    }
+}
+
+/// Sort modules so that every module comes after the modules it depends on.
+///
+/// Modules with cyclic dependencies keep their original order.
+pub fn sort_by_dependencies(modules: Vec<Module>) -> Vec<Module> {
+    let mut remaining = modules;
+    let mut sorted: Vec<Module> = Vec::with_capacity(remaining.len());
+
+    while !remaining.is_empty() {
+        let position = remaining
+            .iter()
+            .position(|module| {
+                !remaining
+                    .iter()
+                    .any(|dependency| module.depends_on(dependency))
+            })
+            .unwrap_or(0);
+        sorted.push(remaining.remove(position));
+    }
+
+    sorted
+}
+
+pub fn join_paths(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<String>>()
+        .join(":")
+}
+
+pub fn split_paths(paths: &str) -> Vec<PathBuf> {
+    paths
+        .split([':', '\n'])
+        .map(str::trim)
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .collect()
+}
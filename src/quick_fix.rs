@@ -0,0 +1,460 @@
+use tree_sitter::Node;
+
+use crate::{
+    formatter::Replacement,
+    refactor::{identifiers, unique_name},
+    source_edit::{ancestor, indent, insert_member, line_indentation, statement},
+    tree_sitter::{TYPE_DECLARATIONS, has_modifier},
+};
+
+/// Body of generated methods
+pub const METHOD_STUB: &str = "throw new UnsupportedOperationException(\"Not implemented yet\");";
+
+/// Expressions whose operand can be the expression that has an incompatible type
+const EXPRESSIONS: [&str; 9] = [
+    "binary_expression",
+    "unary_expression",
+    "method_invocation",
+    "field_access",
+    "array_access",
+    "object_creation_expression",
+    "cast_expression",
+    "ternary_expression",
+    "instanceof_expression",
+];
+
+/// Symbol of a `cant.resolve` diagnostic like `method run(int,String)`
+pub enum Symbol<'a> {
+    Class(&'a str),
+    Variable(&'a str),
+    Method(&'a str, Vec<&'a str>),
+}
+
+impl<'a> Symbol<'a> {
+    pub fn parse(symbol: &'a str) -> Option<Self> {
+        let (kind, name) = symbol.split_once(char::is_whitespace)?;
+        let name = name.trim();
+
+        match kind {
+            "class" | "interface" | "enum" | "record" => Some(Symbol::Class(name)),
+            "variable" => Some(Symbol::Variable(name)),
+            "method" => {
+                let (name, parameters) = name.split_once('(')?;
+                Some(Symbol::Method(
+                    name,
+                    split_types(parameters.strip_suffix(')')?),
+                ))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Name of the type that a `location` like `class A` or `variable b of type B` refers to,
+/// without type arguments
+pub fn location_type(location: &str) -> Option<&str> {
+    let name = match location.rsplit_once(" of type ") {
+        Some((_, name)) => name,
+        None => location
+            .strip_prefix("class ")
+            .or_else(|| location.strip_prefix("interface "))
+            .or_else(|| location.strip_prefix("enum "))
+            .or_else(|| location.strip_prefix("record "))?,
+    };
+
+    name.split('<').next().map(str::trim)
+}
+
+/// Whether the diagnostic at `offset` selects a member through a type like `B.run()`
+/// instead of an instance
+pub fn is_static_access(root: Node, offset: usize, location: &str) -> bool {
+    let Some(selection) = selection(root, offset) else {
+        return false;
+    };
+
+    match selection.child_by_field_name("object") {
+        Some(object) => {
+            !matches!(object.kind(), "this" | "super") && !location.contains(" of type ")
+        }
+        // Unqualified names are static if they are used in a static method
+        None => ancestor(selection, &["method_declaration"])
+            .is_some_and(|method| has_modifier(method, "static")),
+    }
+}
+
+/// Declaration of a method or field that is used at `offset` but does not exist yet
+pub fn member_stub(
+    root: Node,
+    source: &str,
+    offset: usize,
+    symbol: &Symbol,
+    is_static: bool,
+    visibility: &str,
+    unit: &str,
+) -> Option<String> {
+    let selection = selection(root, offset)?;
+    let modifiers = [visibility, if is_static { "static" } else { "" }]
+        .into_iter()
+        .filter(|modifier| !modifier.is_empty())
+        .map(|modifier| format!("{modifier} "))
+        .collect::<String>();
+    let expected = expected_type(selection, source);
+
+    match symbol {
+        Symbol::Method(name, parameters) => {
+            let parameters = parameters
+                .iter()
+                .enumerate()
+                .map(|(i, parameter)| format!("{parameter} arg{i}"))
+                .collect::<Vec<String>>()
+                .join(", ");
+            let return_type = expected.unwrap_or("void");
+
+            Some(format!(
+                "{modifiers}{return_type} {name}({parameters}) {{\n{unit}{METHOD_STUB}\n}}"
+            ))
+        }
+        Symbol::Variable(name) => {
+            let field_type = expected.unwrap_or("Object");
+            Some(format!("{modifiers}{field_type} {name};"))
+        }
+        Symbol::Class(_) => None,
+    }
+}
+
+/// Stub of an abstract method that the class at `offset` has to implement, `signature` is
+/// written like `compare(T,T)` by javac
+pub fn implement_method(
+    root: Node,
+    source: &str,
+    offset: usize,
+    signature: &str,
+    return_type: &str,
+    unit: &str,
+) -> Option<Replacement> {
+    let (name, parameters) = signature.split_once('(')?;
+    let parameters = split_types(parameters.strip_suffix(')')?)
+        .iter()
+        .enumerate()
+        .map(|(i, parameter)| format!("{parameter} arg{i}"))
+        .collect::<Vec<String>>()
+        .join(", ");
+    let method =
+        format!("@Override\npublic {return_type} {name}({parameters}) {{\n{unit}{METHOD_STUB}\n}}");
+
+    insert_member(type_body(root, offset)?, source, &method, unit)
+}
+
+/// Type argument at `index` that the type declared at `offset` passes to its supertype
+/// `name`, like `String` for `Callable<String>`
+pub fn supertype_argument(
+    root: Node,
+    source: &str,
+    offset: usize,
+    name: &str,
+    index: usize,
+) -> Option<String> {
+    let node = root.descendant_for_byte_range(offset, offset)?;
+    let declaration = ancestor(node, &TYPE_DECLARATIONS)?;
+    let mut cursor = declaration.walk();
+    let supertypes = declaration
+        .children(&mut cursor)
+        .filter(|child| {
+            matches!(
+                child.kind(),
+                "superclass" | "super_interfaces" | "extends_interfaces"
+            )
+        })
+        .collect::<Vec<Node>>();
+
+    let supertype = supertypes
+        .iter()
+        .find_map(|supertypes| generic_type(*supertypes, source, name))?;
+    let mut cursor = supertype.walk();
+    let arguments = supertype
+        .named_children(&mut cursor)
+        .find(|child| child.kind() == "type_arguments")?;
+
+    arguments
+        .named_child(index)?
+        .utf8_text(source.as_bytes())
+        .ok()
+        .map(str::to_string)
+}
+
+/// Parameterized type named `name` below `node`
+fn generic_type<'tree>(node: Node<'tree>, source: &str, name: &str) -> Option<Node<'tree>> {
+    if node.kind() == "generic_type"
+        && let Some(raw) = node.named_child(0)
+        && let Ok(text) = raw.utf8_text(source.as_bytes())
+        && (text == name || text.ends_with(&format!(".{name}")))
+    {
+        return Some(node);
+    }
+
+    let mut cursor = node.walk();
+    let children = node.named_children(&mut cursor).collect::<Vec<Node>>();
+    children
+        .into_iter()
+        .find_map(|child| generic_type(child, source, name))
+}
+
+/// Body of the type that is declared at `offset`
+pub fn type_body(root: Node, offset: usize) -> Option<Node> {
+    let node = root.descendant_for_byte_range(offset, offset)?;
+    ancestor(node, &TYPE_DECLARATIONS)?.child_by_field_name("body")
+}
+
+/// Body of the type declaration named `name` below `root`
+pub fn find_type_body<'tree>(root: Node<'tree>, source: &str, name: &str) -> Option<Node<'tree>> {
+    let mut cursor = root.walk();
+    for child in root.named_children(&mut cursor) {
+        if TYPE_DECLARATIONS.contains(&child.kind())
+            && let Some(body) = child.child_by_field_name("body")
+        {
+            if child
+                .child_by_field_name("name")
+                .is_some_and(|declared| declared.utf8_text(source.as_bytes()) == Ok(name))
+            {
+                return Some(body);
+            }
+            if let Some(nested) = find_type_body(body, source, name) {
+                return Some(nested);
+            }
+        } else if child.kind() == "enum_body_declarations"
+            && let Some(nested) = find_type_body(child, source, name)
+        {
+            return Some(nested);
+        }
+    }
+
+    None
+}
+
+/// Declare that the method or constructor around `offset` throws `exception`
+pub fn add_throws(root: Node, offset: usize, exception: &str) -> Option<Replacement> {
+    let node = root.descendant_for_byte_range(offset, offset)?;
+    let method = ancestor(
+        node,
+        &[
+            "method_declaration",
+            "constructor_declaration",
+            // Exceptions can not be thrown out of lambdas and initializers
+            "lambda_expression",
+            "class_body",
+        ],
+    )
+    .filter(|method| method.kind().ends_with("_declaration"))?;
+
+    let mut cursor = method.walk();
+    let throws = method
+        .children(&mut cursor)
+        .find(|child| child.kind() == "throws");
+
+    Some(match throws {
+        Some(throws) => Replacement {
+            start: throws.end_byte(),
+            end: throws.end_byte(),
+            text: format!(", {exception}"),
+        },
+        None => {
+            let parameters = method.child_by_field_name("parameters")?;
+            let end = method
+                .child_by_field_name("dimensions")
+                .unwrap_or(parameters)
+                .end_byte();
+            Replacement {
+                start: end,
+                end,
+                text: format!(" throws {exception}"),
+            }
+        }
+    })
+}
+
+/// Surround the statement at `offset` with a block that catches `exception`. Variables stay
+/// declared before the block, only their initializers move into it.
+pub fn surround_with_try(
+    root: Node,
+    source: &str,
+    offset: usize,
+    exception: &str,
+    unit: &str,
+) -> Option<Replacement> {
+    let node = root.descendant_for_byte_range(offset, offset)?;
+    let statement = statement(node)?;
+    let indentation = line_indentation(statement, source);
+    let (declaration, body) = match statement.kind() {
+        "local_variable_declaration" => {
+            let (declaration, assignments) = split_declaration(statement, source)?;
+            (
+                format!("{declaration}\n{indentation}"),
+                assignments.join(&format!("\n{indentation}")),
+            )
+        }
+        _ => (String::new(), source[statement.byte_range()].to_string()),
+    };
+    // The lines after the first one keep their own indentation
+    let body = indent(&body, unit);
+    let member = ancestor(
+        statement,
+        &["method_declaration", "constructor_declaration"],
+    )
+    .unwrap_or(statement);
+    let name = unique_name("e", &identifiers(member, source));
+    let text = format!(
+        "{declaration}try {{\n{indentation}{body}\n{indentation}}} catch ({exception} {name}) {{\n\
+         {indentation}{unit}throw new RuntimeException({name});\n{indentation}}}"
+    );
+
+    Some(Replacement {
+        start: statement.start_byte(),
+        end: statement.end_byte(),
+        text,
+    })
+}
+
+/// Declaration of the variables of `declaration` without their initializers and the
+/// assignments of the initializers, `None` for `var` and array initializers that can only be
+/// declared
+fn split_declaration(declaration: Node, source: &str) -> Option<(String, Vec<String>)> {
+    let declared = declaration.child_by_field_name("type")?;
+    if &source[declared.byte_range()] == "var" {
+        return None;
+    }
+
+    let mut cursor = declaration.walk();
+    let declarators = declaration
+        .children_by_field_name("declarator", &mut cursor)
+        .collect::<Vec<Node>>();
+    let mut variables = Vec::new();
+    let mut assignments = Vec::new();
+    for declarator in &declarators {
+        let name = declarator.child_by_field_name("name")?;
+        let end = declarator
+            .child_by_field_name("dimensions")
+            .unwrap_or(name)
+            .end_byte();
+        variables.push(&source[declarator.start_byte()..end]);
+        if let Some(value) = declarator.child_by_field_name("value") {
+            if value.kind() == "array_initializer" {
+                return None;
+            }
+            assignments.push(format!(
+                "{} = {};",
+                &source[name.byte_range()],
+                &source[value.byte_range()]
+            ));
+        }
+    }
+
+    // Modifiers and the type precede the first declarator
+    let prefix = &source[declaration.start_byte()..declarators.first()?.start_byte()];
+    Some((format!("{prefix}{};", variables.join(", ")), assignments))
+}
+
+/// Cast the expression that javac reports at `offset` to `target`, javac points to the
+/// operator of binary expressions and to the start of other ones
+pub fn add_cast(root: Node, source: &str, offset: usize, target: &str) -> Option<Replacement> {
+    let mut expression = root.named_descendant_for_byte_range(offset, offset)?;
+    while let Some(parent) = expression.parent() {
+        if !EXPRESSIONS.contains(&parent.kind()) {
+            break;
+        }
+        expression = parent;
+    }
+
+    let text = &source[expression.byte_range()];
+    let text = if matches!(
+        expression.kind(),
+        "binary_expression"
+            | "ternary_expression"
+            | "lambda_expression"
+            | "assignment_expression"
+            | "instanceof_expression"
+            | "cast_expression"
+    ) {
+        format!("({target}) ({text})")
+    } else {
+        format!("({target}) {text}")
+    };
+
+    Some(Replacement {
+        start: expression.start_byte(),
+        end: expression.end_byte(),
+        text,
+    })
+}
+
+/// Target type of an `incompatible types` message
+pub fn required_type(message: &str) -> Option<&str> {
+    let message = message.lines().next()?;
+    let target = match message.rsplit_once(" cannot be converted to ") {
+        Some((_, target)) => target,
+        None => message.rsplit_once(" to ")?.1,
+    };
+
+    // Other problems like `incompatible types: unexpected return value` have no target
+    Some(target.trim()).filter(|target| !target.contains(char::is_whitespace))
+}
+
+/// Method selection, field access or name that the diagnostic at `offset` points to, javac
+/// points to the dot of selections
+fn selection(root: Node, offset: usize) -> Option<Node> {
+    let node = root.descendant_for_byte_range(offset, offset + 1)?;
+    match node.kind() {
+        "." | "identifier" => node
+            .parent()
+            .filter(|parent| matches!(parent.kind(), "method_invocation" | "field_access"))
+            .or(Some(node)),
+        _ => Some(node),
+    }
+}
+
+/// Type that the expression `node` has to have, derived from the declaration or return
+/// statement it is part of. `None` for statements that ignore their value.
+fn expected_type<'a>(node: Node, source: &'a str) -> Option<&'a str> {
+    let parent = node.parent()?;
+    let text = |node: Node| node.utf8_text(source.as_bytes()).ok();
+
+    match parent.kind() {
+        "variable_declarator" if parent.child_by_field_name("value") == Some(node) => {
+            let declaration = parent.parent()?;
+            declaration
+                .child_by_field_name("type")
+                .and_then(text)
+                .filter(|declared| *declared != "var")
+        }
+        "return_statement" => {
+            let method = ancestor(parent, &["method_declaration", "lambda_expression"])?;
+            method.child_by_field_name("type").and_then(text)
+        }
+        "parenthesized_expression" => expected_type(parent, source),
+        "expression_statement" => None,
+        _ => Some("Object"),
+    }
+}
+
+/// Split a list of types like `int,Map<String,Integer>` at the top level commas
+pub fn split_types(types: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (i, c) in types.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(types[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if !types[start..].trim().is_empty() {
+        parts.push(types[start..].trim());
+    }
+
+    parts
+}
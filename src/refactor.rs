@@ -0,0 +1,1201 @@
+use std::collections::HashSet;
+
+use tree_sitter::Node;
+
+use crate::{
+    formatter::Replacement,
+    locals::{
+        Local, declaration, declared_type, is_reference, local_type, locals, visit_references,
+    },
+    organize_imports::is_type_name,
+    source_edit::{BLOCKS, ancestor, indent, insert_first_member, line_indentation, reindent},
+    tree_sitter::{TYPE_DECLARATIONS, has_modifier},
+};
+
+/// Expressions that bind tighter than all operators and never need parentheses
+const PRIMARY_EXPRESSIONS: [&str; 21] = [
+    "identifier",
+    "this",
+    "field_access",
+    "method_invocation",
+    "array_access",
+    "object_creation_expression",
+    "parenthesized_expression",
+    "class_literal",
+    "method_reference",
+    "binary_integer_literal",
+    "character_literal",
+    "decimal_floating_point_literal",
+    "decimal_integer_literal",
+    "false",
+    "hex_floating_point_literal",
+    "hex_integer_literal",
+    "null_literal",
+    "octal_integer_literal",
+    "string_literal",
+    "true",
+    "template_expression",
+];
+
+/// Expressions that are not primary expressions
+const OPERATOR_EXPRESSIONS: [&str; 10] = [
+    "assignment_expression",
+    "binary_expression",
+    "cast_expression",
+    "instanceof_expression",
+    "lambda_expression",
+    "switch_expression",
+    "ternary_expression",
+    "unary_expression",
+    "update_expression",
+    "array_creation_expression",
+];
+
+const METHODS: [&str; 2] = ["method_declaration", "constructor_declaration"];
+
+/// Expressions that can change state when they are evaluated or whose value is a new object
+const EFFECTS: [&str; 5] = [
+    "method_invocation",
+    "object_creation_expression",
+    "array_creation_expression",
+    "assignment_expression",
+    "update_expression",
+];
+
+/// Nodes whose content is evaluated repeatedly or later than the code around them
+const DEFERRED: [&str; 6] = [
+    "for_statement",
+    "enhanced_for_statement",
+    "while_statement",
+    "do_statement",
+    "lambda_expression",
+    "class_body",
+];
+
+const KEYWORDS: [&str; 53] = [
+    "abstract",
+    "assert",
+    "boolean",
+    "break",
+    "byte",
+    "case",
+    "catch",
+    "char",
+    "class",
+    "const",
+    "continue",
+    "default",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "extends",
+    "final",
+    "finally",
+    "float",
+    "for",
+    "goto",
+    "if",
+    "implements",
+    "import",
+    "instanceof",
+    "int",
+    "interface",
+    "long",
+    "native",
+    "new",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "short",
+    "static",
+    "strictfp",
+    "super",
+    "switch",
+    "synchronized",
+    "this",
+    "throw",
+    "throws",
+    "transient",
+    "try",
+    "void",
+    "volatile",
+    "while",
+    "true",
+    "false",
+    "null",
+];
+
+/// Replace the expression between `start` and `end` by a new local variable that is
+/// declared before the statement that contains it
+pub fn extract_variable(
+    root: Node,
+    source: &str,
+    start: usize,
+    end: usize,
+) -> Option<Vec<Replacement>> {
+    let expression = selected_expression(root, source, start, end)?;
+    if matches!(
+        expression.kind(),
+        "assignment_expression" | "update_expression" | "null_literal"
+    ) || expression
+        .parent()
+        .is_some_and(|parent| parent.kind() == "expression_statement")
+    {
+        return None;
+    }
+    let statement = evaluation_statement(expression)?;
+    // Moving the expression before the statement would evaluate it before these effects
+    if has_effects_before(statement, expression) {
+        return None;
+    }
+    let method = ancestor(statement, &METHODS)?;
+    let locals = locals(method, source);
+
+    let variable_type = expected_type(expression, source)
+        .or_else(|| {
+            expression_type(expression, source, &|node| {
+                local_type(&locals, node, source)
+            })
+        })
+        .or_else(|| {
+            // Lambdas need a target type
+            (!matches!(expression.kind(), "lambda_expression" | "method_reference"))
+                .then(|| "var".to_string())
+        })?;
+    let name = unique_name(
+        &suggest_name(expression, source),
+        &identifiers(method, source),
+    );
+    let text = &source[expression.byte_range()];
+    let declaration = format!(
+        "{variable_type} {name} = {text};\n{}",
+        line_indentation(statement, source)
+    );
+
+    // An expression at the start of the statement is replaced by a single edit
+    Some(if statement.start_byte() == expression.start_byte() {
+        vec![Replacement {
+            start: expression.start_byte(),
+            end: expression.end_byte(),
+            text: format!("{declaration}{name}"),
+        }]
+    } else {
+        vec![
+            Replacement {
+                start: statement.start_byte(),
+                end: statement.start_byte(),
+                text: declaration,
+            },
+            Replacement {
+                start: expression.start_byte(),
+                end: expression.end_byte(),
+                text: name,
+            },
+        ]
+    })
+}
+
+/// Replace the expression between `start` and `end` by a new constant of the type that
+/// contains it. The expression must not depend on local variables or the instance.
+pub fn extract_constant(
+    root: Node,
+    source: &str,
+    start: usize,
+    end: usize,
+    unit: &str,
+) -> Option<Vec<Replacement>> {
+    let expression = selected_expression(root, source, start, end)?;
+    let declaration = ancestor(expression, &TYPE_DECLARATIONS)?;
+    let locals = ancestor(expression, &METHODS)
+        .map(|method| locals(method, source))
+        .unwrap_or_default();
+    if !is_constant(expression, source, &locals) {
+        return None;
+    }
+
+    let constant_type = expected_type(expression, source)
+        .or_else(|| expression_type(expression, source, &|_| None))?;
+    let name = unique_name(
+        &constant_name(&suggest_name(expression, source)),
+        &identifiers(declaration, source),
+    );
+    // Fields of interfaces are implicitly public, static and final
+    let modifiers = match declaration.kind() {
+        "interface_declaration" | "annotation_type_declaration" => "",
+        _ => "private static final ",
+    };
+    let text = &source[expression.byte_range()];
+    let member = format!("{modifiers}{constant_type} {name} = {text};");
+
+    Some(vec![
+        insert_first_member(
+            declaration.child_by_field_name("body")?,
+            source,
+            &member,
+            unit,
+        )?,
+        Replacement {
+            start: expression.start_byte(),
+            end: expression.end_byte(),
+            text: name,
+        },
+    ])
+}
+
+/// Move the statements or the expression between `start` and `end` into a new method after
+/// the one that contains them. The local variables they use become parameters and a single
+/// variable that they assign and that is used afterwards becomes the return value.
+pub fn extract_method(
+    root: Node,
+    source: &str,
+    start: usize,
+    end: usize,
+    unit: &str,
+) -> Option<Vec<Replacement>> {
+    let (start, end) = trim(source, start, end);
+    let statements = selected_statements(root, start, end);
+    let expression = match statements {
+        Some(_) => None,
+        None => Some(selected_expression(root, source, start, end)?),
+    };
+    let selected = statements
+        .clone()
+        .unwrap_or(expression.into_iter().collect());
+    let first = *selected.first()?;
+    let method = ancestor(first, &[METHODS[0], METHODS[1], "class_body"])
+        .filter(|method| METHODS.contains(&method.kind()))?;
+    if selected.iter().any(|node| leaves_selection(*node)) {
+        return None;
+    }
+
+    let locals = locals(method, source);
+    let is_inside = |node: Node| node.start_byte() >= start && node.end_byte() <= end;
+    let mut references = Vec::new();
+    let mut assigned = Vec::new();
+    for node in &selected {
+        visit_references(*node, source, &locals, &mut |_, local, is_assignment| {
+            if !is_inside(locals[local].node) {
+                references.push(local);
+                if is_assignment {
+                    assigned.push(local);
+                }
+            }
+        });
+    }
+
+    // Variables that are declared or assigned in the selection and used after it
+    let mut outputs = Vec::new();
+    visit_references(method, source, &locals, &mut |reference, local, _| {
+        if reference.start_byte() >= end
+            && (is_inside(locals[local].node) || assigned.contains(&local))
+            && !outputs.contains(&local)
+        {
+            outputs.push(local);
+        }
+    });
+    let output = match outputs.as_slice() {
+        [] => None,
+        [output] if expression.is_none() => Some(&locals[*output]),
+        _ => return None,
+    };
+
+    let mut parameters = Vec::new();
+    for local in references {
+        if !parameters.contains(&local) {
+            parameters.push(local);
+        }
+    }
+    let arguments = parameters
+        .iter()
+        .map(|local| locals[*local].name.as_str())
+        .collect::<Vec<&str>>()
+        .join(", ");
+    let parameters = parameters
+        .iter()
+        .map(|local| {
+            let local = &locals[*local];
+            Some(format!("{} {}", local.declared_type.as_ref()?, local.name))
+        })
+        .collect::<Option<Vec<String>>>()?
+        .join(", ");
+
+    let name = unique_name(
+        "extracted",
+        &identifiers(ancestor(method, &TYPE_DECLARATIONS)?, source),
+    );
+    let call = format!("{name}({arguments})");
+    let base = line_indentation(first, source);
+    let (return_type, body, call) = match (expression, output) {
+        (Some(expression), _) => {
+            let text = &source[expression.byte_range()];
+            if expression
+                .parent()
+                .is_some_and(|parent| parent.kind() == "expression_statement")
+            {
+                ("void".to_string(), format!("{text};"), call)
+            } else {
+                let return_type = expected_type(expression, source).or_else(|| {
+                    expression_type(expression, source, &|node| {
+                        local_type(&locals, node, source)
+                    })
+                })?;
+                (return_type, format!("return {text};"), call)
+            }
+        }
+        (None, None) => (
+            "void".to_string(),
+            source[start..end].to_string(),
+            format!("{call};"),
+        ),
+        (None, Some(output)) => {
+            let return_type = output.declared_type.clone()?;
+            let body = format!("{}\nreturn {};", &source[start..end], output.name);
+            let call = if is_inside(output.node) {
+                format!("{return_type} {} = {call};", output.name)
+            } else {
+                format!("{} = {call};", output.name)
+            };
+            (return_type, body, call)
+        }
+    };
+
+    let modifiers = if has_modifier(method, "static") {
+        "private static "
+    } else {
+        "private "
+    };
+    let type_parameters = method
+        .child_by_field_name("type_parameters")
+        .map(|parameters| format!("{} ", &source[parameters.byte_range()]))
+        .unwrap_or_default();
+    // The exceptions of the enclosing method cover the ones of the selection
+    let mut cursor = method.walk();
+    let throws = method
+        .children(&mut cursor)
+        .find(|child| child.kind() == "throws")
+        .map(|throws| format!(" {}", &source[throws.byte_range()]))
+        .unwrap_or_default();
+    let declaration = format!(
+        "{modifiers}{type_parameters}{return_type} {name}({parameters}){throws} {{\n{}\n}}",
+        reindent(&body, base, unit)
+    );
+
+    Some(vec![
+        Replacement {
+            start,
+            end,
+            text: call,
+        },
+        Replacement {
+            start: method.end_byte(),
+            end: method.end_byte(),
+            text: format!(
+                "\n\n{}",
+                indent(&declaration, line_indentation(method, source))
+            ),
+        },
+    ])
+}
+
+/// Replace all references to the local variable at `offset` by its initializer and remove
+/// its declaration. Variables that are assigned after their declaration are not inlined, and
+/// neither are initializers that would be evaluated at another time with another result:
+/// an initializer with side effects only replaces a single reference that is evaluated next,
+/// and the inputs of other initializers must not change before a reference.
+pub fn inline_variable(root: Node, source: &str, offset: usize) -> Option<Vec<Replacement>> {
+    let node = root
+        .named_descendant_for_byte_range(offset, offset)
+        .filter(|node| node.kind() == "identifier")
+        .or_else(|| {
+            let before = offset.checked_sub(1)?;
+            root.named_descendant_for_byte_range(before, before)
+                .filter(|node| node.kind() == "identifier")
+        })?;
+    let method = ancestor(node, &METHODS)?;
+    let locals = locals(method, source);
+    let local = declaration(&locals, node, source)?;
+
+    let declarator = locals[local]
+        .node
+        .parent()
+        .filter(|parent| parent.kind() == "variable_declarator")?;
+    let declaration = declarator
+        .parent()
+        .filter(|parent| parent.kind() == "local_variable_declaration")?;
+    let mut cursor = declaration.walk();
+    if declaration
+        .children_by_field_name("declarator", &mut cursor)
+        .count()
+        != 1
+    {
+        return None;
+    }
+    let value = declarator
+        .child_by_field_name("value")
+        .filter(|value| value.kind() != "array_initializer")?;
+
+    let mut references = Vec::new();
+    let mut is_assigned = false;
+    visit_references(
+        method,
+        source,
+        &locals,
+        &mut |reference, referenced, assignment| {
+            if referenced == local && reference != locals[local].node {
+                references.push(reference);
+                is_assigned |= assignment;
+            }
+        },
+    );
+    if is_assigned {
+        return None;
+    }
+
+    let text = &source[value.byte_range()];
+    let has_effects = has_effects(value);
+    if references.is_empty() && has_effects {
+        // The initializer stays as a statement if it can be one
+        return matches!(
+            value.kind(),
+            "method_invocation"
+                | "object_creation_expression"
+                | "assignment_expression"
+                | "update_expression"
+        )
+        .then(|| {
+            vec![Replacement {
+                start: declaration.start_byte(),
+                end: declaration.end_byte(),
+                text: format!("{text};"),
+            }]
+        });
+    }
+    let is_safe = if has_effects {
+        match references.as_slice() {
+            [reference] => is_evaluated_next(declaration, *reference),
+            _ => false,
+        }
+    } else {
+        is_unchanged(method, source, &locals, declaration, value, &references)
+    };
+    if !is_safe {
+        return None;
+    }
+
+    let mut replacements = vec![remove_statement(declaration, source)];
+    replacements.extend(references.into_iter().map(|reference| Replacement {
+        start: reference.start_byte(),
+        end: reference.end_byte(),
+        text: if needs_parentheses(value, reference) {
+            format!("({text})")
+        } else {
+            text.to_string()
+        },
+    }));
+    replacements.sort_by_key(|replacement| replacement.start);
+
+    Some(replacements)
+}
+
+/// Expression that spans exactly the selection between `start` and `end`
+fn selected_expression<'tree>(
+    root: Node<'tree>,
+    source: &str,
+    start: usize,
+    end: usize,
+) -> Option<Node<'tree>> {
+    let (start, end) = trim(source, start, end);
+    let node = root
+        .named_descendant_for_byte_range(start, end)
+        .filter(|node| node.start_byte() == start && node.end_byte() == end)?;
+    let parent = node.parent()?;
+
+    let is_expression =
+        PRIMARY_EXPRESSIONS.contains(&node.kind()) || OPERATOR_EXPRESSIONS.contains(&node.kind());
+    // Names of declarations and members are not expressions of their own
+    let is_name = node.kind() == "identifier"
+        && (parent.child_by_field_name("name") == Some(node)
+            || parent.child_by_field_name("field") == Some(node)
+            || is_type_name(&source[node.byte_range()]));
+
+    (is_expression && !is_name).then_some(node)
+}
+
+/// Statements of a block that span exactly the selection between `start` and `end`
+fn selected_statements(root: Node, start: usize, end: usize) -> Option<Vec<Node>> {
+    let mut block = root.descendant_for_byte_range(start, end);
+    while let Some(node) = block {
+        if BLOCKS.contains(&node.kind()) {
+            let mut cursor = node.walk();
+            let statements = node
+                .named_children(&mut cursor)
+                .filter(|child| child.kind() != "switch_label")
+                .filter(|child| child.start_byte() >= start && child.end_byte() <= end)
+                .collect::<Vec<Node>>();
+
+            if let (Some(first), Some(last)) = (statements.first(), statements.last())
+                && first.start_byte() == start
+                && last.end_byte() == end
+            {
+                return Some(statements);
+            }
+        }
+        block = node.parent();
+    }
+
+    None
+}
+
+/// Whether the control flow of `node` continues outside of it through `return`, `yield` or
+/// jumps to loops and labels around it
+fn leaves_selection(node: Node) -> bool {
+    fn visit(node: Node, in_loop: bool, in_switch: bool) -> bool {
+        match node.kind() {
+            "return_statement" | "yield_statement" => return true,
+            "break_statement" | "continue_statement" => {
+                let is_labeled = node.named_child(0).is_some();
+                let has_target = if node.kind() == "break_statement" {
+                    in_loop || in_switch
+                } else {
+                    in_loop
+                };
+                return is_labeled || !has_target;
+            }
+            // Returns of lambdas and local classes stay in them
+            "lambda_expression" | "class_body" => return false,
+            _ => {}
+        }
+        let in_loop = in_loop
+            || matches!(
+                node.kind(),
+                "for_statement" | "enhanced_for_statement" | "while_statement" | "do_statement"
+            );
+        let in_switch = in_switch || node.kind() == "switch_block";
+
+        let mut cursor = node.walk();
+        let children = node.named_children(&mut cursor).collect::<Vec<Node>>();
+        children
+            .into_iter()
+            .any(|child| visit(child, in_loop, in_switch))
+    }
+
+    visit(node, false, false)
+}
+
+/// Statement before which `expression` can be evaluated without changing how often or
+/// whether it is evaluated
+fn evaluation_statement(expression: Node) -> Option<Node> {
+    let mut child = expression;
+    while let Some(parent) = child.parent() {
+        let is_field = |field: &str| parent.child_by_field_name(field) == Some(child);
+        match parent.kind() {
+            kind if BLOCKS.contains(&kind) => return Some(child),
+            "lambda_expression" | "while_statement" | "do_statement" | "for_statement"
+            | "switch_rule" | "class_body" => return None,
+            "if_statement" | "ternary_expression" if !is_field("condition") => return None,
+            "enhanced_for_statement" if !is_field("value") => return None,
+            "binary_expression"
+                if is_field("right")
+                    && parent
+                        .child_by_field_name("operator")
+                        .is_some_and(|operator| matches!(operator.kind(), "&&" | "||")) =>
+            {
+                return None;
+            }
+            _ => {}
+        }
+        child = parent;
+    }
+
+    None
+}
+
+/// Whether evaluating `node` can have side effects or creates a new object, lambdas and
+/// local classes are not evaluated with it
+fn has_effects(node: Node) -> bool {
+    if EFFECTS.contains(&node.kind()) {
+        return true;
+    }
+    if matches!(node.kind(), "lambda_expression" | "class_body") {
+        return false;
+    }
+
+    let mut cursor = node.walk();
+    let children = node.named_children(&mut cursor).collect::<Vec<Node>>();
+    children.into_iter().any(has_effects)
+}
+
+/// Whether something with side effects is evaluated before `expression` in `statement`,
+/// operands are evaluated from left to right
+fn has_effects_before(statement: Node, expression: Node) -> bool {
+    if statement.end_byte() <= expression.start_byte() {
+        return has_effects(statement);
+    }
+    if statement.start_byte() > expression.start_byte() || statement == expression {
+        return false;
+    }
+
+    let mut cursor = statement.walk();
+    let children = statement.named_children(&mut cursor).collect::<Vec<Node>>();
+    children
+        .into_iter()
+        .any(|child| has_effects_before(child, expression))
+}
+
+/// Whether `reference` is evaluated right after `declaration`: once in the following
+/// statement and before anything else with side effects
+fn is_evaluated_next(declaration: Node, reference: Node) -> bool {
+    let mut next = declaration.next_named_sibling();
+    while let Some(comment) = next.filter(|next| next.kind().ends_with("comment")) {
+        next = comment.next_named_sibling();
+    }
+
+    next.is_some_and(|next| {
+        evaluation_statement(reference) == Some(next) && !has_effects_before(next, reference)
+    })
+}
+
+/// Whether the initializer `value` of `declaration` evaluates to the same value at each of
+/// the `references`: the local variables that it reads are not assigned in between, and if
+/// it reads fields or array elements, nothing with side effects happens in between.
+/// References in loops, lambdas and local classes can be evaluated after anything in them.
+fn is_unchanged(
+    method: Node,
+    source: &str,
+    locals: &[Local],
+    declaration: Node,
+    value: Node,
+    references: &[Node],
+) -> bool {
+    let mut inputs = Vec::new();
+    visit_references(value, source, locals, &mut |_, local, _| inputs.push(local));
+
+    let spans = references
+        .iter()
+        .map(|reference| {
+            let mut end = reference.end_byte();
+            let mut current = reference.parent();
+            // Ancestors that do not contain the declaration start after it
+            while let Some(node) =
+                current.filter(|node| node.start_byte() > declaration.start_byte())
+            {
+                if DEFERRED.contains(&node.kind()) {
+                    end = end.max(node.end_byte());
+                }
+                current = node.parent();
+            }
+            declaration.end_byte()..end
+        })
+        .collect::<Vec<_>>();
+    // Expressions around a reference are evaluated after it
+    let is_between = |node: Node| {
+        spans.iter().any(|span| span.contains(&node.start_byte()))
+            && !references.iter().any(|reference| {
+                node.start_byte() <= reference.start_byte()
+                    && reference.end_byte() <= node.end_byte()
+            })
+    };
+
+    // Lambdas and local classes can only capture variables that are effectively final
+    let is_captured = references.iter().any(|reference| {
+        ancestor(*reference, &["lambda_expression", "class_body"])
+            .is_some_and(|deferred| deferred.start_byte() > declaration.start_byte())
+    });
+    let mut is_changed = false;
+    visit_references(
+        method,
+        source,
+        locals,
+        &mut |reference, local, is_assignment| {
+            is_changed |=
+                is_assignment && inputs.contains(&local) && (is_captured || is_between(reference));
+        },
+    );
+    if is_changed {
+        return false;
+    }
+
+    !reads_state(value, source, locals) || !has_effects_between(method, &is_between)
+}
+
+/// Whether `node` reads fields or array elements, which can change without an assignment to
+/// a local variable
+fn reads_state(node: Node, source: &str, locals: &[Local]) -> bool {
+    match node.kind() {
+        "field_access" | "array_access" => return true,
+        "identifier" => {
+            let name = &source[node.byte_range()];
+            return is_reference(node)
+                && declaration(locals, node, source).is_none()
+                && !is_type_name(name)
+                && !is_constant_name(name);
+        }
+        "lambda_expression" | "class_body" => return false,
+        _ => {}
+    }
+
+    let mut cursor = node.walk();
+    let children = node.named_children(&mut cursor).collect::<Vec<Node>>();
+    children
+        .into_iter()
+        .any(|child| reads_state(child, source, locals))
+}
+
+fn has_effects_between(node: Node, is_between: &impl Fn(Node) -> bool) -> bool {
+    if EFFECTS.contains(&node.kind()) && is_between(node) {
+        return true;
+    }
+
+    let mut cursor = node.walk();
+    let children = node.named_children(&mut cursor).collect::<Vec<Node>>();
+    children
+        .into_iter()
+        .any(|child| has_effects_between(child, is_between))
+}
+
+/// Whether `node` can be evaluated in a static field initializer
+fn is_constant(node: Node, source: &str, locals: &[Local]) -> bool {
+    match node.kind() {
+        "this" | "super" | "lambda_expression" | "method_reference" => return false,
+        "method_invocation" if node.child_by_field_name("object").is_none() => return false,
+        "identifier" => {
+            if !is_reference(node) {
+                return true;
+            }
+            if declaration(locals, node, source).is_some() {
+                return false;
+            }
+            let name = &source[node.byte_range()];
+            let is_object = node
+                .parent()
+                .is_some_and(|parent| parent.child_by_field_name("object") == Some(node));
+            return (is_object && is_type_name(name)) || is_constant_name(name);
+        }
+        _ => {}
+    }
+
+    let mut cursor = node.walk();
+    let children = node.named_children(&mut cursor).collect::<Vec<Node>>();
+    children
+        .into_iter()
+        .all(|child| is_constant(child, source, locals))
+}
+
+/// Declared type of the variable that `expression` initializes
+fn expected_type(expression: Node, source: &str) -> Option<String> {
+    let declarator = expression
+        .parent()
+        .filter(|parent| parent.kind() == "variable_declarator")
+        .filter(|declarator| declarator.child_by_field_name("value") == Some(expression))?;
+    let declaration = declarator.parent()?;
+
+    declared_type(
+        declaration.child_by_field_name("type"),
+        declarator.child_by_field_name("dimensions"),
+        source,
+    )
+}
+
+/// Type of `expression` if it follows from its literals, operators and casts. `local_type`
+/// gives the types of identifiers.
+fn expression_type(
+    expression: Node,
+    source: &str,
+    local_type: &dyn Fn(Node) -> Option<String>,
+) -> Option<String> {
+    let text = &source[expression.byte_range()];
+    let operand = |field: &str| {
+        expression
+            .child_by_field_name(field)
+            .and_then(|operand| expression_type(operand, source, local_type))
+    };
+
+    match expression.kind() {
+        "string_literal" => Some("String".to_string()),
+        "character_literal" => Some("char".to_string()),
+        "true" | "false" => Some("boolean".to_string()),
+        "decimal_integer_literal"
+        | "hex_integer_literal"
+        | "octal_integer_literal"
+        | "binary_integer_literal" => Some(if text.ends_with(['l', 'L']) {
+            "long".to_string()
+        } else {
+            "int".to_string()
+        }),
+        "decimal_floating_point_literal" | "hex_floating_point_literal" => {
+            Some(if text.ends_with(['f', 'F']) {
+                "float".to_string()
+            } else {
+                "double".to_string()
+            })
+        }
+        "identifier" => local_type(expression),
+        "parenthesized_expression" => {
+            expression_type(expression.named_child(0)?, source, local_type)
+        }
+        "cast_expression" => expression
+            .child_by_field_name("type")
+            .map(|cast| source[cast.byte_range()].to_string()),
+        "object_creation_expression" => expression
+            .child_by_field_name("type")
+            .map(|created| source[created.byte_range()].to_string())
+            .filter(|created| !created.contains("<>")),
+        "instanceof_expression" => Some("boolean".to_string()),
+        "assignment_expression" => operand("left"),
+        "update_expression" => expression_type(expression.named_child(0)?, source, local_type),
+        "unary_expression" => match expression.child_by_field_name("operator")?.kind() {
+            "!" => Some("boolean".to_string()),
+            _ => operand("operand").map(|operand| promote(&operand, "int")),
+        },
+        "ternary_expression" => {
+            let consequence = operand("consequence")?;
+            (operand("alternative")? == consequence).then_some(consequence)
+        }
+        "binary_expression" => match expression.child_by_field_name("operator")?.kind() {
+            "==" | "!=" | "<" | ">" | "<=" | ">=" | "&&" | "||" => Some("boolean".to_string()),
+            "<<" | ">>" | ">>>" => operand("left").map(|left| promote(&left, "int")),
+            operator => {
+                let left = operand("left")?;
+                let right = operand("right")?;
+                if operator == "+" && (left == "String" || right == "String") {
+                    Some("String".to_string())
+                } else if left == "boolean" && right == "boolean" {
+                    Some("boolean".to_string())
+                } else {
+                    Some(promote(&left, &right))
+                }
+            }
+        },
+        _ => None,
+    }
+}
+
+/// Binary numeric promotion of the types `a` and `b`
+fn promote(a: &str, b: &str) -> String {
+    ["double", "float", "long"]
+        .into_iter()
+        .find(|wider| a == *wider || b == *wider)
+        .unwrap_or("int")
+        .to_string()
+}
+
+/// Name for a variable with the value of `expression` like `name` for `person.getName()`
+fn suggest_name(expression: Node, source: &str) -> String {
+    let text = |node: Node| &source[node.byte_range()];
+    let name = match expression.kind() {
+        "method_invocation" => expression.child_by_field_name("name").map(|name| {
+            let name = text(name);
+            ["get", "is", "to"]
+                .into_iter()
+                .find_map(|prefix| {
+                    name.strip_prefix(prefix)
+                        .filter(|rest| rest.starts_with(|c: char| c.is_uppercase()))
+                })
+                .unwrap_or(name)
+        }),
+        "field_access" => expression.child_by_field_name("field").map(text),
+        "identifier" => Some(text(expression)),
+        "object_creation_expression" | "cast_expression" => expression
+            .child_by_field_name("type")
+            .and_then(|created| text(created).split('<').next())
+            .and_then(|created| created.rsplit('.').next()),
+        "string_literal" => Some("text"),
+        "parenthesized_expression" => {
+            return expression
+                .named_child(0)
+                .map(|inner| suggest_name(inner, source))
+                .unwrap_or("value".to_string());
+        }
+        _ => None,
+    };
+
+    let name = lower_camel_case(name.unwrap_or("value"));
+    if name.is_empty() || KEYWORDS.contains(&name.as_str()) {
+        "value".to_string()
+    } else {
+        name
+    }
+}
+
+/// `URLConnection` becomes `urlConnection`
+fn lower_camel_case(name: &str) -> String {
+    let upper = name.chars().take_while(|c| c.is_uppercase()).count();
+    let lower = match upper {
+        0 => 0,
+        _ if upper == name.chars().count() => upper,
+        1 => 1,
+        _ => upper - 1,
+    };
+
+    name.chars()
+        .enumerate()
+        .map(|(i, c)| if i < lower { c.to_ascii_lowercase() } else { c })
+        .collect()
+}
+
+/// `maxSize` becomes `MAX_SIZE`
+fn constant_name(name: &str) -> String {
+    let mut constant = String::new();
+    let mut previous_lowercase = false;
+    for c in name.chars() {
+        if c.is_uppercase() && previous_lowercase {
+            constant.push('_');
+        }
+        previous_lowercase = c.is_lowercase() || c.is_ascii_digit();
+        constant.push(c.to_ascii_uppercase());
+    }
+
+    constant
+}
+
+fn is_constant_name(name: &str) -> bool {
+    name.chars()
+        .all(|c| c.is_uppercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// `name` or `name` with the smallest number appended that is not in `taken`
+pub fn unique_name(name: &str, taken: &HashSet<String>) -> String {
+    if !taken.contains(name) {
+        return name.to_string();
+    }
+
+    (2..)
+        .map(|i| format!("{name}{i}"))
+        .find(|candidate| !taken.contains(candidate))
+        .unwrap_or_default()
+}
+
+/// All identifiers below `node`, new names must not hide or conflict with them
+pub fn identifiers(node: Node, source: &str) -> HashSet<String> {
+    let mut identifiers = HashSet::new();
+    let mut cursor = node.walk();
+    let mut stack = vec![node];
+    while let Some(node) = stack.pop() {
+        if node.kind() == "identifier" {
+            identifiers.insert(source[node.byte_range()].to_string());
+        }
+        stack.extend(node.named_children(&mut cursor));
+    }
+
+    identifiers
+}
+
+/// Whether the initializer `value` has to be put in parentheses when it replaces `reference`
+fn needs_parentheses(value: Node, reference: Node) -> bool {
+    if PRIMARY_EXPRESSIONS.contains(&value.kind()) {
+        return false;
+    }
+
+    !reference.parent().is_some_and(|parent| {
+        matches!(
+            parent.kind(),
+            "variable_declarator"
+                | "argument_list"
+                | "return_statement"
+                | "parenthesized_expression"
+                | "array_initializer"
+        ) || (parent.kind() == "assignment_expression"
+            && parent.child_by_field_name("right") == Some(reference))
+    })
+}
+
+/// Remove `statement` together with its line if nothing else is on it
+fn remove_statement(statement: Node, source: &str) -> Replacement {
+    let line_start = source[..statement.start_byte()]
+        .rfind('\n')
+        .map(|line_break| line_break + 1)
+        .unwrap_or(0);
+    let line_end = source[statement.end_byte()..]
+        .find('\n')
+        .map(|line_break| statement.end_byte() + line_break + 1)
+        .unwrap_or(source.len());
+
+    if source[line_start..statement.start_byte()].trim().is_empty()
+        && source[statement.end_byte()..line_end].trim().is_empty()
+    {
+        Replacement {
+            start: line_start,
+            end: line_end,
+            text: String::new(),
+        }
+    } else {
+        Replacement {
+            start: statement.start_byte(),
+            end: statement.end_byte(),
+            text: String::new(),
+        }
+    }
+}
+
+/// Selection without the whitespace around it
+fn trim(source: &str, start: usize, end: usize) -> (usize, usize) {
+    let selected = &source[start..end];
+    let start = start + selected.len() - selected.trim_start().len();
+    let end = start + selected.trim().len();
+
+    (start, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::Parser;
+
+    use super::{extract_variable, inline_variable};
+    use crate::formatter::Replacement;
+
+    /// Statements of the body of a method in a class with fields
+    fn unit(body: &str) -> String {
+        format!(
+            "class Demo {{\n    int count;\n    int[] items;\n\n    void run(int x) {{\n{body}    }}\n}}\n"
+        )
+    }
+
+    fn refactor(
+        body: &str,
+        refactoring: impl Fn(tree_sitter::Node, &str) -> Option<Vec<Replacement>>,
+    ) -> Option<String> {
+        let source = unit(body);
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_java::LANGUAGE.into())
+            .unwrap();
+        let tree = parser.parse(&source, None).unwrap();
+
+        let mut refactored = source.clone();
+        let mut replacements = refactoring(tree.root_node(), &source)?;
+        replacements.sort_by_key(|replacement| replacement.start);
+        for replacement in replacements.iter().rev() {
+            refactored.replace_range(replacement.start..replacement.end, &replacement.text);
+        }
+        Some(refactored)
+    }
+
+    #[test]
+    fn variables_are_inlined_if_they_keep_their_value() {
+        let cases = [
+            (
+                "        int tmp = x + 1;\n        use(tmp, tmp * 2);\n",
+                Some("        use(x + 1, (x + 1) * 2);\n"),
+            ),
+            (
+                "        int tmp = x;\n        use(tmp);\n        x++;\n",
+                Some("        use(x);\n        x++;\n"),
+            ),
+            (
+                "        int tmp = items[0];\n        use(tmp);\n",
+                Some("        use(items[0]);\n"),
+            ),
+            // Inputs are assigned before a reference
+            (
+                "        int tmp = x;\n        x++;\n        use(tmp);\n",
+                None,
+            ),
+            (
+                "        int tmp = x;\n        while (x > 0) {\n            use(tmp);\n            x--;\n        }\n",
+                None,
+            ),
+            // Lambdas can not capture variables that are assigned
+            (
+                "        x++;\n        int tmp = x;\n        run(() -> use(tmp));\n",
+                None,
+            ),
+            // Fields might be changed by the call
+            (
+                "        int tmp = count;\n        other();\n        use(tmp);\n",
+                None,
+            ),
+            (
+                "        int tmp = x;\n        other();\n",
+                Some("        other();\n"),
+            ),
+        ];
+
+        for (body, expected) in cases {
+            let offset = unit(body).find("tmp").unwrap();
+            assert_eq!(
+                refactor(body, |root, source| inline_variable(root, source, offset)),
+                expected.map(unit),
+                "{body}"
+            );
+        }
+    }
+
+    #[test]
+    fn initializers_with_side_effects_are_evaluated_once_and_in_order() {
+        let cases = [
+            (
+                "        int tmp = next();\n        use(tmp);\n",
+                Some("        use(next());\n"),
+            ),
+            (
+                "        int tmp = next();\n        other();\n",
+                Some("        next();\n        other();\n"),
+            ),
+            ("        int tmp = next() + 1;\n        other();\n", None),
+            ("        int tmp = next();\n        use(tmp, tmp);\n", None),
+            (
+                "        int tmp = next();\n        use(other(), tmp);\n",
+                None,
+            ),
+            (
+                "        int tmp = next();\n        other();\n        use(tmp);\n",
+                None,
+            ),
+            (
+                "        int tmp = next();\n        while (x > 0) {\n            use(tmp);\n        }\n",
+                None,
+            ),
+            (
+                "        int tmp = next();\n        run(() -> use(tmp));\n",
+                None,
+            ),
+            (
+                "        int tmp = next();\n        if (x > 0) use(tmp);\n",
+                None,
+            ),
+        ];
+
+        for (body, expected) in cases {
+            let offset = unit(body).find("tmp").unwrap();
+            assert_eq!(
+                refactor(body, |root, source| inline_variable(root, source, offset)),
+                expected.map(unit),
+                "{body}"
+            );
+        }
+    }
+
+    #[test]
+    fn extracted_variables_keep_the_order_of_evaluation() {
+        let cases = [
+            (
+                "        use(x + 1);\n",
+                "x + 1",
+                Some("        int value = x + 1;\n        use(value);\n"),
+            ),
+            (
+                "        use(x, next());\n",
+                "next()",
+                Some("        var next2 = next();\n        use(x, next2);\n"),
+            ),
+            ("        use(other(), next());\n", "next()", None),
+            ("        use(x++, x + 1);\n", "x + 1", None),
+            ("        while (x > 0) use(next());\n", "next()", None),
+        ];
+
+        for (body, selection, expected) in cases {
+            let start = unit(body).find(selection).unwrap();
+            let end = start + selection.len();
+            assert_eq!(
+                refactor(body, |root, source| extract_variable(
+                    root, source, start, end
+                )),
+                expected.map(unit),
+                "{body}"
+            );
+        }
+    }
+}
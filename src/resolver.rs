@@ -0,0 +1,407 @@
+use lsp_types::Position;
+use tree_sitter::Node;
+
+use crate::{
+    calls::{self, METHODS},
+    classfile::JavaType,
+    generate::declarations,
+    imports::{self, Resolution, Scope},
+    locals::{Local, declaration, declared_type, is_reference, locals},
+    source_edit::ancestor,
+    state::State,
+    symbols::{MemberKind, TypeIndex},
+    tree_sitter::{TYPE_DECLARATIONS, collect_members, collect_type_declarations, package_name},
+};
+
+/// Resolves the static types of the expressions in one member of a compilation unit and the
+/// methods that they invoke. Types are fully qualified with nested types separated by `.`,
+/// generic types are erased.
+pub struct Resolver<'a, 'tree> {
+    state: &'a State,
+    root: Node<'tree>,
+    source: &'a str,
+    scope: Scope,
+    types: Vec<(String, Node<'tree>)>,
+    /// The type that declares the member followed by its outer types
+    enclosing: Vec<String>,
+    locals: Vec<Local<'tree>>,
+}
+
+impl<'a, 'tree> Resolver<'a, 'tree> {
+    /// Resolver for the member of a type declaration that contains `node`
+    pub fn new(
+        state: &'a State,
+        root: Node<'tree>,
+        source: &'a str,
+        node: Node<'tree>,
+    ) -> Option<Self> {
+        let package = package_name(root, source.as_bytes()).unwrap_or_default();
+        let types = collect_type_declarations(root, source.as_bytes(), package);
+
+        // Local and anonymous classes are not declarations of the compilation unit
+        let mut enclosing = Vec::new();
+        let mut current = ancestor(node, &TYPE_DECLARATIONS);
+        while let Some(declaration) = current {
+            if let Some((fqdn, _)) = types.iter().find(|(_, node)| *node == declaration) {
+                enclosing.push(fqdn.clone());
+            }
+            current = declaration
+                .parent()
+                .and_then(|parent| ancestor(parent, &TYPE_DECLARATIONS));
+        }
+        if enclosing.is_empty() {
+            return None;
+        }
+
+        let member = ancestor(node, &METHODS)
+            .or_else(|| ancestor(node, &["field_declaration", "static_initializer"]))
+            .unwrap_or(node);
+
+        Some(Resolver {
+            state,
+            root,
+            source,
+            scope: Scope::new(root, source.as_bytes()),
+            types,
+            enclosing,
+            locals: locals(member, source),
+        })
+    }
+
+    /// Type of the sources or the classpath that declares the method that `invocation`
+    /// invokes, chained invocations are resolved through the return types
+    pub fn invoked_type(&self, invocation: Node) -> Option<String> {
+        let name = &self.source[invocation.child_by_field_name("name")?.byte_range()];
+        match invocation.child_by_field_name("object") {
+            None => self
+                .enclosing
+                .iter()
+                .find_map(|fqdn| self.declaring(fqdn, |fqdn| self.declares_method(fqdn, name)))
+                .or_else(|| match self.scope.resolve_member(name, self.state)? {
+                    Resolution::Member(owner, _) => Some(owner),
+                    _ => None,
+                }),
+            Some(object) => self.declaring(&self.receiver(object)?, |fqdn| {
+                self.declares_method(fqdn, name)
+            }),
+        }
+    }
+
+    /// Type whose constructor an object creation or an explicit constructor invocation like
+    /// `this(...)` or `super(...)` invokes
+    pub fn constructed_type(&self, node: Node) -> Option<String> {
+        match node.kind() {
+            "object_creation_expression" => self.expression_type(node),
+            "explicit_constructor_invocation" => {
+                self.receiver(node.child_by_field_name("constructor")?)
+            }
+            _ => None,
+        }
+    }
+
+    /// Static type of `expression`
+    pub fn expression_type(&self, expression: Node) -> Option<String> {
+        match expression.kind() {
+            "this" => self.enclosing.first().cloned(),
+            "identifier" => self.variable_type(expression),
+            "field_access" => {
+                let object = expression.child_by_field_name("object")?;
+                let name = &self.source[expression.child_by_field_name("field")?.byte_range()];
+                let owner = self.declaring(&self.receiver(object)?, |fqdn| {
+                    self.declares_field(fqdn, name)
+                })?;
+                self.member_type(&owner, name, None)
+            }
+            "method_invocation" => {
+                let name = &self.source[expression.child_by_field_name("name")?.byte_range()];
+                let arguments = expression.child_by_field_name("arguments")?;
+                self.member_type(
+                    &self.invoked_type(expression)?,
+                    name,
+                    Some(arguments.named_child_count()),
+                )
+            }
+            "object_creation_expression" | "cast_expression" => {
+                let declared = expression.child_by_field_name("type")?;
+                self.resolve_type(&self.source[declared.byte_range()])
+            }
+            "parenthesized_expression" => self.expression_type(expression.named_child(0)?),
+            "ternary_expression" => {
+                self.expression_type(expression.child_by_field_name("consequence")?)
+            }
+            "assignment_expression" => {
+                self.expression_type(expression.child_by_field_name("left")?)
+            }
+            "string_literal" => Some("java.lang.String".to_string()),
+            _ => None,
+        }
+    }
+
+    /// Type that declares the field or enum constant that the identifier `node` refers to
+    /// or declares, `None` for local variables and other names
+    pub fn field_owner(&self, node: Node) -> Option<String> {
+        let name = &self.source[node.byte_range()];
+        let parent = node.parent()?;
+        match parent.kind() {
+            "field_access" if parent.child_by_field_name("field") == Some(node) => {
+                let object = parent.child_by_field_name("object")?;
+                self.declaring(&self.receiver(object)?, |fqdn| {
+                    self.declares_field(fqdn, name)
+                })
+            }
+            "variable_declarator"
+                if parent.child_by_field_name("name") == Some(node)
+                    && parent.parent().is_some_and(|declaration| {
+                        matches!(
+                            declaration.kind(),
+                            "field_declaration" | "constant_declaration"
+                        )
+                    }) =>
+            {
+                self.enclosing.first().cloned()
+            }
+            "enum_constant" if parent.child_by_field_name("name") == Some(node) => {
+                self.enclosing.first().cloned()
+            }
+            // Other declarations name types, methods and local variables
+            _ if parent.child_by_field_name("name") == Some(node) => None,
+            "scoped_identifier" | "import_declaration" | "package_declaration" => None,
+            _ if is_reference(node) && declaration(&self.locals, node, self.source).is_none() => {
+                self.enclosing
+                    .iter()
+                    .find_map(|fqdn| self.declaring(fqdn, |fqdn| self.declares_field(fqdn, name)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Static type of an object of a field access or an invocation, which can also name a
+    /// type for static members
+    fn receiver(&self, object: Node) -> Option<String> {
+        match object.kind() {
+            "super" => self
+                .state
+                .supertypes(self.enclosing.first()?)
+                .into_iter()
+                .next(),
+            "identifier" if self.is_variable(object) => self.variable_type(object),
+            "identifier" => match self
+                .scope
+                .resolve_type(&self.source[object.byte_range()], self.state)?
+            {
+                Resolution::Type(fqdn) => Some(fqdn),
+                _ => None,
+            },
+            "field_access" => self.expression_type(object).or_else(|| {
+                // Qualified types like `Outer.Inner` or `java.util.List`
+                let field = object.child_by_field_name("field")?;
+                match imports::resolve(field, self.source.as_bytes(), &self.scope, self.state)? {
+                    Resolution::Type(fqdn) => Some(fqdn),
+                    _ => None,
+                }
+            }),
+            _ => self.expression_type(object),
+        }
+    }
+
+    /// Whether the identifier `node` refers to a local variable or a field
+    fn is_variable(&self, node: Node) -> bool {
+        let name = &self.source[node.byte_range()];
+
+        declaration(&self.locals, node, self.source).is_some()
+            || self.enclosing.iter().any(|fqdn| {
+                self.declaring(fqdn, |fqdn| self.declares_field(fqdn, name))
+                    .is_some()
+            })
+    }
+
+    /// Static type of the local variable or field that the identifier `node` refers to,
+    /// variables that are declared with `var` have the type of their initializer
+    fn variable_type(&self, node: Node) -> Option<String> {
+        if let Some(local) = declaration(&self.locals, node, self.source) {
+            let local = &self.locals[local];
+            return match &local.declared_type {
+                Some(declared) => self.resolve_type(declared),
+                None => {
+                    let declarator = local
+                        .node
+                        .parent()
+                        .filter(|parent| parent.kind() == "variable_declarator")?;
+                    self.expression_type(declarator.child_by_field_name("value")?)
+                }
+            };
+        }
+
+        let name = &self.source[node.byte_range()];
+        let owner = self
+            .enclosing
+            .iter()
+            .find_map(|fqdn| self.declaring(fqdn, |fqdn| self.declares_field(fqdn, name)))?;
+        self.member_type(&owner, name, None)
+    }
+
+    /// Resolve a declared type like `List<String>` in this compilation unit, arrays and
+    /// primitive types have no declaration
+    fn resolve_type(&self, declared: &str) -> Option<String> {
+        resolve_type(self.state, &self.scope, declared)
+    }
+
+    /// Type of the field `name` of `owner`, or the return type of its method `name` that
+    /// takes `arguments`
+    fn member_type(&self, owner: &str, name: &str, arguments: Option<usize>) -> Option<String> {
+        if self.types.iter().any(|(fqdn, _)| fqdn == owner) {
+            return member_type(self.state, self.root, self.source, owner, name, arguments);
+        }
+        if let Some(source) = self.state.source(owner) {
+            return self.state.with_document(&source.location.uri, |document| {
+                document.analyze(Position::default(), |root, source, _| {
+                    member_type(self.state, root, source, owner, name, arguments)
+                })
+            });
+        }
+
+        let class = self.state.class(owner)?;
+        let member_type = match arguments {
+            Some(arguments) => {
+                let mut methods = class
+                    .methods
+                    .iter()
+                    .filter(|method| method.name == name)
+                    .collect::<Vec<_>>();
+                // Overloads with another number of parameters are the last resort
+                methods.sort_by_key(|method| method.parameters.len() != arguments);
+                &methods.first()?.return_type
+            }
+            None => {
+                &class
+                    .fields
+                    .iter()
+                    .find(|field| field.name == name)?
+                    .field_type
+            }
+        };
+        match member_type {
+            JavaType::Class(fqdn) => Some(fqdn.replace('$', ".")),
+            _ => None,
+        }
+    }
+
+    fn declares_method(&self, fqdn: &str, name: &str) -> bool {
+        self.declares(fqdn, name, MemberKind::Method)
+    }
+
+    /// Whether `fqdn` declares the field or enum constant `name`
+    fn declares_field(&self, fqdn: &str, name: &str) -> bool {
+        self.declares(fqdn, name, MemberKind::Field)
+            || self.declares(fqdn, name, MemberKind::EnumConstant)
+    }
+
+    fn declares(&self, fqdn: &str, name: &str, kind: MemberKind) -> bool {
+        if let Some(source) = self.state.source(fqdn) {
+            return source
+                .members
+                .iter()
+                .any(|member| member.name == name && member.info.kind == kind);
+        }
+        // Types of this compilation unit that are not indexed yet
+        if let Some((_, declaration)) = self.types.iter().find(|(declared, _)| declared == fqdn) {
+            return declaration
+                .child_by_field_name("body")
+                .map(|body| collect_members(body, self.source.as_bytes()))
+                .unwrap_or_default()
+                .iter()
+                .any(|member| {
+                    member.name == name
+                        && (member.declaration.kind() == "method_declaration")
+                            == (kind == MemberKind::Method)
+                });
+        }
+
+        self.state
+            .member_info(fqdn, name)
+            .is_some_and(|info| info.kind == kind)
+    }
+
+    /// `fqdn` itself or the closest supertype that satisfies `declares`, the superclass is
+    /// searched before the interfaces
+    fn declaring(&self, fqdn: &str, declares: impl Fn(&str) -> bool) -> Option<String> {
+        let mut pending = vec![fqdn.to_string()];
+        let mut visited = Vec::new();
+        while let Some(fqdn) = pending.pop() {
+            if declares(&fqdn) {
+                return Some(fqdn);
+            }
+
+            for supertype in self.state.supertypes(&fqdn).into_iter().rev() {
+                if !visited.contains(&supertype) {
+                    visited.push(supertype.clone());
+                    pending.push(supertype);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// Resolve a declared type like `List<String>` in the compilation unit of `scope`
+fn resolve_type(state: &State, scope: &Scope, declared: &str) -> Option<String> {
+    if declared.ends_with(']') {
+        return None;
+    }
+    let raw = declared.split('<').next()?.trim();
+
+    scope
+        .candidates(raw)
+        .into_iter()
+        .find(|candidate| state.has_type(candidate))
+}
+
+/// Type of a field or return type of a method of `owner` from the compilation unit `root`
+fn member_type(
+    state: &State,
+    root: Node,
+    source: &str,
+    owner: &str,
+    name: &str,
+    arguments: Option<usize>,
+) -> Option<String> {
+    let package = package_name(root, source.as_bytes()).unwrap_or_default();
+    let (_, declaration) = collect_type_declarations(root, source.as_bytes(), package)
+        .into_iter()
+        .find(|(fqdn, _)| fqdn == owner)?;
+    let body = declaration.child_by_field_name("body")?;
+
+    let declared = match arguments {
+        Some(arguments) => {
+            let mut methods = declarations(body)
+                .into_iter()
+                .filter_map(|member| Some((calls::method(member, owner, source)?, member)))
+                .filter(|(method, _)| method.name == name)
+                .collect::<Vec<_>>();
+            methods.sort_by_key(|(method, _)| !method.accepts(arguments));
+            let (_, method) = methods.first()?;
+            declared_type(method.child_by_field_name("type"), None, source)?
+        }
+        None => {
+            let member = collect_members(body, source.as_bytes())
+                .into_iter()
+                .filter(|member| member.declaration.kind() != "method_declaration")
+                .find(|member| member.name == name)?;
+            // Enum constants are instances of their enum
+            if member.declaration.kind() == "enum_constant" {
+                return Some(owner.to_string());
+            }
+            declared_type(
+                member.declaration.child_by_field_name("type"),
+                member
+                    .name_node
+                    .parent()
+                    .and_then(|declarator| declarator.child_by_field_name("dimensions")),
+                source,
+            )?
+        }
+    };
+
+    resolve_type(state, &Scope::new(root, source.as_bytes()), &declared)
+}
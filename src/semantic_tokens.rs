@@ -0,0 +1,726 @@
+use std::borrow::Cow;
+
+use lsp_types::{
+    Range, SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokensEdit,
+    SemanticTokensLegend,
+};
+use ropey::Rope;
+use tree_sitter::Node;
+
+use crate::{
+    imports::{self, Resolution, Scope},
+    position::{self, PositionEncoding},
+    symbols::{MemberInfo, MemberKind, TypeIndex, TypeInfo, TypeKind},
+    tree_sitter::{
+        TYPE_DECLARATIONS, collect_members, has_modifier, is_deprecated, member_info, type_info,
+    },
+};
+
+/// Token types in the order of the legend, see [`TokenType`]
+const TOKEN_TYPES: [SemanticTokenType; 10] = [
+    SemanticTokenType::CLASS,
+    SemanticTokenType::INTERFACE,
+    SemanticTokenType::ENUM,
+    SemanticTokenType::TYPE_PARAMETER,
+    SemanticTokenType::PROPERTY,
+    SemanticTokenType::ENUM_MEMBER,
+    SemanticTokenType::PARAMETER,
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::METHOD,
+    SemanticTokenType::DECORATOR,
+];
+
+/// Token modifiers in the order of the legend, see the `MODIFIER_*` bits
+const TOKEN_MODIFIERS: [SemanticTokenModifier; 5] = [
+    SemanticTokenModifier::DECLARATION,
+    SemanticTokenModifier::STATIC,
+    SemanticTokenModifier::READONLY,
+    SemanticTokenModifier::DEPRECATED,
+    // Local variables and parameters that are assigned after their declaration
+    SemanticTokenModifier::new("mutable"),
+];
+
+const MODIFIER_DECLARATION: u32 = 1 << 0;
+const MODIFIER_STATIC: u32 = 1 << 1;
+const MODIFIER_READONLY: u32 = 1 << 2;
+const MODIFIER_DEPRECATED: u32 = 1 << 3;
+const MODIFIER_MUTABLE: u32 = 1 << 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TokenType {
+    Class,
+    Interface,
+    Enum,
+    TypeParameter,
+    Property,
+    EnumMember,
+    Parameter,
+    Variable,
+    Method,
+    Decorator,
+}
+
+pub fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: TOKEN_TYPES.to_vec(),
+        token_modifiers: TOKEN_MODIFIERS.to_vec(),
+    }
+}
+
+/// Classified name, `start` and `end` are byte indexes
+struct Token {
+    start: usize,
+    end: usize,
+    token_type: TokenType,
+    modifiers: u32,
+}
+
+/// Local variable or parameter
+struct Local {
+    name: String,
+    token_type: TokenType,
+    readonly: bool,
+    mutated: bool,
+}
+
+/// Walks the syntax tree and classifies the names, locals are tracked in scopes while the
+/// names of types and members are resolved through `index`
+struct Highlighter<'a, I: TypeIndex> {
+    source: &'a [u8],
+    scope: &'a Scope,
+    index: &'a I,
+    tokens: Vec<Token>,
+    locals: Vec<Local>,
+    /// Occurrences of locals, their modifiers are known after the whole tree was visited
+    local_tokens: Vec<(Token, usize)>,
+    /// Indexes into `locals` that are visible in the nested blocks
+    visible: Vec<Vec<usize>>,
+    type_parameters: Vec<Vec<String>>,
+    /// Members of the enclosing types, the innermost one is last
+    members: Vec<Vec<(String, MemberInfo)>>,
+}
+
+/// Classify the names in the syntax tree below `root` and encode them relative to each
+/// other as the protocol demands
+pub fn tokens(
+    root: Node,
+    source: &str,
+    content: &Rope,
+    encoding: PositionEncoding,
+    index: &impl TypeIndex,
+) -> Vec<SemanticToken> {
+    let tokens = classify(root, source, index);
+
+    encode(&tokens, content, encoding)
+}
+
+/// Like [`tokens`] but only for the names that start within `range`, the whole tree is
+/// still visited because locals can be declared before the range
+pub fn tokens_in_range(
+    root: Node,
+    source: &str,
+    content: &Rope,
+    encoding: PositionEncoding,
+    range: Range,
+    index: &impl TypeIndex,
+) -> Vec<SemanticToken> {
+    let (Ok(start), Ok(end)) = (
+        position::byte_index(content, range.start, encoding),
+        position::byte_index(content, range.end, encoding),
+    ) else {
+        return Vec::new();
+    };
+    let tokens = classify(root, source, index)
+        .into_iter()
+        .filter(|token| (start..end).contains(&token.start))
+        .collect::<Vec<Token>>();
+
+    encode(&tokens, content, encoding)
+}
+
+fn classify(root: Node, source: &str, index: &impl TypeIndex) -> Vec<Token> {
+    let scope = Scope::new(root, source.as_bytes());
+    let mut highlighter = Highlighter {
+        source: source.as_bytes(),
+        scope: &scope,
+        index,
+        tokens: Vec::new(),
+        locals: Vec::new(),
+        local_tokens: Vec::new(),
+        visible: vec![Vec::new()],
+        type_parameters: Vec::new(),
+        members: Vec::new(),
+    };
+    highlighter.visit(root);
+
+    let Highlighter {
+        mut tokens,
+        locals,
+        local_tokens,
+        ..
+    } = highlighter;
+    tokens.extend(local_tokens.into_iter().map(|(mut token, local)| {
+        if locals[local].readonly {
+            token.modifiers |= MODIFIER_READONLY;
+        }
+        if locals[local].mutated {
+            token.modifiers |= MODIFIER_MUTABLE;
+        }
+        token
+    }));
+    tokens.sort_by_key(|token| token.start);
+    tokens.dedup_by_key(|token| token.start);
+
+    tokens
+}
+
+/// Single edit that turns `previous` into `current`
+pub fn delta(previous: &[SemanticToken], current: &[SemanticToken]) -> SemanticTokensEdit {
+    let prefix = previous
+        .iter()
+        .zip(current)
+        .take_while(|(previous, current)| previous == current)
+        .count();
+    let suffix = previous[prefix..]
+        .iter()
+        .rev()
+        .zip(current[prefix..].iter().rev())
+        .take_while(|(previous, current)| previous == current)
+        .count();
+
+    // Every token consists of five integers
+    SemanticTokensEdit {
+        start: (prefix * 5) as u32,
+        delete_count: ((previous.len() - prefix - suffix) * 5) as u32,
+        data: Some(current[prefix..current.len() - suffix].to_vec()),
+    }
+}
+
+fn encode(tokens: &[Token], content: &Rope, encoding: PositionEncoding) -> Vec<SemanticToken> {
+    let mut encoded = Vec::with_capacity(tokens.len());
+    let (mut line, mut character) = (0, 0);
+
+    for token in tokens {
+        let start = position::position(content, token.start, encoding);
+        let Some(text) = content
+            .get_byte_slice(token.start..token.end)
+            .map(Cow::from)
+        else {
+            continue;
+        };
+        // Tokens must not span multiple lines
+        if text.contains('\n') {
+            continue;
+        }
+
+        encoded.push(SemanticToken {
+            delta_line: start.line - line,
+            delta_start: if start.line == line {
+                start.character - character
+            } else {
+                start.character
+            },
+            length: encoding.len(&text) as u32,
+            token_type: token.token_type as u32,
+            token_modifiers_bitset: token.modifiers,
+        });
+        line = start.line;
+        character = start.character;
+    }
+
+    encoded
+}
+
+impl<I: TypeIndex> Highlighter<'_, I> {
+    fn visit(&mut self, node: Node) {
+        match node.kind() {
+            kind if TYPE_DECLARATIONS.contains(&kind) => self.visit_type_declaration(node),
+            "method_declaration"
+            | "constructor_declaration"
+            | "compact_constructor_declaration" => self.visit_method_declaration(node),
+            "lambda_expression" => {
+                self.visible.push(Vec::new());
+                if let Some(parameters) = node.child_by_field_name("parameters") {
+                    match parameters.kind() {
+                        "identifier" => self.declare(parameters, TokenType::Parameter, false),
+                        "inferred_parameters" => {
+                            let mut cursor = parameters.walk();
+                            for parameter in parameters.named_children(&mut cursor) {
+                                self.declare(parameter, TokenType::Parameter, false);
+                            }
+                        }
+                        _ => self.visit(parameters),
+                    }
+                }
+                if let Some(body) = node.child_by_field_name("body") {
+                    self.visit(body);
+                }
+                self.visible.pop();
+            }
+            "block"
+            | "for_statement"
+            | "catch_clause"
+            | "try_with_resources_statement"
+            | "switch_block_statement_group"
+            | "switch_rule" => {
+                self.visible.push(Vec::new());
+                self.visit_children(node, None);
+                self.visible.pop();
+            }
+            "enhanced_for_statement" => {
+                self.visible.push(Vec::new());
+                let name = node.child_by_field_name("name");
+                if let Some(name) = name {
+                    self.declare(name, TokenType::Variable, has_modifier(node, "final"));
+                }
+                self.visit_children(node, name);
+                self.visible.pop();
+            }
+            "formal_parameter" | "catch_formal_parameter" => {
+                let name = node.child_by_field_name("name");
+                if let Some(name) = name {
+                    self.declare(name, TokenType::Parameter, has_modifier(node, "final"));
+                }
+                self.visit_children(node, name);
+            }
+            "local_variable_declaration" => {
+                let readonly = has_modifier(node, "final");
+                let mut cursor = node.walk();
+                for child in node.named_children(&mut cursor) {
+                    if child.kind() == "variable_declarator" {
+                        self.visit_declarator(child, TokenType::Variable, readonly);
+                    } else {
+                        self.visit(child);
+                    }
+                }
+            }
+            "spread_parameter" => {
+                let mut cursor = node.walk();
+                for child in node.named_children(&mut cursor) {
+                    if child.kind() == "variable_declarator" {
+                        self.visit_declarator(child, TokenType::Parameter, false);
+                    } else {
+                        self.visit(child);
+                    }
+                }
+            }
+            "resource" => {
+                // Resources are implicitly final
+                let name = node.child_by_field_name("name");
+                if let Some(name) = name {
+                    self.declare(name, TokenType::Variable, true);
+                }
+                self.visit_children(node, name);
+            }
+            "field_declaration" | "constant_declaration" => {
+                let mut cursor = node.walk();
+                for child in node.named_children(&mut cursor) {
+                    if child.kind() == "variable_declarator" {
+                        let name = child.child_by_field_name("name");
+                        if let Some(name) = name {
+                            self.classify_member_declaration(name);
+                        }
+                        self.visit_children(child, name);
+                    } else {
+                        self.visit(child);
+                    }
+                }
+            }
+            "enum_constant" => {
+                let name = node.child_by_field_name("name");
+                if let Some(name) = name {
+                    self.classify_member_declaration(name);
+                }
+                self.visit_children(node, name);
+            }
+            "marker_annotation" | "annotation" => {
+                let name = node.child_by_field_name("name");
+                if let Some(name) = name {
+                    // The last segment of a qualified annotation is the name of the type
+                    let name = match name.kind() {
+                        "scoped_identifier" => name.child_by_field_name("name").unwrap_or(name),
+                        _ => name,
+                    };
+                    let deprecated = self
+                        .resolve_type_info(name)
+                        .is_some_and(|info| info.deprecated);
+                    self.push(name, TokenType::Decorator, deprecated_modifier(deprecated));
+                }
+                if let Some(arguments) = node.child_by_field_name("arguments") {
+                    self.visit(arguments);
+                }
+            }
+            "type_parameter" => {
+                let mut cursor = node.walk();
+                for child in node.named_children(&mut cursor) {
+                    if child.kind() == "type_identifier" {
+                        self.push(child, TokenType::TypeParameter, MODIFIER_DECLARATION);
+                    } else {
+                        self.visit(child);
+                    }
+                }
+            }
+            "type_identifier" => self.classify_type(node),
+            "identifier" => self.classify_identifier(node),
+            "package_declaration" | "import_declaration" => {}
+            _ => self.visit_children(node, None),
+        }
+    }
+
+    fn visit_children(&mut self, node: Node, skip: Option<Node>) {
+        let mut cursor = node.walk();
+        for child in node.named_children(&mut cursor) {
+            if Some(child) != skip {
+                self.visit(child);
+            }
+        }
+    }
+
+    fn visit_declarator(&mut self, declarator: Node, token_type: TokenType, readonly: bool) {
+        let name = declarator.child_by_field_name("name");
+        if let Some(name) = name {
+            self.declare(name, token_type, readonly);
+        }
+        self.visit_children(declarator, name);
+    }
+
+    fn visit_type_declaration(&mut self, node: Node) {
+        let info = type_info(node, self.source);
+        let name = node.child_by_field_name("name");
+        if let Some(name) = name {
+            let modifiers = MODIFIER_DECLARATION | deprecated_modifier(info.deprecated);
+            self.push(name, type_token(info.kind), modifiers);
+        }
+
+        let mut members = node
+            .child_by_field_name("body")
+            .map(|body| {
+                collect_members(body, self.source)
+                    .iter()
+                    .map(|member| {
+                        (
+                            member.name.clone(),
+                            member_info(member, info.kind, self.source),
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        // Components of records are fields as well
+        if let Some(parameters) = node.child_by_field_name("parameters") {
+            let mut cursor = parameters.walk();
+            for parameter in parameters.named_children(&mut cursor) {
+                if let Some(name) = parameter
+                    .child_by_field_name("name")
+                    .and_then(|name| name.utf8_text(self.source).ok())
+                {
+                    let info = MemberInfo {
+                        kind: MemberKind::Field,
+                        is_static: false,
+                        deprecated: false,
+                    };
+                    members.push((name.to_string(), info));
+                }
+            }
+        }
+
+        self.members.push(members);
+        self.type_parameters
+            .push(self.collect_type_parameters(node));
+        self.visible.push(Vec::new());
+        self.visit_children(node, name);
+        self.visible.pop();
+        self.type_parameters.pop();
+        self.members.pop();
+    }
+
+    fn visit_method_declaration(&mut self, node: Node) {
+        let name = node.child_by_field_name("name");
+        if let Some(name) = name {
+            let deprecated = deprecated_modifier(is_deprecated(node, self.source));
+            if node.kind() == "method_declaration" {
+                let is_static = if has_modifier(node, "static") {
+                    MODIFIER_STATIC
+                } else {
+                    0
+                };
+                let modifiers = MODIFIER_DECLARATION | is_static | deprecated;
+                self.push(name, TokenType::Method, modifiers);
+            } else {
+                // Constructors are named like their class
+                let modifiers = MODIFIER_DECLARATION | deprecated;
+                self.push(name, TokenType::Class, modifiers);
+            }
+        }
+
+        self.type_parameters
+            .push(self.collect_type_parameters(node));
+        self.visible.push(Vec::new());
+        self.visit_children(node, name);
+        self.visible.pop();
+        self.type_parameters.pop();
+    }
+
+    fn collect_type_parameters(&self, declaration: Node) -> Vec<String> {
+        let Some(parameters) = declaration.child_by_field_name("type_parameters") else {
+            return Vec::new();
+        };
+
+        let mut cursor = parameters.walk();
+        parameters
+            .named_children(&mut cursor)
+            .filter_map(|parameter| {
+                let mut cursor = parameter.walk();
+                parameter
+                    .named_children(&mut cursor)
+                    .find(|child| child.kind() == "type_identifier")
+            })
+            .filter_map(|name| name.utf8_text(self.source).ok())
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Declaration of a field or enum constant of the innermost type
+    fn classify_member_declaration(&mut self, name: Node) {
+        let Ok(text) = name.utf8_text(self.source) else {
+            return;
+        };
+        let info = self
+            .members
+            .last()
+            .and_then(|members| members.iter().find(|(member, _)| member == text))
+            .map(|(_, info)| *info);
+
+        if let Some(info) = info {
+            let readonly = info.kind == MemberKind::EnumConstant
+                || name
+                    .parent()
+                    .and_then(|declarator| declarator.parent())
+                    .is_some_and(|declaration| {
+                        has_modifier(declaration, "final")
+                            || declaration.kind() == "constant_declaration"
+                    });
+            let modifiers = MODIFIER_DECLARATION
+                | member_modifiers(info)
+                | if readonly { MODIFIER_READONLY } else { 0 };
+            self.push(name, member_token(info.kind), modifiers);
+        }
+    }
+
+    fn classify_type(&mut self, node: Node) {
+        let Ok(name) = node.utf8_text(self.source) else {
+            return;
+        };
+        // `var` is parsed as a type
+        if name == "var" {
+            return;
+        }
+
+        if self
+            .type_parameters
+            .iter()
+            .rev()
+            .any(|parameters| parameters.iter().any(|parameter| parameter == name))
+        {
+            self.push(node, TokenType::TypeParameter, 0);
+            return;
+        }
+
+        // Types that can not be resolved are still highlighted as classes
+        let (token_type, modifiers) = self
+            .resolve_type_info(node)
+            .map(|info| (type_token(info.kind), deprecated_modifier(info.deprecated)))
+            .unwrap_or((TokenType::Class, 0));
+        self.push(node, token_type, modifiers);
+    }
+
+    fn classify_identifier(&mut self, node: Node) {
+        let Ok(name) = node.utf8_text(self.source) else {
+            return;
+        };
+        let Some(parent) = node.parent() else {
+            return;
+        };
+
+        match parent.kind() {
+            "method_invocation" if parent.child_by_field_name("name") == Some(node) => {
+                let info = match parent.child_by_field_name("object") {
+                    Some(_) => self.resolve_member_info(node),
+                    None => self
+                        .enclosing_member(name, |kind| kind == MemberKind::Method)
+                        .or_else(|| self.resolve_member_info(node)),
+                };
+                let modifiers = info.map(member_modifiers).unwrap_or(0);
+                self.push(node, TokenType::Method, modifiers);
+                return;
+            }
+            "method_reference" if parent.named_child(0) != Some(node) => {
+                self.push(node, TokenType::Method, 0);
+                return;
+            }
+            "field_access" if parent.child_by_field_name("field") == Some(node) => {
+                let object = parent.child_by_field_name("object");
+                if object.is_some_and(|object| object.kind() == "this") {
+                    let info = self.enclosing_member(name, |kind| kind != MemberKind::Method);
+                    let (token_type, modifiers) = info
+                        .map(|info| (member_token(info.kind), member_modifiers(info)))
+                        .unwrap_or((TokenType::Property, 0));
+                    self.push(node, token_type, modifiers);
+                } else {
+                    self.classify_resolved(node, TokenType::Property);
+                }
+                return;
+            }
+            // Labels and names of the module declaration
+            "labeled_statement" | "break_statement" | "continue_statement"
+            | "scoped_identifier" | "module_declaration" => return,
+            _ => {}
+        }
+
+        if let Some(local) = self.local(name) {
+            let is_assigned = match parent.kind() {
+                "assignment_expression" => parent.child_by_field_name("left") == Some(node),
+                "update_expression" => true,
+                _ => false,
+            };
+            if is_assigned {
+                self.locals[local].mutated = true;
+            }
+            let token = Token {
+                start: node.start_byte(),
+                end: node.end_byte(),
+                token_type: self.locals[local].token_type,
+                modifiers: 0,
+            };
+            self.local_tokens.push((token, local));
+            return;
+        }
+
+        if let Some(info) = self.enclosing_member(name, |kind| kind != MemberKind::Method) {
+            self.push(node, member_token(info.kind), member_modifiers(info));
+            return;
+        }
+
+        self.classify_resolved(node, TokenType::Variable);
+    }
+
+    /// Classify a name through the imports and the index, unresolved names are skipped
+    /// unless a `fallback` is given
+    fn classify_resolved(&mut self, node: Node, fallback: TokenType) {
+        match imports::resolve(node, self.source, self.scope, self.index) {
+            Some(Resolution::Type(fqdn)) => {
+                if let Some(info) = self.index.type_info(&fqdn) {
+                    let modifiers = deprecated_modifier(info.deprecated);
+                    self.push(node, type_token(info.kind), modifiers);
+                }
+            }
+            Some(Resolution::Member(fqdn, member)) => {
+                let info = self.index.member_info(&fqdn, &member);
+                let (token_type, modifiers) = info
+                    .map(|info| (member_token(info.kind), member_modifiers(info)))
+                    .unwrap_or((TokenType::Property, 0));
+                self.push(node, token_type, modifiers);
+            }
+            _ if fallback == TokenType::Property => self.push(node, fallback, 0),
+            _ => {}
+        }
+    }
+
+    fn resolve_type_info(&self, node: Node) -> Option<TypeInfo> {
+        match imports::resolve(node, self.source, self.scope, self.index)? {
+            Resolution::Type(fqdn) => self.index.type_info(&fqdn),
+            _ => None,
+        }
+    }
+
+    fn resolve_member_info(&self, node: Node) -> Option<MemberInfo> {
+        match imports::resolve(node, self.source, self.scope, self.index)? {
+            Resolution::Member(fqdn, member) => self.index.member_info(&fqdn, &member),
+            _ => None,
+        }
+    }
+
+    /// Member of one of the enclosing types, inherited members are not known
+    fn enclosing_member(
+        &self,
+        name: &str,
+        filter: impl Fn(MemberKind) -> bool,
+    ) -> Option<MemberInfo> {
+        self.members.iter().rev().find_map(|members| {
+            members
+                .iter()
+                .find(|(member, info)| member == name && filter(info.kind))
+                .map(|(_, info)| *info)
+        })
+    }
+
+    fn local(&self, name: &str) -> Option<usize> {
+        self.visible
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.iter().rev())
+            .copied()
+            .find(|local| self.locals[*local].name == name)
+    }
+
+    fn declare(&mut self, name: Node, token_type: TokenType, readonly: bool) {
+        let Ok(text) = name.utf8_text(self.source) else {
+            return;
+        };
+
+        let local = self.locals.len();
+        self.locals.push(Local {
+            name: text.to_string(),
+            token_type,
+            readonly,
+            mutated: false,
+        });
+        if let Some(scope) = self.visible.last_mut() {
+            scope.push(local);
+        }
+
+        let token = Token {
+            start: name.start_byte(),
+            end: name.end_byte(),
+            token_type,
+            modifiers: MODIFIER_DECLARATION,
+        };
+        self.local_tokens.push((token, local));
+    }
+
+    fn push(&mut self, node: Node, token_type: TokenType, modifiers: u32) {
+        self.tokens.push(Token {
+            start: node.start_byte(),
+            end: node.end_byte(),
+            token_type,
+            modifiers,
+        });
+    }
+}
+
+fn type_token(kind: TypeKind) -> TokenType {
+    match kind {
+        TypeKind::Class | TypeKind::Record => TokenType::Class,
+        TypeKind::Interface => TokenType::Interface,
+        TypeKind::Enum => TokenType::Enum,
+        TypeKind::Annotation => TokenType::Decorator,
+    }
+}
+
+fn member_token(kind: MemberKind) -> TokenType {
+    match kind {
+        MemberKind::Field => TokenType::Property,
+        MemberKind::Method => TokenType::Method,
+        MemberKind::EnumConstant => TokenType::EnumMember,
+    }
+}
+
+fn member_modifiers(info: MemberInfo) -> u32 {
+    let is_static = if info.is_static { MODIFIER_STATIC } else { 0 };
+
+    is_static | deprecated_modifier(info.deprecated)
+}
+
+fn deprecated_modifier(deprecated: bool) -> u32 {
+    if deprecated { MODIFIER_DEPRECATED } else { 0 }
+}
@@ -0,0 +1,182 @@
+use tree_sitter::Node;
+
+use crate::formatter::Replacement;
+
+/// Nodes that contain a sequence of statements
+pub const BLOCKS: [&str; 3] = ["block", "constructor_body", "switch_block_statement_group"];
+
+/// Whitespace at the start of the line that `node` starts on
+pub fn line_indentation<'a>(node: Node, source: &'a str) -> &'a str {
+    let start = source[..node.start_byte()]
+        .rfind('\n')
+        .map(|line_break| line_break + 1)
+        .unwrap_or(0);
+    let line = &source[start..];
+
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
+
+/// Prefix every line of `text` that is not empty with `indentation`
+pub fn indent(text: &str, indentation: &str) -> String {
+    text.lines()
+        .map(|line| {
+            if line.is_empty() {
+                String::new()
+            } else {
+                format!("{indentation}{line}")
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Move `text` that starts at the indentation `from` to the indentation `to`, lines that are
+/// indented less keep only their relative indentation
+pub fn reindent(text: &str, from: &str, to: &str) -> String {
+    let lines = text
+        .lines()
+        .map(|line| line.strip_prefix(from).unwrap_or(line.trim_start()))
+        .collect::<Vec<&str>>()
+        .join("\n");
+
+    indent(&lines, to)
+}
+
+/// Insert `member` before the first member of the type body `body`, comments like Javadoc
+/// stay with the member they document
+pub fn insert_first_member(
+    body: Node,
+    source: &str,
+    member: &str,
+    unit: &str,
+) -> Option<Replacement> {
+    // Members of enums have to follow the constants
+    let first = body.named_child(0).filter(|_| body.kind() != "enum_body");
+    let Some(first) = first else {
+        return insert_member(body, source, member, unit);
+    };
+    let indentation = line_indentation(first, source);
+
+    Some(Replacement {
+        start: first.start_byte(),
+        end: first.start_byte(),
+        text: format!(
+            "{}\n\n{indentation}",
+            indent(member, indentation).trim_start()
+        ),
+    })
+}
+
+/// Insert `member` after the last member of the type body `body`, it is indented one `unit`
+/// deeper than the type
+pub fn insert_member(body: Node, source: &str, member: &str, unit: &str) -> Option<Replacement> {
+    let indentation = line_indentation(body.parent()?, source);
+    let text = indent(member, &format!("{indentation}{unit}"));
+    let mut cursor = body.walk();
+    let children = body.children(&mut cursor).collect::<Vec<Node>>();
+    let open = children.first().filter(|open| open.kind() == "{")?;
+    let close = children.last().filter(|close| close.kind() == "}")?;
+
+    // Members of enums follow the constants and a semicolon
+    if body.kind() == "enum_body" {
+        if let Some(declarations) = children
+            .iter()
+            .find(|child| child.kind() == "enum_body_declarations")
+        {
+            let last = declarations.child(declarations.child_count().checked_sub(1)?)?;
+            return Some(insert_after(last, format!("\n\n{text}")));
+        }
+        if let Some(last) = children.iter().rev().find(|child| child.is_named()) {
+            return Some(insert_after(*last, format!(";\n\n{text}")));
+        }
+    }
+
+    match children[1..children.len() - 1].last() {
+        Some(last) => Some(insert_after(*last, format!("\n\n{text}"))),
+        None => Some(Replacement {
+            start: open.end_byte(),
+            end: close.start_byte(),
+            text: format!("\n{text}\n{indentation}"),
+        }),
+    }
+}
+
+/// Insert `member` between the members of the type body `body` where `offset` is, or after
+/// the last member if `offset` is inside a member or outside of the body
+pub fn insert_member_at(
+    body: Node,
+    source: &str,
+    offset: usize,
+    member: &str,
+    unit: &str,
+) -> Option<Replacement> {
+    let mut cursor = body.walk();
+    let members = body.named_children(&mut cursor).collect::<Vec<Node>>();
+    if body.kind() == "enum_body"
+        || offset <= body.start_byte()
+        || offset >= body.end_byte()
+        || members
+            .iter()
+            .any(|member| member.start_byte() < offset && offset < member.end_byte())
+    {
+        return insert_member(body, source, member, unit);
+    }
+
+    match members
+        .iter()
+        .rev()
+        .find(|member| member.end_byte() <= offset)
+    {
+        Some(previous) => {
+            let indentation = line_indentation(body.parent()?, source);
+            let text = indent(member, &format!("{indentation}{unit}"));
+            Some(insert_after(*previous, format!("\n\n{text}")))
+        }
+        None => insert_first_member(body, source, member, unit),
+    }
+}
+
+/// Indentation that the members of the type body `body` add to the one of the type
+pub fn member_unit(body: Node, source: &str) -> Option<String> {
+    let member = body.named_child(0)?;
+    let indentation = line_indentation(body.parent()?, source);
+
+    line_indentation(member, source)
+        .strip_prefix(indentation)
+        .filter(|unit| !unit.is_empty())
+        .map(str::to_string)
+}
+
+fn insert_after(node: Node, text: String) -> Replacement {
+    Replacement {
+        start: node.end_byte(),
+        end: node.end_byte(),
+        text,
+    }
+}
+
+/// Statement that contains `node` and is part of a block
+pub fn statement(node: Node) -> Option<Node> {
+    let mut current = node;
+    while let Some(parent) = current.parent() {
+        if BLOCKS.contains(&parent.kind()) {
+            return Some(current);
+        }
+        current = parent;
+    }
+
+    None
+}
+
+/// Closest node of one of `kinds` that contains `node`, including `node` itself
+pub fn ancestor<'tree>(node: Node<'tree>, kinds: &[&str]) -> Option<Node<'tree>> {
+    let mut current = Some(node);
+    while let Some(node) = current {
+        if kinds.contains(&node.kind()) {
+            return Some(node);
+        }
+        current = node.parent();
+    }
+
+    None
+}
@@ -0,0 +1,171 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use lsp_types::{Location, Range, Url};
+use ropey::Rope;
+use tree_sitter::{Node, Parser};
+use walkdir::WalkDir;
+
+use crate::{
+    imports::Scope,
+    position::{self, PositionEncoding},
+    symbols::{MemberInfo, TypeInfo},
+    tree_sitter::{
+        collect_members, collect_type_declarations, member_info, package_name, supertype_nodes,
+        type_info,
+    },
+};
+
+/// Type that is declared in the sources of the project
+pub struct SourceType {
+    /// Location of the name of the type
+    pub location: Location,
+    pub info: TypeInfo,
+    /// Fields, methods and enum constants
+    pub members: Vec<SourceMember>,
+    /// Candidates for each supertype in the order of `Scope::candidates`, the first one that
+    /// exists is the supertype
+    pub supertypes: Vec<Vec<String>>,
+}
+
+pub struct SourceMember {
+    pub name: String,
+    /// Location of the name of the member
+    pub location: Location,
+    pub info: MemberInfo,
+}
+
+/// Find all types that are declared in the java files below `roots`
+pub fn index<P: AsRef<Path>>(
+    roots: &[P],
+    encoding: PositionEncoding,
+) -> HashMap<String, SourceType> {
+    index_files(&java_files(roots), encoding)
+}
+
+/// The java files below `roots`
+pub fn java_files<P: AsRef<Path>>(roots: &[P]) -> Vec<PathBuf> {
+    roots
+        .iter()
+        .flat_map(WalkDir::new)
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry.path().is_file()
+                && entry
+                    .path()
+                    .extension()
+                    .map(|ext| ext == "java")
+                    .unwrap_or(false)
+        })
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+/// Find all types that are declared in `files`
+pub fn index_files(files: &[PathBuf], encoding: PositionEncoding) -> HashMap<String, SourceType> {
+    let mut types = HashMap::new();
+    let mut parser = Parser::new();
+    if parser
+        .set_language(&tree_sitter_java::LANGUAGE.into())
+        .is_err()
+    {
+        return types;
+    }
+
+    for file in files {
+        types.extend(index_file(&mut parser, file, encoding));
+    }
+
+    types
+}
+
+fn index_file(
+    parser: &mut Parser,
+    path: &Path,
+    encoding: PositionEncoding,
+) -> Vec<(String, SourceType)> {
+    let Ok(content) = fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    let Some(tree) = parser.parse(&content, None) else {
+        return Vec::new();
+    };
+    let Ok(uri) = Url::from_file_path(path) else {
+        return Vec::new();
+    };
+
+    let rope = Rope::from_str(&content);
+    let source = content.as_bytes();
+    let root = tree.root_node();
+    let package = package_name(root, source).unwrap_or_default();
+    let scope = Scope::new(root, source);
+    let location = |node: Node| {
+        let range = Range::new(
+            position::position(&rope, node.start_byte(), encoding),
+            position::position(&rope, node.end_byte(), encoding),
+        );
+
+        Location::new(uri.clone(), range)
+    };
+
+    collect_type_declarations(root, source, package)
+        .into_iter()
+        .map(|(name, node)| {
+            let info = type_info(node, source);
+            let members = node
+                .child_by_field_name("body")
+                .map(|body| collect_members(body, source))
+                .unwrap_or_default()
+                .into_iter()
+                .map(|member| SourceMember {
+                    info: member_info(&member, info.kind, source),
+                    name: member.name,
+                    location: location(member.name_node),
+                })
+                .collect();
+            let supertypes = supertypes(node, source, &scope);
+            let node = node.child_by_field_name("name").unwrap_or(node);
+
+            (
+                name,
+                SourceType {
+                    location: location(node),
+                    info,
+                    members,
+                    supertypes,
+                },
+            )
+        })
+        .collect()
+}
+
+/// Candidates for the supertypes of the type `declaration`, including the implicit superclass
+fn supertypes(declaration: Node, source: &[u8], scope: &Scope) -> Vec<Vec<String>> {
+    let mut supertypes = supertype_nodes(declaration)
+        .into_iter()
+        .filter_map(|supertype| {
+            let raw = match supertype.kind() {
+                "generic_type" => supertype.named_child(0)?,
+                _ => supertype,
+            };
+            Some(scope.candidates(raw.utf8_text(source).ok()?))
+        })
+        .collect::<Vec<Vec<String>>>();
+
+    let implicit = match declaration.kind() {
+        "class_declaration" if declaration.child_by_field_name("superclass").is_none() => {
+            Some("java.lang.Object")
+        }
+        "enum_declaration" => Some("java.lang.Enum"),
+        "record_declaration" => Some("java.lang.Record"),
+        _ => None,
+    };
+    if let Some(implicit) = implicit {
+        supertypes.insert(0, vec![implicit.to_string()]);
+    }
+
+    supertypes
+}
@@ -1,29 +1,479 @@
 use crate::{
-    classfile::Classfile, classpath_indexer, compiler::Compiler, document::Document,
+    calls::{self, Call, Method},
+    class_source::{self, SourceFile},
+    classfile::Classfile,
+    classpath_indexer::{self, IndexedJar},
+    compiler::Compiler,
+    configuration::Configuration,
+    document::Document,
     errors::DocumentError,
+    jdk,
+    main_loop::Task,
+    position::PositionEncoding,
+    source_index::{self, SourceType},
+    symbols::{MemberInfo, MemberKind, TypeIndex, TypeInfo, TypeKind},
 };
-use crossbeam_channel::Sender;
+use crossbeam_channel::{Receiver, Sender};
+use jclassfile::{class_file::ClassFlags, fields::FieldFlags, methods::MethodFlags};
 use lsp_server::Message;
-use lsp_types::{Range, Url};
-use std::collections::HashMap;
+use lsp_types::{Location, Position, Range, TextDocumentContentChangeEvent, Url};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fs,
+    path::PathBuf,
+    rc::Rc,
+    thread,
+    time::SystemTime,
+};
 
 pub struct State {
     documents: HashMap<String, Document>,
     classes: HashMap<String, Classfile>,
+    /// Classes of the classpath and the JDK that can be imported by their simple name
+    imported_names: HashMap<String, Vec<String>>,
+    jars: HashMap<PathBuf, IndexedJar>,
+    /// Types that are declared in the sources of the project
+    sources: HashMap<String, SourceType>,
+    /// Files that annotation processors generated with the time they were modified when they
+    /// were indexed
+    generated: HashMap<PathBuf, SystemTime>,
+    /// Direct subtypes of the types of the sources and the classpath, nested types are
+    /// separated by `.`
+    subtypes: HashMap<String, Vec<String>>,
+    /// Invocations of methods of the sources in the files of the sources and the open
+    /// documents
+    calls: HashMap<Url, Vec<Call>>,
+    /// Files whose invocations changed since they were indexed
+    stale_calls: HashSet<Url>,
+    /// Source files that are not open, they are parsed again once they are modified
+    files: RefCell<HashMap<Url, (SystemTime, Rc<Document>)>>,
+    configuration: Configuration,
+    reload: ReloadState,
+    pub encoding: PositionEncoding,
+    /// The client can show annotated edits of refactorings for review before it applies them
+    pub change_annotations: bool,
     pub compiler: Compiler,
     pub sender: Sender<Message>,
+    task_sender: Sender<Task>,
+    task_receiver: Receiver<Task>,
+}
+
+#[derive(PartialEq)]
+enum ReloadState {
+    Idle,
+    Running,
+    /// Build files changed again while the project was reloaded
+    Outdated,
 }
 
 impl State {
-    pub fn new(sender: Sender<Message>, compiler: Compiler) -> Self {
-        State {
+    pub fn new(
+        sender: Sender<Message>,
+        compiler: Compiler,
+        configuration: Configuration,
+        encoding: PositionEncoding,
+        change_annotations: bool,
+    ) -> Self {
+        let (task_sender, task_receiver) = crossbeam_channel::unbounded();
+        let mut state = State {
             documents: HashMap::new(),
-            classes: classpath_indexer::index(compiler.classpath()),
+            classes: HashMap::new(),
+            imported_names: HashMap::new(),
+            jars: HashMap::new(),
+            sources: HashMap::new(),
+            generated: HashMap::new(),
+            subtypes: HashMap::new(),
+            calls: HashMap::new(),
+            stale_calls: HashSet::new(),
+            files: RefCell::new(HashMap::new()),
+            configuration,
+            reload: ReloadState::Idle,
+            encoding,
+            change_annotations,
             compiler,
             sender,
+            task_sender,
+            task_receiver,
+        };
+        state.index_classpath();
+        state.index_sources();
+
+        state
+    }
+
+    /// Declaration of a type from the sources of the project
+    pub fn source(&self, fqdn: &str) -> Option<&SourceType> {
+        self.sources.get(fqdn)
+    }
+
+    /// Class from the classpath or the JDK, nested classes can be separated by `.` or `$`
+    pub fn class(&self, fqdn: &str) -> Option<&Classfile> {
+        let mut name = fqdn.to_string();
+        loop {
+            if let Some(class) = self.classes.get(&name) {
+                return Some(class);
+            }
+
+            let separator = name.rfind('.')?;
+            name.replace_range(separator..separator + 1, "$");
+        }
+    }
+
+    /// Location of a class from the classpath or the JDK, or of its `member`, in the source
+    /// file of its top level class. The source is extracted from the sources of the jar or
+    /// the JDK, without them it is a stub that is generated from the class files.
+    pub fn class_location(&self, fqdn: &str, member: Option<&str>) -> Option<Location> {
+        let class = self.class(fqdn)?;
+        let (archive, outer, nested) = self.class_origin(class)?;
+        // Sources that lack the class are replaced by a stub
+        let path = class_source::source_file(archive, outer, &nested)?
+            .write()
+            .or_else(|| class_source::stub_file(archive, outer, &nested)?.write())?;
+        let range = class_source::declaration_range(
+            &path,
+            &class.fqdn.replace('$', "."),
+            member,
+            self.encoding,
+        )
+        .unwrap_or_default();
+
+        Some(Location::new(Url::from_file_path(path).ok()?, range))
+    }
+
+    /// Locations of classes for the items of lists, the files of [`State::class_location`]
+    /// are written in the background and the ranges are the start of the files
+    pub fn class_item_locations(&self, fqdns: &[&str]) -> Vec<Option<Location>> {
+        let mut pending = HashMap::new();
+        let locations = fqdns
+            .iter()
+            .map(|fqdn| {
+                let (archive, outer, nested) = self.class_origin(self.class(fqdn)?)?;
+                let file = class_source::source_file(archive, outer, &nested)?;
+                let uri = Url::from_file_path(file.path()).ok()?;
+                if !matches!(file, SourceFile::Written(_)) {
+                    pending.insert(file.path().to_path_buf(), file);
+                }
+                Some(Location::new(uri, Range::default()))
+            })
+            .collect();
+
+        if !pending.is_empty() {
+            thread::spawn(move || {
+                for file in pending.into_values() {
+                    file.write();
+                }
+            });
+        }
+        locations
+    }
+
+    /// Archive of `class` with the top level class that contains it and all of its nested
+    /// classes
+    fn class_origin(&self, class: &Classfile) -> Option<(&PathBuf, &Classfile, Vec<&Classfile>)> {
+        let outer = class.fqdn.split('$').next()?;
+        let (archive, indexed) = self
+            .jars
+            .iter()
+            .find(|(_, indexed)| indexed.classes.contains(&class.fqdn))?;
+        let nested = indexed
+            .classes
+            .iter()
+            .filter(|nested| nested.starts_with(&format!("{outer}$")))
+            .filter_map(|nested| self.classes.get(nested))
+            .collect::<Vec<&Classfile>>();
+
+        Some((archive, self.classes.get(outer)?, nested))
+    }
+
+    /// Index the types of all source roots of the project
+    pub fn index_sources(&mut self) {
+        self.sources = source_index::index(&self.compiler.source_roots(), self.encoding);
+        // Generated types are indexed again after the next compilation
+        self.generated.clear();
+        self.index_hierarchy();
+        self.index_calls();
+    }
+
+    /// Index the types that annotation processors generated during the last compilation,
+    /// only files that were added, modified or deleted since they were indexed are read
+    pub fn index_generated_sources(&mut self) {
+        let files = source_index::java_files(&self.compiler.generated_sources())
+            .into_iter()
+            .filter_map(|path| {
+                let modified = fs::metadata(&path)
+                    .and_then(|metadata| metadata.modified())
+                    .ok()?;
+                Some((path, modified))
+            })
+            .collect::<HashMap<PathBuf, SystemTime>>();
+        let changed = self
+            .generated
+            .keys()
+            .chain(files.keys())
+            .filter(|path| self.generated.get(*path) != files.get(*path))
+            .cloned()
+            .collect::<HashSet<PathBuf>>();
+        if changed.is_empty() {
+            return;
+        }
+
+        // Types of changed files are replaced, the ones of deleted files are removed
+        let uris = changed
+            .iter()
+            .filter_map(|path| Url::from_file_path(path).ok())
+            .collect::<HashSet<Url>>();
+        let outdated = self
+            .sources
+            .iter()
+            .filter(|(_, source)| uris.contains(&source.location.uri))
+            .map(|(fqdn, _)| fqdn.clone())
+            .collect::<Vec<String>>();
+        // Only the generated types change their place in the hierarchy
+        for fqdn in &outdated {
+            self.unlink_subtype(fqdn);
+            self.sources.remove(fqdn);
+        }
+
+        let modified = changed
+            .into_iter()
+            .filter(|path| files.contains_key(path))
+            .collect::<Vec<PathBuf>>();
+        let types = source_index::index_files(&modified, self.encoding);
+        let generated = types.keys().cloned().collect::<Vec<String>>();
+        self.sources.extend(types);
+        for fqdn in &generated {
+            self.link_subtype(fqdn);
+        }
+
+        self.generated = files;
+    }
+
+    /// Direct superclass and interfaces of a type from the sources or the classpath
+    pub fn supertypes(&self, fqdn: &str) -> Vec<String> {
+        if let Some(source) = self.source(fqdn) {
+            return source
+                .supertypes
+                .iter()
+                .filter_map(|candidates| {
+                    candidates
+                        .iter()
+                        .find(|candidate| self.has_type(candidate))
+                        .cloned()
+                })
+                .collect();
+        }
+
+        self.class(fqdn)
+            .map(|class| {
+                class
+                    .supertypes()
+                    .into_iter()
+                    .map(|supertype| supertype.fqdn.replace('$', "."))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Direct subtypes of a type from the sources or the classpath
+    pub fn subtypes(&self, fqdn: &str) -> &[String] {
+        self.subtypes
+            .get(&fqdn.replace('$', "."))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Collect the subtypes of all types by reversing the supertypes of the sources and the
+    /// classes. Everything extends `Object`, so it has no subtypes in the index.
+    fn index_hierarchy(&mut self) {
+        let mut subtypes = HashMap::<String, Vec<String>>::new();
+        // Anonymous and local classes are named like `Outer$1` or `Outer$1Local`
+        let types = self
+            .classes
+            .keys()
+            .filter(|fqdn| {
+                !fqdn
+                    .split('$')
+                    .skip(1)
+                    .any(|nested| nested.starts_with(|c: char| c.is_ascii_digit()))
+            })
+            .map(|fqdn| fqdn.replace('$', "."))
+            .chain(self.sources.keys().cloned());
+
+        for fqdn in types {
+            for supertype in self.supertypes(&fqdn) {
+                if supertype != "java.lang.Object" {
+                    subtypes.entry(supertype).or_default().push(fqdn.clone());
+                }
+            }
+        }
+        for types in subtypes.values_mut() {
+            types.sort();
+            types.dedup();
+        }
+
+        self.subtypes = subtypes;
+    }
+
+    /// Add `fqdn` to the subtypes of its supertypes
+    fn link_subtype(&mut self, fqdn: &str) {
+        for supertype in self.supertypes(fqdn) {
+            if supertype != "java.lang.Object" {
+                let subtypes = self.subtypes.entry(supertype).or_default();
+                if let Err(index) = subtypes.binary_search_by(|subtype| subtype.as_str().cmp(fqdn))
+                {
+                    subtypes.insert(index, fqdn.to_string());
+                }
+            }
         }
     }
 
+    /// Remove `fqdn` from the subtypes of its supertypes
+    fn unlink_subtype(&mut self, fqdn: &str) {
+        for supertype in self.supertypes(fqdn) {
+            if let Some(subtypes) = self.subtypes.get_mut(&supertype) {
+                subtypes.retain(|subtype| subtype != fqdn);
+            }
+        }
+    }
+
+    /// Invocations of `method` in the whole project together with the file they are in, files
+    /// that changed have to be indexed with [`State::index_stale_calls`] before
+    pub fn incoming_calls(&self, method: &Method) -> Vec<(&Url, &Call)> {
+        self.calls
+            .iter()
+            .flat_map(|(uri, calls)| calls.iter().map(move |call| (uri, call)))
+            .filter(|(_, call)| call.invocation.invokes(method))
+            .collect()
+    }
+
+    /// Index the invocations of the files that changed since the last call hierarchy
+    pub fn index_stale_calls(&mut self) {
+        for uri in std::mem::take(&mut self.stale_calls) {
+            match self.file_calls(&uri) {
+                Some(calls) => self.calls.insert(uri, calls),
+                None => self.calls.remove(&uri),
+            };
+        }
+    }
+
+    /// Mark the invocations of all files of the sources as stale, open documents take the
+    /// place of their files
+    fn index_calls(&mut self) {
+        self.calls.clear();
+        self.stale_calls = self
+            .sources
+            .values()
+            .map(|source| &source.location.uri)
+            .chain(self.documents.values().map(|document| &document.uri))
+            .cloned()
+            .collect();
+    }
+
+    /// The invocations of the document or file at `uri` are indexed again when they are
+    /// needed, edits do not pay for the resolution of all invocations
+    fn update_calls(&mut self, uri: &Url) {
+        self.stale_calls.insert(uri.clone());
+    }
+
+    fn file_calls(&self, uri: &Url) -> Option<Vec<Call>> {
+        self.with_document(uri, |document| {
+            document.analyze(Position::default(), |root, source, _| {
+                Some(calls::calls(self, root, source))
+            })
+        })
+    }
+
+    pub fn task_receiver(&self) -> Receiver<Task> {
+        self.task_receiver.clone()
+    }
+
+    /// Determine the project structure and classpath again in the background.
+    ///
+    /// The result is sent as [`Task::ProjectReloaded`].
+    pub fn reload_project(&mut self) {
+        if self.reload != ReloadState::Idle {
+            self.reload = ReloadState::Outdated;
+            return;
+        }
+
+        self.reload = ReloadState::Running;
+        let configuration = self.configuration.clone();
+        let sender = self.task_sender.clone();
+        thread::spawn(move || {
+            let _ = sender.send(Task::ProjectReloaded(Compiler::new(&configuration)));
+        });
+    }
+
+    /// Mark the running reload as done, starts another one if the project changed meanwhile
+    pub fn finish_reload(&mut self) {
+        let outdated = self.reload == ReloadState::Outdated;
+        self.reload = ReloadState::Idle;
+
+        if outdated {
+            self.reload_project();
+        }
+    }
+
+    /// Bring the class index in line with the classpath of the compiler and the JDK, only jars
+    /// that were added or modified since they were indexed are read.
+    pub fn index_classpath(&mut self) {
+        let mut jars = self
+            .compiler
+            .classpath()
+            .into_iter()
+            .filter(|path| classpath_indexer::is_jar(path))
+            .collect::<Vec<PathBuf>>();
+        jars.extend(jdk::modules());
+
+        let outdated = self
+            .jars
+            .iter()
+            .filter(|(path, indexed)| {
+                !jars.contains(path) || indexed.modified != classpath_indexer::modified(path)
+            })
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<PathBuf>>();
+        for path in outdated {
+            if let Some(indexed) = self.jars.remove(&path) {
+                for class in indexed.classes {
+                    if let Some(class) = self.classes.remove(&class)
+                        && let Some(name) = imported_name(&class)
+                        && let Some(types) = self.imported_names.get_mut(name)
+                    {
+                        types.retain(|fqdn| *fqdn != class.fqdn);
+                    }
+                }
+            }
+        }
+
+        for path in jars {
+            if self.jars.contains_key(&path) {
+                continue;
+            }
+
+            let classes = classpath_indexer::index(&path);
+            self.jars.insert(
+                path.clone(),
+                IndexedJar {
+                    modified: classpath_indexer::modified(&path),
+                    classes: classes.iter().map(|class| class.fqdn.clone()).collect(),
+                },
+            );
+            for class in classes {
+                if let Some(name) = imported_name(&class) {
+                    self.imported_names
+                        .entry(name.to_string())
+                        .or_default()
+                        .push(class.fqdn.clone());
+                }
+                self.classes.insert(class.fqdn.clone(), class);
+            }
+        }
+
+        self.index_hierarchy();
+    }
+
     pub fn documents_uri(&mut self) -> Vec<&Url> {
         self.documents
             .values()
@@ -31,41 +481,249 @@ impl State {
             .collect()
     }
 
-    pub fn document(&mut self, uri: &str) -> Option<&mut Document> {
+    pub fn document(&self, uri: &str) -> Option<&Document> {
+        self.documents.get(uri)
+    }
+
+    /// Run `f` on the document at `uri`, documents that are not open are read from disk and
+    /// kept until the file is modified
+    pub fn with_document<T>(&self, uri: &Url, f: impl FnOnce(&Document) -> Option<T>) -> Option<T> {
+        if let Some(document) = self.document(uri.as_str()) {
+            return f(document);
+        }
+
+        let path = uri.to_file_path().ok()?;
+        let modified = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .ok()?;
+        let cached = self
+            .files
+            .borrow()
+            .get(uri)
+            .filter(|(parsed, _)| *parsed == modified)
+            .map(|(_, document)| Rc::clone(document));
+        // The cache is not borrowed while `f` runs, it can read other files
+        let document = match cached {
+            Some(document) => document,
+            None => {
+                let content = fs::read_to_string(&path).ok()?;
+                let document =
+                    Rc::new(Document::new(uri.clone(), 0, &content, self.encoding).ok()?);
+                self.files
+                    .borrow_mut()
+                    .insert(uri.clone(), (modified, Rc::clone(&document)));
+                document
+            }
+        };
+
+        f(&document)
+    }
+
+    pub fn configuration(&self) -> &Configuration {
+        &self.configuration
+    }
+
+    pub fn document_mut(&mut self, uri: &str) -> Option<&mut Document> {
         self.documents.get_mut(uri)
     }
 
-    pub fn register_document(&mut self, uri: Url, content: &str) -> Result<(), DocumentError> {
+    /// Versions of the documents that are in sync with the client and saved. javac compiles
+    /// the files on disk, so its diagnostics do not belong to versions with unsaved edits.
+    pub fn document_versions(&self) -> HashMap<Url, i32> {
         self.documents
-            .insert(uri.to_string(), Document::new(uri, content)?);
+            .values()
+            .filter(|document| !document.is_dirty() && document.is_saved())
+            .map(|document| (document.uri.clone(), document.version()))
+            .collect()
+    }
+
+    pub fn register_document(
+        &mut self,
+        uri: Url,
+        version: i32,
+        content: &str,
+    ) -> Result<(), DocumentError> {
+        self.documents.insert(
+            uri.to_string(),
+            Document::new(uri.clone(), version, content, self.encoding)?,
+        );
+        self.update_calls(&uri);
 
         Ok(())
     }
 
+    /// Apply the changes of one `didChange` notification, they all belong to `version`
     pub fn update_document(
         &mut self,
         uri: Url,
         version: i32,
-        range: Option<&Range>,
-        text: &str,
+        changes: &[TextDocumentContentChangeEvent],
     ) -> Result<(), DocumentError> {
+        let Some(document) = self.documents.get_mut(uri.as_str()) else {
+            return Ok(());
+        };
+
         // Ignore change requests that are older than the internal state
-        if let Some(document) = self.documents.get_mut(uri.as_str())
-            && document.should_update(version)
-        {
-            if let Some(range) = range {
-                document.update(range.start, range.end, text)?;
+        if !document.should_update(version) {
+            eprintln!(
+                "Ignoring stale change of {uri}, version {version} is not newer than {}",
+                document.version()
+            );
+            return Ok(());
+        }
+
+        if version > document.version() + 1 {
+            eprintln!(
+                "Missed changes of {uri} between version {} and {version}",
+                document.version()
+            );
+            document.mark_dirty();
+        }
+
+        // Edits of a document that is out of sync would apply to the wrong content, only a
+        // change of the whole content or saving the document brings it back in sync
+        let changes = if document.is_dirty() {
+            match changes.iter().rposition(|change| change.range.is_none()) {
+                Some(replacement) => &changes[replacement..],
+                None => {
+                    eprintln!("Dropping changes of {uri} until it is in sync again");
+                    // Saving brings back the content of this version
+                    document.set_version(version);
+                    return Ok(());
+                }
+            }
+        } else {
+            changes
+        };
+
+        for change in changes {
+            let result = if let Some(range) = change.range {
+                document.update(range.start, range.end, &change.text)
             } else {
                 // If range is none then just replace the whole document
-                self.documents
-                    .insert(uri.to_string(), Document::new(uri, text)?);
+                document.replace(&change.text)
+            };
+
+            if let Err(error) = result {
+                document.mark_dirty();
+                return Err(error);
             }
         }
+        document.set_version(version);
+        self.update_calls(&uri);
+
+        Ok(())
+    }
+
+    /// Read a dirty document from disk again, once it is saved the disk has the content the
+    /// client has
+    pub fn resync_document(&mut self, uri: &Url) -> Result<(), DocumentError> {
+        if let Some(document) = self.documents.get_mut(uri.as_str())
+            && document.is_dirty()
+            && let Ok(path) = uri.to_file_path()
+            && let Ok(content) = fs::read_to_string(path)
+        {
+            document.replace(&content)?;
+            self.update_calls(uri);
+        }
 
         Ok(())
     }
 
     pub fn unregister_document(&mut self, uri: Url) {
         self.documents.remove(&uri.to_string());
+        // The file on disk has the content that was saved last
+        self.update_calls(&uri);
+    }
+}
+
+impl TypeIndex for State {
+    fn type_info(&self, fqdn: &str) -> Option<TypeInfo> {
+        if let Some(source) = self.source(fqdn) {
+            return Some(source.info);
+        }
+
+        let class = self.class(fqdn)?;
+        let kind = if class.flags.contains(ClassFlags::ACC_ANNOTATION) {
+            TypeKind::Annotation
+        } else if class.flags.contains(ClassFlags::ACC_INTERFACE) {
+            TypeKind::Interface
+        } else if class.flags.contains(ClassFlags::ACC_ENUM) {
+            TypeKind::Enum
+        } else {
+            TypeKind::Class
+        };
+
+        Some(TypeInfo {
+            kind,
+            deprecated: class.deprecated,
+        })
     }
+
+    fn find_types(&self, name: &str) -> Vec<String> {
+        let mut types = self
+            .sources
+            .keys()
+            .filter(|fqdn| fqdn.rsplit('.').next() == Some(name))
+            .cloned()
+            .collect::<Vec<String>>();
+
+        types.extend(
+            self.imported_names
+                .get(name)
+                .into_iter()
+                .flatten()
+                .map(|fqdn| fqdn.replace('$', ".")),
+        );
+        types.sort();
+        types.dedup();
+
+        types
+    }
+
+    fn member_info(&self, fqdn: &str, member: &str) -> Option<MemberInfo> {
+        if let Some(source) = self.source(fqdn) {
+            return source
+                .members
+                .iter()
+                .find(|source_member| source_member.name == member)
+                .map(|source_member| source_member.info);
+        }
+
+        let class = self.class(fqdn)?;
+        if let Some(field) = class.fields.iter().find(|field| field.name == member) {
+            return Some(MemberInfo {
+                kind: if field.flags.contains(FieldFlags::ACC_ENUM) {
+                    MemberKind::EnumConstant
+                } else {
+                    MemberKind::Field
+                },
+                is_static: field.flags.contains(FieldFlags::ACC_STATIC),
+                deprecated: field.deprecated,
+            });
+        }
+
+        class
+            .methods
+            .iter()
+            .find(|method| method.name == member)
+            .map(|method| MemberInfo {
+                kind: MemberKind::Method,
+                is_static: method.flags.contains(MethodFlags::ACC_STATIC),
+                deprecated: method.deprecated,
+            })
+    }
+}
+
+/// Simple name of `class` if it can be imported, anonymous and local classes are named like
+/// `Outer$1` or `Outer$1Local`
+fn imported_name(class: &Classfile) -> Option<&str> {
+    let name = class.fqdn.rsplit(['.', '$']).next()?;
+    let is_local = class
+        .fqdn
+        .split('$')
+        .skip(1)
+        .any(|nested| nested.starts_with(|c: char| c.is_ascii_digit()));
+
+    (class.flags.contains(ClassFlags::ACC_PUBLIC) && !is_local).then_some(name)
 }
@@ -0,0 +1,47 @@
+/// Types and their members that names can be resolved to
+pub trait TypeIndex {
+    /// Look up a type, nested types are separated by `.` from their outer type
+    fn type_info(&self, fqdn: &str) -> Option<TypeInfo>;
+    /// Look up a field, method or enum constant that is declared by a type
+    fn member_info(&self, fqdn: &str, member: &str) -> Option<MemberInfo>;
+
+    /// Fully qualified names of the importable types with the simple name `name`
+    fn find_types(&self, name: &str) -> Vec<String>;
+
+    fn has_type(&self, fqdn: &str) -> bool {
+        self.type_info(fqdn).is_some()
+    }
+
+    fn has_member(&self, fqdn: &str, member: &str) -> bool {
+        self.member_info(fqdn, member).is_some()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TypeKind {
+    Class,
+    Interface,
+    Enum,
+    Record,
+    Annotation,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MemberKind {
+    Field,
+    Method,
+    EnumConstant,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TypeInfo {
+    pub kind: TypeKind,
+    pub deprecated: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MemberInfo {
+    pub kind: MemberKind,
+    pub is_static: bool,
+    pub deprecated: bool,
+}
@@ -1,22 +1,38 @@
 use tree_sitter::{Node, Point, TreeCursor};
 
-// TODO: Handle wildcards
-pub fn collect_imports(mut cursor: TreeCursor) -> Vec<Node> {
-    let mut imports = Vec::new();
+use crate::symbols::{MemberInfo, MemberKind, TypeInfo, TypeKind};
 
-    loop {
-        let node = cursor.node();
+/// Import declaration of a compilation unit
+pub struct Import {
+    /// Imported name without the trailing `.*`
+    pub path: String,
+    pub is_static: bool,
+    /// Whether all types (or static members) of `path` are imported
+    pub on_demand: bool,
+}
 
-        if node.kind() == "import_declaration" && node.child_count() > 1 {
-            imports.push(node.child(1).unwrap());
-        };
+pub fn collect_imports(root: Node, source: &[u8]) -> Vec<Import> {
+    let mut cursor = root.walk();
 
-        if !cursor.goto_next_sibling() && !cursor.goto_first_child() {
-            break;
-        }
-    }
+    root.children(&mut cursor)
+        .filter(|node| node.kind() == "import_declaration")
+        .filter_map(|node| import(node, source))
+        .collect()
+}
+
+/// Import that the `import_declaration` node declares
+pub fn import(declaration: Node, source: &[u8]) -> Option<Import> {
+    let mut cursor = declaration.walk();
+    let children = declaration.children(&mut cursor).collect::<Vec<Node>>();
+    let path = children
+        .iter()
+        .find(|child| matches!(child.kind(), "identifier" | "scoped_identifier"))?;
 
-    imports
+    Some(Import {
+        path: path.utf8_text(source).ok()?.to_string(),
+        is_static: children.iter().any(|child| child.kind() == "static"),
+        on_demand: children.iter().any(|child| child.kind() == "asterisk"),
+    })
 }
 
 pub fn find_node_by_point(mut cursor: TreeCursor, point: Point) -> Node {
@@ -24,3 +40,204 @@ pub fn find_node_by_point(mut cursor: TreeCursor, point: Point) -> Node {
 
     cursor.node()
 }
+
+/// Kinds of nodes that declare a type
+pub const TYPE_DECLARATIONS: [&str; 5] = [
+    "class_declaration",
+    "interface_declaration",
+    "enum_declaration",
+    "record_declaration",
+    "annotation_type_declaration",
+];
+
+/// Name of the package that is declared in the compilation unit
+pub fn package_name<'a>(root: Node, source: &'a [u8]) -> Option<&'a str> {
+    let mut cursor = root.walk();
+    let package = root
+        .named_children(&mut cursor)
+        .find(|node| node.kind() == "package_declaration")?;
+    let mut cursor = package.walk();
+    let name = package
+        .named_children(&mut cursor)
+        .find(|node| matches!(node.kind(), "identifier" | "scoped_identifier"))?;
+
+    name.utf8_text(source).ok()
+}
+
+/// Collect all type declarations below `node` (including nested ones) together with their
+/// name qualified by `prefix`
+pub fn collect_type_declarations<'tree>(
+    node: Node<'tree>,
+    source: &[u8],
+    prefix: &str,
+) -> Vec<(String, Node<'tree>)> {
+    let mut types = Vec::new();
+    let mut cursor = node.walk();
+
+    for child in node.named_children(&mut cursor) {
+        if child.kind() == "enum_body_declarations" {
+            types.extend(collect_type_declarations(child, source, prefix));
+        } else if TYPE_DECLARATIONS.contains(&child.kind())
+            && let Some(name) = child
+                .child_by_field_name("name")
+                .and_then(|name| name.utf8_text(source).ok())
+        {
+            let qualified_name = if prefix.is_empty() {
+                name.to_string()
+            } else {
+                format!("{prefix}.{name}")
+            };
+
+            if let Some(body) = child.child_by_field_name("body") {
+                types.extend(collect_type_declarations(body, source, &qualified_name));
+            }
+            types.push((qualified_name, child));
+        }
+    }
+
+    types
+}
+
+/// Field, method or enum constant that is declared in the body of a type
+pub struct Member<'tree> {
+    pub name: String,
+    pub name_node: Node<'tree>,
+    /// Node that carries the modifiers, for fields this is the whole field declaration
+    pub declaration: Node<'tree>,
+}
+
+/// Collect the fields, methods and enum constants that are declared in the body of a type
+pub fn collect_members<'tree>(body: Node<'tree>, source: &[u8]) -> Vec<Member<'tree>> {
+    let mut members = Vec::new();
+    let mut cursor = body.walk();
+
+    for child in body.named_children(&mut cursor) {
+        match child.kind() {
+            "enum_body_declarations" => members.extend(collect_members(child, source)),
+            "method_declaration" | "enum_constant" | "annotation_type_element_declaration" => {
+                members.extend(member(child, child, source));
+            }
+            "field_declaration" | "constant_declaration" => {
+                let mut cursor = child.walk();
+                for declarator in child.children_by_field_name("declarator", &mut cursor) {
+                    members.extend(member(declarator, child, source));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    members
+}
+
+fn member<'tree>(
+    named: Node<'tree>,
+    declaration: Node<'tree>,
+    source: &[u8],
+) -> Option<Member<'tree>> {
+    let name_node = named.child_by_field_name("name")?;
+
+    Some(Member {
+        name: name_node.utf8_text(source).ok()?.to_string(),
+        name_node,
+        declaration,
+    })
+}
+
+/// Kind and deprecation of the type that `declaration` declares
+pub fn type_info(declaration: Node, source: &[u8]) -> TypeInfo {
+    let kind = match declaration.kind() {
+        "interface_declaration" => TypeKind::Interface,
+        "enum_declaration" => TypeKind::Enum,
+        "record_declaration" => TypeKind::Record,
+        "annotation_type_declaration" => TypeKind::Annotation,
+        _ => TypeKind::Class,
+    };
+
+    TypeInfo {
+        kind,
+        deprecated: is_deprecated(declaration, source),
+    }
+}
+
+/// Kind, modifiers and deprecation of a member of a type of kind `owner`
+pub fn member_info(member: &Member, owner: TypeKind, source: &[u8]) -> MemberInfo {
+    let kind = match member.declaration.kind() {
+        "method_declaration" | "annotation_type_element_declaration" => MemberKind::Method,
+        "enum_constant" => MemberKind::EnumConstant,
+        _ => MemberKind::Field,
+    };
+    // Constants of interfaces and enums are static without a modifier
+    let is_static = has_modifier(member.declaration, "static")
+        || kind == MemberKind::EnumConstant
+        || (kind == MemberKind::Field
+            && matches!(owner, TypeKind::Interface | TypeKind::Annotation));
+
+    MemberInfo {
+        kind,
+        is_static,
+        deprecated: is_deprecated(member.declaration, source),
+    }
+}
+
+/// Types of the `extends` and `implements` clauses of a type declaration, the superclass
+/// comes first
+pub fn supertype_nodes(declaration: Node) -> Vec<Node> {
+    let mut cursor = declaration.walk();
+    let clauses = declaration
+        .children(&mut cursor)
+        .filter(|child| {
+            matches!(
+                child.kind(),
+                "superclass" | "super_interfaces" | "extends_interfaces"
+            )
+        })
+        .collect::<Vec<Node>>();
+
+    clauses
+        .into_iter()
+        .flat_map(|clause| {
+            // Interfaces are listed in a type list, the superclass is a type of its own
+            let node = clause
+                .named_child(0)
+                .filter(|child| child.kind() == "type_list")
+                .unwrap_or(clause);
+            let mut cursor = node.walk();
+            node.named_children(&mut cursor).collect::<Vec<Node>>()
+        })
+        .collect()
+}
+
+/// Whether the modifiers of `declaration` contain `modifier`, e.g. `static`
+pub fn has_modifier(declaration: Node, modifier: &str) -> bool {
+    modifiers(declaration)
+        .map(|modifiers| {
+            let mut cursor = modifiers.walk();
+            modifiers
+                .children(&mut cursor)
+                .any(|child| child.kind() == modifier)
+        })
+        .unwrap_or(false)
+}
+
+/// Whether `declaration` is annotated with `@Deprecated`
+pub fn is_deprecated(declaration: Node, source: &[u8]) -> bool {
+    modifiers(declaration)
+        .map(|modifiers| {
+            let mut cursor = modifiers.walk();
+            modifiers
+                .named_children(&mut cursor)
+                .filter(|child| matches!(child.kind(), "marker_annotation" | "annotation"))
+                .filter_map(|annotation| annotation.child_by_field_name("name"))
+                .filter_map(|name| name.utf8_text(source).ok())
+                .any(|name| name == "Deprecated" || name == "java.lang.Deprecated")
+        })
+        .unwrap_or(false)
+}
+
+fn modifiers(declaration: Node) -> Option<Node> {
+    let mut cursor = declaration.walk();
+    declaration
+        .named_children(&mut cursor)
+        .find(|child| child.kind() == "modifiers")
+}